@@ -1,10 +1,16 @@
-use crate::core::types::{Shape, DataType, Port, Dim, WorkspaceSlot};
+use crate::core::types::{Shape, DataType, Port, Dim, Layout, WorkspaceSlot};
 use crate::manifest::{Manifest, SourceDef};
 use crate::inliner::json::JsonGraph;
+use crate::resolver::ir::RuntimeAssertion;
+use crate::resolver::{ELLIPSIS_DIM, expand_ellipsis};
+use crate::core::utils::{find_cycle, json_parse_context};
 use std::collections::HashMap;
+use std::path::Path;
 use petgraph::algo::toposort;
 use anyhow::{Context, anyhow};
 
+mod unify;
+
 #[derive(Debug)]
 pub struct Resource {
     pub shape: Shape,
@@ -22,10 +28,15 @@ pub struct ProjectPlan {
     pub resources: HashMap<String, Resource>,
     pub programs: HashMap<String, ProgramInterface>,
     pub execution_order: Vec<String>,
+    pub execution_levels: Vec<Vec<String>>, // programs grouped by dependency depth, in execution order
     pub links: Vec<(String, String)>,
     pub synthetic_vars: HashMap<String, String>, // var_name -> C-expression
     pub workspace_info: HashMap<String, Vec<WorkspaceSlot>>, // prog_id -> list of internal buffers
+    pub runtime_assertions: HashMap<String, Vec<RuntimeAssertion>>, // prog_id -> checks on runtime dims
     pub program_graphs: HashMap<String, JsonGraph>, // Store parsed graphs to avoid re-parsing
+    pub input_defaults: HashMap<String, f32>, // "prog.port" -> fill value for unconnected inputs
+    pub output_layouts: HashMap<String, HashMap<String, Layout>>, // prog_id -> output port -> element order
+    pub display_source: Option<String>, // source with "type": "Display", blitted into the framebuffer each step
 }
 
 pub fn analyze_project(manifest: &Manifest, base_path: &std::path::Path) -> anyhow::Result<ProjectPlan> {
@@ -37,11 +48,37 @@ pub fn analyze_project(manifest: &Manifest, base_path: &std::path::Path) -> anyh
     // Default data type if not specified
     let default_dtype = DataType::F32;
 
+    let mut display_source = None;
     for (name, def) in &manifest.sources {
         let shape = resolve_source_shape(def, manifest, &mut synthetic_vars)?;
+        let dtype = match &def.dtype {
+            Some(dtype_name) => DataType::from_name(dtype_name)
+                .with_context(|| format!("Invalid dtype for source '{}'", name))?,
+            None => default_dtype,
+        };
+        if def.kind.as_deref().is_some_and(|kind| kind.eq_ignore_ascii_case("display")) {
+            if let Some(other) = &display_source {
+                return Err(anyhow!("Sources '{}' and '{}' are both marked as display; only one can be shown", other, name));
+            }
+            // [height, width] is grayscale, a static last axis of 3 or 4 holds RGB(A) channels
+            let channels_ok = match shape.dims.as_slice() {
+                [_, _] => true,
+                [_, _, Dim::Static(c)] => matches!(c, 1 | 3 | 4),
+                _ => false,
+            };
+            if !channels_ok {
+                return Err(anyhow!("Display source '{}' must have shape [height, width] or [height, width, 1|3|4]", name));
+            }
+            // The framebuffer is refreshed from the source every step, so something must write it
+            let target = format!("sources.{}", name);
+            if !manifest.links.iter().any(|(src, dst)| *dst == target && !src.starts_with("sources.")) {
+                return Err(anyhow!("Display source '{}' is not linked from any program output", name));
+            }
+            display_source = Some(name.clone());
+        }
         resources.insert(name.clone(), Resource {
             shape,
-            dtype: default_dtype,
+            dtype,
         });
     }
 
@@ -59,15 +96,28 @@ pub fn analyze_project(manifest: &Manifest, base_path: &std::path::Path) -> anyh
         
         let content = std::fs::read_to_string(&path)
             .with_context(|| format!("Failed to read program graph file: {}", path))?;
-        let json_graph: JsonGraph = serde_json::from_str(&content)
-            .with_context(|| format!("Failed to parse JSON graph: {}", path))?;
+        let json_graph = JsonGraph::from_json(&content)
+            .map_err(|e| json_parse_context(e, Path::new(&path), &content))?;
 
         let mut inputs = HashMap::new();
         for p in &json_graph.inputs {
+            // The declared shape only matters for unconnected inputs; links override it
+            let mut dims = Vec::new();
+            if let Some(js_dims) = &p.shape {
+                for js_dim in js_dims {
+                    dims.push(process_json_dim(js_dim, &mut synthetic_vars, manifest));
+                }
+            }
+            // A declared dtype is checked against the link, an undeclared one follows it
+            let dtype = match &p.dtype {
+                Some(dtype_name) => DataType::from_name(dtype_name)
+                    .with_context(|| format!("Invalid dtype for input '{}' of program '{}'", p.name, prog_def.id))?,
+                None => default_dtype,
+            };
             inputs.insert(p.name.clone(), Port { 
                 name: p.name.clone(), 
-                shape: Shape { dims: vec![] }, // Will be resolved via links
-                dtype: default_dtype 
+                shape: Shape { dims }, // Will be resolved via links
+                dtype 
             });
         }
 
@@ -80,10 +130,16 @@ pub fn analyze_project(manifest: &Manifest, base_path: &std::path::Path) -> anyh
                 }
             }
 
+            let dtype = match &p.dtype {
+                Some(dtype_name) => DataType::from_name(dtype_name)
+                    .with_context(|| format!("Invalid dtype for output '{}' of program '{}'", p.name, prog_def.id))?,
+                None => default_dtype,
+            };
+
             outputs.insert(p.name.clone(), Port { 
                 name: p.name.clone(), 
                 shape: Shape { dims }, 
-                dtype: default_dtype 
+                dtype 
             });
         }
 
@@ -98,8 +154,12 @@ pub fn analyze_project(manifest: &Manifest, base_path: &std::path::Path) -> anyh
     // ... (logic remains the same)
     let mut dep_graph = petgraph::graph::DiGraph::<String, ()>::new();
     let mut node_indices = HashMap::new();
+    let mut unifier = unify::DimUnifier::default();
 
-    for prog_id in programs.keys() {
+    // Sorted so that toposort, and therefore the generated code, is stable between runs
+    let mut prog_ids: Vec<_> = programs.keys().collect();
+    prog_ids.sort();
+    for prog_id in prog_ids {
         node_indices.insert(prog_id.clone(), dep_graph.add_node(prog_id.clone()));
     }
 
@@ -125,29 +185,108 @@ pub fn analyze_project(manifest: &Manifest, base_path: &std::path::Path) -> anyh
                     .cloned()
                     .ok_or_else(|| anyhow!("Output '{}' not found in program '{}'", src_port_name, src_prog))?
             };
+            // Undeclared program outputs only get their dtype once the program is resolved
+            let src_dtype_known = src_is_resource || declared_dtype(&program_graphs[&src_prog], &src_port_name, false);
 
-            if let Some(prog) = programs.get_mut(dst_prog_id) {
-                if let Some(target_port) = prog.inputs.get_mut(dst_port_name) {
-                    target_port.shape = src_port.shape;
+            if let Some(prog) = programs.get_mut(dst_prog_id)
+                && let Some(target_port) = prog.inputs.get_mut(dst_port_name) {
+                // A `...` in the declaration stands for however many leading dims the link brings
+                let declared = expand_ellipsis(&target_port.shape, &src_port.shape)
+                    .with_context(|| format!("Link '{}' -> '{}' does not fit the declared input shape", src_addr, dst_addr))?;
+                unifier.link(&src_port.shape, &declared, &format!("'{}' -> '{}'", src_addr, dst_addr))?;
+                target_port.shape = src_port.shape;
+                if declared_dtype(&program_graphs[dst_prog_id], dst_port_name, true) {
+                    if src_dtype_known && target_port.dtype != src_port.dtype {
+                        return Err(dtype_mismatch(src_addr, dst_addr, src_port.dtype, target_port.dtype));
+                    }
+                } else {
                     target_port.dtype = src_port.dtype;
                 }
             }
 
             // Add edge to dependency graph if it's a program-to-program link
-            if !src_is_resource {
-                if let (Some(&u), Some(&v)) = (node_indices.get(&src_prog), node_indices.get(dst_prog_id)) {
-                    if u != v {
-                        dep_graph.add_edge(u, v, ());
-                    }
-                }
+            if !src_is_resource
+                && let (Some(&u), Some(&v)) = (node_indices.get(&src_prog), node_indices.get(dst_prog_id))
+                && u != v {
+                dep_graph.add_edge(u, v, ());
             }
         }
     }
 
+    // Every interface and source shape refers to each linked dim by one canonical name
+    let source_symbols = resources.values()
+        .flat_map(|r: &Resource| &r.shape.dims)
+        .filter_map(|d| match d {
+            Dim::Variable(name) => Some(name.clone()),
+            Dim::Static(_) => None,
+        })
+        .collect();
+    let substitutions = unifier.substitutions(&synthetic_vars, &source_symbols);
+    if !substitutions.is_empty() {
+        for res in resources.values_mut() {
+            unify::substitute_shape(&mut res.shape, &substitutions);
+        }
+        for interface in programs.values_mut() {
+            for port in interface.inputs.values_mut().chain(interface.outputs.values_mut()) {
+                unify::substitute_shape(&mut port.shape, &substitutions);
+            }
+        }
+        for expr in synthetic_vars.values_mut() {
+            *expr = unify::substitute_expr(expr, &substitutions);
+        }
+    }
+
+    // Defaults are only allowed on existing inputs that nothing is linked to
+    let mut input_defaults = HashMap::new();
+    for (addr, value) in &manifest.defaults {
+        let (prog_id, port_name) = addr.split_once('.')
+            .ok_or_else(|| anyhow!("Invalid default target '{}', expected 'program.input'", addr))?;
+        let port = programs.get(prog_id)
+            .and_then(|p| p.inputs.get(port_name))
+            .ok_or_else(|| anyhow!("Default target '{}' is not a program input", addr))?;
+        if manifest.links.iter().any(|(_, dst)| dst == addr) {
+            return Err(anyhow!("Input '{}' is linked and also has a default value", addr));
+        }
+        if port.shape.dims.is_empty() {
+            return Err(anyhow!("Input '{}' needs a declared shape to be filled with a default value", addr));
+        }
+        if port.shape.dims.contains(&Dim::Variable(ELLIPSIS_DIM.to_string())) {
+            return Err(anyhow!("Input '{}' declares a '...' dim, which only a link can expand, so it cannot be filled with a default value", addr));
+        }
+        input_defaults.insert(addr.clone(), *value);
+    }
+
+    let mut output_layouts: HashMap<String, HashMap<String, Layout>> = HashMap::new();
+    for (addr, layout_name) in &manifest.layouts {
+        let (prog_id, port_name) = addr.split_once('.')
+            .ok_or_else(|| anyhow!("Invalid layout target '{}', expected 'program.output'", addr))?;
+        if !programs.get(prog_id).is_some_and(|p| p.outputs.contains_key(port_name)) {
+            return Err(anyhow!("Layout target '{}' is not a program output", addr));
+        }
+        let layout = Layout::from_name(layout_name)
+            .with_context(|| format!("Invalid layout for output '{}'", addr))?;
+        output_layouts.entry(prog_id.to_string()).or_default().insert(port_name.to_string(), layout);
+    }
+
     // Phase 3: Topological sort for execution order
     let order_indices = toposort(&dep_graph, None)
-        .map_err(|_| anyhow!("Circular dependency detected between programs in manifest links"))?;
+        .map_err(|_| anyhow!("Circular dependency detected between programs in manifest links: {}", find_cycle(&dep_graph, |id| id.clone()).join(", ")))?;
     
+    // A program sits one level below the deepest program feeding it
+    let mut depth = HashMap::new();
+    let mut execution_levels: Vec<Vec<String>> = Vec::new();
+    for &idx in &order_indices {
+        let level = dep_graph.neighbors_directed(idx, petgraph::Direction::Incoming)
+            .map(|u| depth[&u] + 1)
+            .max()
+            .unwrap_or(0);
+        depth.insert(idx, level);
+        if execution_levels.len() <= level {
+            execution_levels.resize(level + 1, Vec::new());
+        }
+        execution_levels[level].push(dep_graph[idx].clone());
+    }
+
     let execution_order = order_indices.into_iter()
         .map(|idx| dep_graph[idx].clone())
         .collect();
@@ -156,13 +295,60 @@ pub fn analyze_project(manifest: &Manifest, base_path: &std::path::Path) -> anyh
         resources,
         programs,
         execution_order,
+        execution_levels,
         links: manifest.links.clone(),
         synthetic_vars,
         workspace_info: HashMap::new(),
+        runtime_assertions: HashMap::new(),
         program_graphs,
+        input_defaults,
+        output_layouts,
+        display_source,
     })
 }
 
+/// Records the dtype a resolved program computes for `port` and carries it along the
+/// links leaving that output, so downstream inputs and the linker's buffers agree with it.
+pub fn propagate_output_dtype(plan: &mut ProjectPlan, prog_id: &str, port: &str, dtype: DataType) -> anyhow::Result<()> {
+    if let Some(output) = plan.programs.get_mut(prog_id).and_then(|p| p.outputs.get_mut(port)) {
+        output.dtype = dtype;
+    }
+    let src_addr = format!("{}.{}", prog_id, port);
+    for (src, dst) in &plan.links {
+        if *src != src_addr {
+            continue;
+        }
+        if let Some(res_id) = dst.strip_prefix("sources.") {
+            if let Some(res) = plan.resources.get(res_id)
+                && res.dtype != dtype {
+                return Err(dtype_mismatch(src, dst, dtype, res.dtype));
+            }
+            continue;
+        }
+        let Some((dst_prog_id, dst_port_name)) = dst.split_once('.') else {
+            continue;
+        };
+        let declared = plan.program_graphs.get(dst_prog_id).is_some_and(|g| declared_dtype(g, dst_port_name, true));
+        if let Some(target_port) = plan.programs.get_mut(dst_prog_id).and_then(|p| p.inputs.get_mut(dst_port_name)) {
+            if declared && target_port.dtype != dtype {
+                return Err(dtype_mismatch(src, dst, dtype, target_port.dtype));
+            }
+            target_port.dtype = dtype;
+        }
+    }
+    Ok(())
+}
+
+/// Whether the graph gives its input (or output) `port` an explicit dtype.
+fn declared_dtype(graph: &JsonGraph, port: &str, input: bool) -> bool {
+    let ports = if input { &graph.inputs } else { &graph.outputs };
+    ports.iter().any(|p| p.name == port && p.dtype.is_some())
+}
+
+fn dtype_mismatch(src_addr: &str, dst_addr: &str, src: DataType, dst: DataType) -> anyhow::Error {
+    anyhow!("Link '{}' -> '{}' carries {} values but '{}' is declared as {}", src_addr, dst_addr, src.name(), dst_addr, dst.name())
+}
+
 fn resolve_source_shape(
     def: &SourceDef, 
     manifest: &Manifest, 
@@ -192,33 +378,78 @@ pub fn process_json_dim(
     match js_dim {
         Value(v) => Dim::Static(*v),
         Symbol(s) => {
-            if s == "..." || s == "_" {
+            if s == ELLIPSIS_DIM {
+                Dim::Variable(ELLIPSIS_DIM.to_string())
+            } else if s == "_" {
                 Dim::Variable("dynamic".to_string())
             } else {
-                if let Some(p_val) = manifest.parameters.as_ref().and_then(|p| p.get(s)) {
-                    let is_dynamic = p_val.get("type").and_then(|t| t.as_str()) == Some("dynamic");
-                    if is_dynamic {
-                        Dim::Variable(s.clone())
-                    } else {
-                        let actual_val = p_val.get("value").unwrap_or(p_val);
-                        if let Some(u) = actual_val.as_u64() {
-                            Dim::Static(u as usize)
-                        } else {
-                            Dim::Variable(s.clone())
-                        }
-                    }
-                } else {
-                    Dim::Variable(s.clone())
+                match eval_static_dim(js_dim, manifest, &mut Vec::new()) {
+                    Some(v) => Dim::Static(v),
+                    None => Dim::Variable(param_alias(s, manifest)),
                 }
             }
         }
         Op(op) => {
+            if let Some(v) = eval_static_dim(js_dim, manifest, &mut Vec::new()) {
+                return Dim::Static(v);
+            }
             let c_expr = json_dim_op_to_c_expr(op);
             let var_name = format!("var_{}", hash_string(&c_expr));
             synthetic_vars.insert(var_name.clone(), c_expr);
             Dim::Variable(var_name)
         }
-        _ => Dim::Variable("dynamic".to_string()),
+        Ellipsis => Dim::Variable(ELLIPSIS_DIM.to_string()),
+        Wildcard => Dim::Variable("dynamic".to_string()),
+    }
+}
+
+/// The symbol a parameter stands for when its value names another parameter, e.g.
+/// `"M": { "value": "N" }` makes `M` another name for `N`. Chains are followed to the end.
+fn param_alias(name: &str, manifest: &Manifest) -> String {
+    let mut current = name.to_string();
+    let mut seen = vec![current.clone()];
+    while let Some(p_val) = manifest.parameters.as_ref().and_then(|params| params.get(&current))
+        && let Some(target) = p_val.get("value").unwrap_or(p_val).as_str()
+        && !seen.iter().any(|s| s == target) {
+        current = target.to_string();
+        seen.push(current.clone());
+    }
+    current
+}
+
+/// Folds a dim to a constant when every parameter it depends on is static.
+/// Parameter values may themselves be expressions over other parameters.
+fn eval_static_dim(
+    js_dim: &crate::inliner::json::JsonDim,
+    manifest: &Manifest,
+    visiting: &mut Vec<String>,
+) -> Option<usize> {
+    use crate::inliner::json::JsonDim::*;
+    use crate::inliner::json::JsonDimOp;
+    match js_dim {
+        Value(v) => Some(*v),
+        Symbol(s) => {
+            let p_val = manifest.parameters.as_ref()?.get(s)?;
+            if p_val.get("type").and_then(|t| t.as_str()) == Some("dynamic") || visiting.contains(s) {
+                return None;
+            }
+            let actual_val = p_val.get("value").unwrap_or(p_val);
+            if let Some(u) = actual_val.as_u64() {
+                return Some(u as usize);
+            }
+            let inner: crate::inliner::json::JsonDim = serde_json::from_value(actual_val.clone()).ok()?;
+            visiting.push(s.clone());
+            let result = eval_static_dim(&inner, manifest, visiting);
+            visiting.pop();
+            result
+        }
+        Op(op) => match op {
+            JsonDimOp::Add(a, b) => eval_static_dim(a, manifest, visiting)?.checked_add(eval_static_dim(b, manifest, visiting)?),
+            JsonDimOp::Sub(a, b) => eval_static_dim(a, manifest, visiting)?.checked_sub(eval_static_dim(b, manifest, visiting)?),
+            JsonDimOp::Mul(a, b) => eval_static_dim(a, manifest, visiting)?.checked_mul(eval_static_dim(b, manifest, visiting)?),
+            JsonDimOp::Div(a, b) => eval_static_dim(a, manifest, visiting)?.checked_div(eval_static_dim(b, manifest, visiting)?),
+        },
+        _ => None,
     }
 }
 
@@ -242,10 +473,20 @@ fn json_dim_to_c_expr(js_dim: &crate::inliner::json::JsonDim) -> String {
     }
 }
 
+/// 64-bit FNV-1a. Synthetic var names end up in generated code, so the hash must not
+/// change between toolchains the way `DefaultHasher` may.
+///
+/// ```
+/// // `N / 2` always gets this name, whichever process or toolchain compiles it
+/// let manifest = std::path::Path::new("tests/programs/split_symbolic/manifest.json");
+/// let opts = sion_flow_rt::CompileOptions::default();
+/// let artifacts = sion_flow_rt::compile_manifest(manifest, &opts).unwrap();
+/// let runtime = &artifacts.file("runtime.c").unwrap().contents;
+/// assert!(runtime.contains("var_767d4d50f03efcf7 = (N / 2);"), "{}", runtime);
+/// ```
 fn hash_string(s: &str) -> String {
-    use std::collections::hash_map::DefaultHasher;
-    use std::hash::{Hasher, Hash};
-    let mut hasher = DefaultHasher::new();
-    s.hash(&mut hasher);
-    format!("{:x}", hasher.finish())
+    const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+    const FNV_PRIME: u64 = 0x100000001b3;
+    let hash = s.bytes().fold(FNV_OFFSET_BASIS, |hash, byte| (hash ^ byte as u64).wrapping_mul(FNV_PRIME));
+    format!("{:x}", hash)
 }
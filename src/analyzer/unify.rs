@@ -0,0 +1,142 @@
+//! Symbolic dim unification. A link makes the dims of its source port equal to the dims its
+//! destination input declares, so `[N]` flowing into an input declared `[M]` means `M == N`.
+//! The equalities are joined into classes, each class settles on one canonical dim, and
+//! every other name in it is substituted away before code is generated.
+
+use crate::core::types::{Dim, Shape};
+use crate::core::utils::replace_ident;
+use crate::resolver::ELLIPSIS_DIM;
+use anyhow::anyhow;
+use std::collections::{HashMap, HashSet};
+
+/// Placeholder for `_` dims; like `...` it is not a real symbol.
+const DYNAMIC_DIM: &str = "dynamic";
+
+#[derive(Debug, Default)]
+pub struct DimUnifier {
+    parent: HashMap<String, String>,
+    /// Root symbol -> static value of its class and the link that fixed it
+    values: HashMap<String, (usize, String)>,
+}
+
+impl DimUnifier {
+    /// Records that `src` (the linked port's shape) and `dst` (the declared input shape)
+    /// agree dim by dim. Shapes of different rank, or an undeclared destination, imply nothing.
+    pub fn link(&mut self, src: &Shape, dst: &Shape, link: &str) -> anyhow::Result<()> {
+        if src.dims.len() != dst.dims.len() {
+            return Ok(());
+        }
+        for (a, b) in src.dims.iter().zip(&dst.dims) {
+            self.unify(a, b, link)?;
+        }
+        Ok(())
+    }
+
+    fn unify(&mut self, a: &Dim, b: &Dim, link: &str) -> anyhow::Result<()> {
+        match (a, b) {
+            (Dim::Static(x), Dim::Static(y)) => {
+                if x != y {
+                    return Err(anyhow!("Link {} connects a dim of size {} to one declared as {}", link, x, y));
+                }
+                Ok(())
+            }
+            (Dim::Variable(name), Dim::Static(value)) | (Dim::Static(value), Dim::Variable(name)) => {
+                if is_placeholder(name) {
+                    return Ok(());
+                }
+                let root = self.find(name);
+                self.fix(root, *value, link)
+            }
+            (Dim::Variable(x), Dim::Variable(y)) => {
+                if is_placeholder(x) || is_placeholder(y) {
+                    return Ok(());
+                }
+                let (rx, ry) = (self.find(x), self.find(y));
+                if rx == ry {
+                    return Ok(());
+                }
+                self.parent.insert(ry.clone(), rx.clone());
+                if let Some((value, fixed_by)) = self.values.remove(&ry) {
+                    self.fix(rx, value, &fixed_by)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn find(&mut self, name: &str) -> String {
+        let parent = self.parent.entry(name.to_string()).or_insert_with(|| name.to_string()).clone();
+        if parent == name {
+            return parent;
+        }
+        let root = self.find(&parent);
+        self.parent.insert(name.to_string(), root.clone());
+        root
+    }
+
+    fn fix(&mut self, root: String, value: usize, link: &str) -> anyhow::Result<()> {
+        match self.values.get(&root) {
+            Some((existing, fixed_by)) if *existing != value => Err(anyhow!(
+                "Link {} forces dim '{}' to be {}, but link {} already fixed it to {}",
+                link, root, value, fixed_by, existing
+            )),
+            Some(_) => Ok(()),
+            None => {
+                self.values.insert(root, (value, link.to_string()));
+                Ok(())
+            }
+        }
+    }
+
+    /// Maps every non-canonical symbol to the dim replacing it. A class with a static value
+    /// becomes that value; otherwise synthetic vars win (they carry an expression), then
+    /// symbols named by a source shape, then the alphabetically first name.
+    pub fn substitutions(mut self, synthetic: &HashMap<String, String>, source_symbols: &HashSet<String>) -> HashMap<String, Dim> {
+        let mut names: Vec<String> = self.parent.keys().cloned().collect();
+        names.sort();
+        let mut classes: HashMap<String, Vec<String>> = HashMap::new();
+        for name in names {
+            let root = self.find(&name);
+            classes.entry(root).or_default().push(name);
+        }
+
+        let mut map = HashMap::new();
+        for (root, members) in classes {
+            let canonical = match self.values.get(&root) {
+                Some((value, _)) => Dim::Static(*value),
+                None => {
+                    let rank = |name: &String| (!synthetic.contains_key(name), !source_symbols.contains(name));
+                    let best = members.iter().min_by_key(|name| rank(name)).expect("a class has a member");
+                    Dim::Variable(best.clone())
+                }
+            };
+            for name in members {
+                if canonical != Dim::Variable(name.clone()) {
+                    map.insert(name, canonical.clone());
+                }
+            }
+        }
+        map
+    }
+}
+
+fn is_placeholder(name: &str) -> bool {
+    name == DYNAMIC_DIM || name == ELLIPSIS_DIM
+}
+
+/// Rewrites `shape` through the substitution map.
+pub fn substitute_shape(shape: &mut Shape, map: &HashMap<String, Dim>) {
+    for dim in &mut shape.dims {
+        if let Dim::Variable(name) = dim
+            && let Some(canonical) = map.get(name) {
+            *dim = canonical.clone();
+        }
+    }
+}
+
+/// Rewrites the symbols a synthetic var's C expression refers to.
+pub fn substitute_expr(expr: &str, map: &HashMap<String, Dim>) -> String {
+    let mut names: Vec<_> = map.iter().collect();
+    names.sort_by_key(|(name, _)| name.as_str());
+    names.into_iter().fold(expr.to_string(), |expr, (name, canonical)| replace_ident(&expr, name, &canonical.to_c_expr()))
+}
@@ -0,0 +1,212 @@
+//! CUDA backend. Each node becomes one or more kernels (see `kernels::node_segments`).
+//! Module inputs are copied to the device on entry and outputs copied back on exit; all
+//! intermediate buffers stay on the device.
+
+use super::kernels::{collect_dim_vars, node_segments, Segment};
+use super::{constant_data_name, float_literal, generate_module_header, get_input_var, CompiledModule};
+use crate::core::op::Op;
+use crate::core::utils::sanitize_id;
+use crate::linearizer::ir::LinearIR;
+
+const PRELUDE: &str = "#include <cuda_runtime.h>
+#include <math.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+
+#define SION_CUDA_CHECK(call) do { \\
+    cudaError_t err = (call); \\
+    if (err != cudaSuccess) { \\
+        fprintf(stderr, \"CUDA error: %s at %s:%d\\n\", cudaGetErrorString(err), __FILE__, __LINE__); \\
+        exit(1); \\
+    } \\
+} while (0)
+#define SION_CUDA_BLOCK 256
+";
+
+/// Device buffers are grown on demand and kept for the lifetime of the process.
+const DEVICE_POOL: &str = "static void* sion_device_buffers[COUNT];
+static size_t sion_device_bytes[COUNT];
+
+static void* sion_device_buffer(int slot, size_t bytes) {
+    if (sion_device_bytes[slot] < bytes) {
+        if (sion_device_buffers[slot]) SION_CUDA_CHECK(cudaFree(sion_device_buffers[slot]));
+        SION_CUDA_CHECK(cudaMalloc(&sion_device_buffers[slot], bytes));
+        sion_device_bytes[slot] = bytes;
+    }
+    return sion_device_buffers[slot];
+}
+";
+
+pub fn compile_module(module_id: &str, ir: &LinearIR) -> anyhow::Result<CompiledModule> {
+    Ok(CompiledModule {
+        id: module_id.to_string(),
+        c_source: generate_module_source(module_id, ir)?,
+        h_source: generate_module_header(module_id, ir),
+        // Intermediates live in device memory owned by the module
+        workspace_slots: Vec::new(),
+    })
+}
+
+pub fn generate_module_source(module_id: &str, ir: &LinearIR) -> anyhow::Result<String> {
+    if let Some(port) = ir.inputs.iter().chain(ir.outputs.iter()).find(|p| p.dtype.is_emulated()) {
+        anyhow::bail!("CUDA backend does not support {:?} port '{}' in module '{}'", port.dtype, port.name, module_id);
+    }
+
+    let mut c = String::new();
+    c.push_str(PRELUDE);
+    c.push('\n');
+
+    let dim_vars = collect_dim_vars(ir);
+    for var in &dim_vars {
+        c.push_str(&"extern \"C\" int32_t VAR;\n".replace("VAR", var));
+    }
+    if !dim_vars.is_empty() {
+        c.push('\n');
+    }
+
+    // Every pointer a node body can refer to becomes a kernel parameter
+    let mut buffers: Vec<(&'static str, String)> = Vec::new();
+    let mut setup = String::new();
+    let mut slot_count = 0;
+    let mut device_slot = |setup: &mut String, c_type: &str, name: &str, size_expr: &str| {
+        let mut line = "    TYPE* NAME = (TYPE*)sion_device_buffer(SLOT, sizeof(TYPE) * (SIZE));\n".to_string();
+        line = line.replace("TYPE", c_type);
+        line = line.replace("NAME", name);
+        line = line.replace("SLOT", &slot_count.to_string());
+        line = line.replace("SIZE", size_expr);
+        setup.push_str(&line);
+        slot_count += 1;
+    };
+
+    for port in &ir.inputs {
+        let name = "in_NAME".replace("NAME", &sanitize_id(&port.name));
+        let c_type = port.dtype.to_c_type();
+        device_slot(&mut setup, c_type, &name, &port.shape.to_c_size_expr());
+        let mut copy = "    if (host_NAME) SION_CUDA_CHECK(cudaMemcpy(NAME, host_NAME, sizeof(TYPE) * (SIZE), cudaMemcpyHostToDevice));\n".to_string();
+        copy = copy.replace("NAME", &name);
+        copy = copy.replace("TYPE", c_type);
+        copy = copy.replace("SIZE", &port.shape.to_c_size_expr());
+        setup.push_str(&copy);
+        buffers.push((c_type, name));
+    }
+    for port in &ir.outputs {
+        let name = "out_NAME".replace("NAME", &sanitize_id(&port.name));
+        device_slot(&mut setup, port.dtype.to_c_type(), &name, &port.shape.to_c_size_expr());
+        buffers.push((port.dtype.to_c_type(), name));
+    }
+
+    for node in &ir.nodes {
+        let id = sanitize_id(&node.id);
+        let c_type = node.compute_dtype().to_c_type();
+        if let Some(out_name) = &node.output_alias {
+            let mut alias = "    TYPE* ID = out_NAME;\n".to_string();
+            alias = alias.replace("TYPE", c_type);
+            alias = alias.replace("ID", &id);
+            alias = alias.replace("NAME", &sanitize_id(out_name));
+            setup.push_str(&alias);
+        } else if let Op::Constant { values, .. } = &node.op {
+            let literals: Vec<String> = values.iter().map(|v| float_literal(*v)).collect();
+            let rows: Vec<String> = literals.chunks(8).map(|row| format!("    {}", row.join(", "))).collect();
+            let data = constant_data_name(module_id, &node.id);
+            let mut decl = "static __device__ TYPE DATA[COUNT] = {\nVALUES\n};\n".to_string();
+            decl = decl.replace("TYPE", c_type);
+            decl = decl.replace("DATA", &data);
+            decl = decl.replace("COUNT", &values.len().to_string());
+            decl = decl.replace("VALUES", &rows.join(",\n"));
+            c.push_str(&decl);
+
+            let mut addr = "    TYPE* ID = NULL;\n    SION_CUDA_CHECK(cudaGetSymbolAddress((void**)&ID, DATA));\n".to_string();
+            addr = addr.replace("TYPE", c_type);
+            addr = addr.replace("DATA", &data);
+            addr = addr.replace("ID", &id);
+            setup.push_str(&addr);
+        } else if matches!(node.op, Op::Flatten { .. }) {
+            let mut view = "    TYPE* ID = SRC;\n".to_string();
+            view = view.replace("TYPE", node.inputs[0].compute_dtype().to_c_type());
+            view = view.replace("ID", &id);
+            view = view.replace("SRC", &get_input_var(&node.inputs[0]));
+            setup.push_str(&view);
+        } else if node.has_workspace_slot() {
+            device_slot(&mut setup, c_type, &id, &node.padded_slot_size_expr());
+        } else {
+            continue;
+        }
+        buffers.push((c_type, id));
+    }
+    if ir.nodes.iter().any(|n| matches!(n.op, Op::Constant { .. })) {
+        c.push('\n');
+    }
+    c.push_str(&DEVICE_POOL.replace("COUNT", &slot_count.max(1).to_string()));
+    c.push('\n');
+
+    let params: Vec<String> = buffers.iter().map(|(ty, name)| format!("{}* {}", ty, name))
+        .chain(dim_vars.iter().map(|v| format!("int32_t {}", v)))
+        .collect();
+    let args: Vec<String> = buffers.iter().map(|(_, name)| name.clone())
+        .chain(dim_vars.iter().cloned())
+        .collect();
+    let params = params.join(", ");
+    let args = args.join(", ");
+
+    // Kernels
+    let mut launches = String::new();
+    for node in &ir.nodes {
+        let id = sanitize_id(&node.id);
+        for (k, segment) in node_segments(node, ir).iter().enumerate() {
+            let kernel_name = format!("k_{}_{}", id, k);
+            match segment {
+                Segment::Parallel { var, count, body } => {
+                    let mut kernel = "static __global__ void KERNEL(PARAMS) {\n    int LOOP_VAR = blockIdx.x * blockDim.x + threadIdx.x;\n    if (LOOP_VAR >= (COUNT)) return;\nBODY}\n\n".to_string();
+                    kernel = kernel.replace("KERNEL", &kernel_name);
+                    kernel = kernel.replace("LOOP_VAR", var);
+                    kernel = kernel.replace("COUNT", count);
+                    kernel = kernel.replace("PARAMS", &params);
+                    kernel = kernel.replace("BODY", body);
+                    c.push_str(&kernel);
+
+                    let mut launch = "    if ((COUNT) > 0) KERNEL<<<((COUNT) + SION_CUDA_BLOCK - 1) / SION_CUDA_BLOCK, SION_CUDA_BLOCK>>>(ARGS);\n".to_string();
+                    launch = launch.replace("COUNT", count);
+                    launch = launch.replace("KERNEL", &kernel_name);
+                    launch = launch.replace("ARGS", &args);
+                    launches.push_str(&launch);
+                }
+                Segment::Serial(body) => {
+                    let mut kernel = "static __global__ void KERNEL(PARAMS) {\nBODY}\n\n".to_string();
+                    kernel = kernel.replace("KERNEL", &kernel_name);
+                    kernel = kernel.replace("PARAMS", &params);
+                    kernel = kernel.replace("BODY", body);
+                    c.push_str(&kernel);
+
+                    launches.push_str(&"    KERNEL<<<1, 1>>>(ARGS);\n".replace("KERNEL", &kernel_name).replace("ARGS", &args));
+                }
+            }
+        }
+    }
+
+    // Host entry point, same signature as the C backend
+    let mut sig = vec!["void** workspace".to_string()];
+    for port in &ir.inputs {
+        sig.push(format!("const {}* host_in_{}", port.dtype.to_c_type(), sanitize_id(&port.name)));
+    }
+    for port in &ir.outputs {
+        sig.push(format!("{}* host_out_{}", port.dtype.to_c_type(), sanitize_id(&port.name)));
+    }
+    let mut func = "extern \"C\" void FUNC_NAME_func(ARGS) {\n    (void)workspace;\n".to_string();
+    func = func.replace("FUNC_NAME", module_id);
+    func = func.replace("ARGS", &sig.join(", "));
+    c.push_str(&func);
+    c.push_str(&setup);
+    c.push('\n');
+    c.push_str(&launches);
+    c.push_str("    SION_CUDA_CHECK(cudaGetLastError());\n\n");
+    for port in &ir.outputs {
+        let mut copy = "    SION_CUDA_CHECK(cudaMemcpy(host_out_NAME, out_NAME, sizeof(TYPE) * (SIZE), cudaMemcpyDeviceToHost));\n".to_string();
+        copy = copy.replace("NAME", &sanitize_id(&port.name));
+        copy = copy.replace("TYPE", port.dtype.to_c_type());
+        copy = copy.replace("SIZE", &port.shape.to_c_size_expr());
+        c.push_str(&copy);
+    }
+    c.push_str("}\n");
+    Ok(c)
+}
@@ -0,0 +1,131 @@
+//! Splits nodes into device kernels for the GPU backends. Node bodies come from the C
+//! emitter: every `#pragma omp parallel for` loop becomes a kernel with one work item per
+//! iteration and the remaining statements run as a single work item. MatMul gets a
+//! dedicated kernel with one work item per output element.
+
+use super::{emit_node_code, get_input_var, matmul_batch_indices, scalar_type, transposed_operand_reads, typed_literal, CodegenOptions};
+use crate::core::op::Op;
+use crate::core::types::Dim;
+use crate::core::utils::sanitize_id;
+use crate::linearizer::ir::{LinearIR, LinearNode};
+use std::collections::BTreeSet;
+
+/// A piece of a node's C body, run as one kernel launch.
+pub enum Segment {
+    /// An independent loop `for (int VAR = 0; VAR < COUNT; VAR++) { BODY }`.
+    Parallel { var: String, count: String, body: String },
+    Serial(String),
+}
+
+pub fn node_segments(node: &LinearNode, ir: &LinearIR) -> Vec<Segment> {
+    if matches!(node.op, Op::MatMul) && node.inputs[1].shape.dims.len() >= 2 {
+        return vec![matmul_segment(node)];
+    }
+    let mut code = String::new();
+    emit_node_code(&mut code, node, ir, &CodegenOptions { blas: false, parallel_threshold: 0, unroll_threshold: 0, debug_checks: false, split_functions: 0, line_directives: false });
+    split_segments(&code)
+}
+
+/// One thread per output element, accumulating in a register.
+fn matmul_segment(node: &LinearNode) -> Segment {
+    let a_shape = &node.inputs[0].shape;
+    let b_shape = &node.inputs[1].shape;
+    let mut body = "    int b = idx / ((GEMM_M) * (GEMM_N));\n    int i = (idx / (GEMM_N)) % (GEMM_M);\n    int j = idx % (GEMM_N);\n    SCALAR acc = ZERO;\n    for (int l = 0; l < GEMM_K; l++) {\n        acc += LEFT[BATCH_A * GEMM_M * GEMM_K + i * GEMM_K + l] * RIGHT[BATCH_B * GEMM_K * GEMM_N + l * GEMM_N + j];\n    }\n    VAR[idx] = acc;\n".to_string();
+    body = transposed_operand_reads(body, node, ("GEMM_M", "GEMM_K", "GEMM_N"));
+    body = body.replace("SCALAR", scalar_type(node.dtype));
+    body = body.replace("ZERO", &typed_literal(0.0, node.dtype));
+    body = body.replace("GEMM_M", &a_shape.dims[a_shape.dims.len() - 2].to_c_expr());
+    body = body.replace("GEMM_K", &a_shape.dims[a_shape.dims.len() - 1].to_c_expr());
+    body = body.replace("GEMM_N", &b_shape.dims[b_shape.dims.len() - 1].to_c_expr());
+    let (batch_a, batch_b) = matmul_batch_indices(node);
+    body = body.replace("BATCH_A", &batch_a);
+    body = body.replace("BATCH_B", &batch_b);
+    body = body.replace("VAR", &sanitize_id(&node.id));
+    body = body.replace("LEFT", &get_input_var(&node.inputs[0]));
+    body = body.replace("RIGHT", &get_input_var(&node.inputs[1]));
+    Segment::Parallel { var: "idx".to_string(), count: node.shape.to_c_size_expr(), body }
+}
+
+/// Splits C node code into kernel launches at its `#pragma omp parallel for` loops.
+fn split_segments(code: &str) -> Vec<Segment> {
+    let mut segments = Vec::new();
+    let mut serial = String::new();
+    let mut rest = code;
+
+    while !rest.is_empty() {
+        let (line, tail) = rest.split_once('\n').unwrap_or((rest, ""));
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("#pragma omp parallel for")
+            && let Some((var, count, body, after)) = parse_parallel_loop(tail) {
+            flush_serial(&mut segments, &mut serial);
+            segments.push(Segment::Parallel { var, count, body });
+            rest = after;
+            continue;
+        }
+        if !trimmed.starts_with("#pragma") {
+            serial.push_str(line);
+            serial.push('\n');
+        }
+        rest = tail;
+    }
+    flush_serial(&mut segments, &mut serial);
+    segments
+}
+
+fn flush_serial(segments: &mut Vec<Segment>, serial: &mut String) {
+    // Comment-only code (inputs, in-place outputs) needs no launch
+    if serial.lines().any(|l| !l.trim().is_empty() && !l.trim_start().starts_with("//")) {
+        segments.push(Segment::Serial(serial.clone()));
+    }
+    serial.clear();
+}
+
+/// Parses `for (int VAR = 0; VAR < COUNT; VAR++) { BODY }` at the start of `code`,
+/// returning the loop variable, trip count, body and the code after the loop.
+fn parse_parallel_loop(code: &str) -> Option<(String, String, String, &str)> {
+    let header = code.trim_start().strip_prefix("for (int ")?;
+    let (var, header) = header.split_once(" = 0; ")?;
+    let header = header.strip_prefix(var)?.strip_prefix(" < ")?;
+    let (count, header) = header.split_once(&format!("; {}++) {{", var))?;
+
+    let mut depth = 1;
+    for (pos, ch) in header.char_indices() {
+        match ch {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    let body = header[..pos].trim_matches(' ');
+                    let body = match body.strip_prefix('\n') {
+                        Some(block) => block.to_string(),
+                        None => format!("    {}\n", body),
+                    };
+                    let after = header[pos + 1..].split_once('\n').map(|(_, t)| t).unwrap_or("");
+                    return Some((var.to_string(), count.to_string(), body, after));
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Identifiers used by symbolic dims; they are runtime globals passed to every kernel.
+pub fn collect_dim_vars(ir: &LinearIR) -> BTreeSet<String> {
+    let mut vars = BTreeSet::new();
+    let shapes = ir.nodes.iter()
+        .flat_map(|n| std::iter::once(&n.shape).chain(n.inputs.iter().map(|i| &i.shape)))
+        .chain(ir.inputs.iter().chain(ir.outputs.iter()).map(|p| &p.shape));
+    for shape in shapes {
+        for dim in &shape.dims {
+            if let Dim::Variable(expr) = dim {
+                for ident in expr.split(|ch: char| !(ch.is_ascii_alphanumeric() || ch == '_')) {
+                    if ident.starts_with(|ch: char| ch.is_ascii_alphabetic() || ch == '_') {
+                        vars.insert(ident.to_string());
+                    }
+                }
+            }
+        }
+    }
+    vars
+}
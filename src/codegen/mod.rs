@@ -1,44 +1,399 @@
-use crate::linearizer::ir::{LinearIR, LinearNode, InputConnection};
+use crate::linearizer::ir::{LinearIR, LinearNode, InputConnection, WORKSPACE_ALIGNMENT};
 use crate::core::op::Op;
-use crate::core::utils::sanitize_id;
+use crate::core::utils::{replace_ident, sanitize_id};
+use crate::core::types::{Shape, Dim, DataType, Layout, WorkspaceSlot};
+use std::collections::{HashMap, HashSet};
 
-pub fn generate_module_source(module_id: &str, ir: &LinearIR) -> String {
+/// `sion_half` storage type with float conversions. Uses the compiler's `_Float16` when
+/// `SION_USE_FLOAT16` is defined, otherwise a portable round-to-nearest-even emulation.
+pub const HALF_SUPPORT: &str = r#"#ifndef SION_HALF_SUPPORT
+#define SION_HALF_SUPPORT
+typedef uint16_t sion_half;
+#ifdef SION_USE_FLOAT16
+static inline float sion_half_to_float(sion_half h) {
+    union { sion_half h; _Float16 f; } u = { h };
+    return (float)u.f;
+}
+static inline sion_half sion_float_to_half(float v) {
+    union { _Float16 f; sion_half h; } u = { (_Float16)v };
+    return u.h;
+}
+#else
+static inline float sion_half_to_float(sion_half h) {
+    union { uint32_t u; float f; } out;
+    uint32_t sign = (uint32_t)(h & 0x8000) << 16;
+    uint32_t exp = (h >> 10) & 0x1F;
+    uint32_t mant = h & 0x3FF;
+    if (exp == 0) {
+        out.f = (float)mant * 5.9604644775390625e-8f;
+        out.u |= sign;
+    } else if (exp == 31) {
+        out.u = sign | 0x7F800000u | (mant << 13);
+    } else {
+        out.u = sign | ((exp + 112) << 23) | (mant << 13);
+    }
+    return out.f;
+}
+static inline sion_half sion_float_to_half(float v) {
+    union { float f; uint32_t u; } in = { v };
+    union { uint32_t u; float f; } denorm_magic = { 126u << 23 };
+    uint32_t sign = in.u & 0x80000000u;
+    uint16_t out;
+    in.u ^= sign;
+    if (in.u >= 0x47800000u) {
+        out = in.u > 0x7F800000u ? 0x7E00 : 0x7C00;
+    } else if (in.u < 0x38800000u) {
+        in.f += denorm_magic.f;
+        out = (uint16_t)(in.u - denorm_magic.u);
+    } else {
+        uint32_t mant_odd = (in.u >> 13) & 1;
+        in.u += 0xC8000FFFu + mant_odd;
+        out = (uint16_t)(in.u >> 13);
+    }
+    return out | (uint16_t)(sign >> 16);
+}
+#endif
+#endif
+"#;
+
+/// Workspace slots come from the runtime's aligned allocator, which lets the compiler
+/// vectorize without peeling.
+const ASSUME_ALIGNED: &str = "#ifndef SION_ASSUME_ALIGNED
+#if defined(__GNUC__) || defined(__clang__)
+#define SION_ASSUME_ALIGNED(p) __builtin_assume_aligned((p), ALIGNMENT)
+#else
+#define SION_ASSUME_ALIGNED(p) (p)
+#endif
+#endif
+";
+
+/// Helpers for `--debug-codegen`: `SION_AT` is a checked buffer access and
+/// `SION_CHECK_FINITE` scans a node's result, both abort with the node id.
+const DEBUG_CHECKS: &str = r#"#ifndef SION_DEBUG_CHECKS
+#define SION_DEBUG_CHECKS
+#include <stdio.h>
+#include <stdlib.h>
+static inline long long sion_check_index(long long idx, long long size, const char* buf, const char* node) {
+    if (idx < 0 || idx >= size) {
+        fprintf(stderr, "Index %lld out of bounds for %s (size %lld) in node '%s'\n", idx, buf, size, node);
+        abort();
+    }
+    return idx;
+}
+#define SION_AT(buf, idx, size, node) buf[sion_check_index((idx), (size), #buf, node)]
+#define SION_CHECK_FINITE(buf, size, node) do { \
+    for (long long sion_k = 0; sion_k < (long long)(size); sion_k++) { \
+        if (!isfinite((double)buf[sion_k])) { \
+            fprintf(stderr, "Non-finite value %f at index %lld of node '%s'\n", (double)buf[sion_k], sion_k, node); \
+            abort(); \
+        } \
+    } \
+} while (0)
+#endif
+"#;
+
+#[derive(Debug, Clone)]
+pub struct CompiledModule {
+    pub id: String,
+    pub c_source: String,
+    pub h_source: String,
+    pub workspace_slots: Vec<WorkspaceSlot>,
+}
+
+pub mod cuda;
+mod kernels;
+pub mod opencl;
+pub mod rust;
+
+/// Code generation target, selected by the manifest's `backend` field or `--backend`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Backend {
+    #[default]
+    C,
+    Cuda,
+    OpenCl,
+    Rust,
+}
+
+impl Backend {
+    /// Name accepted back by `from_name`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Backend::C => "c",
+            Backend::Cuda => "cuda",
+            Backend::OpenCl => "opencl",
+            Backend::Rust => "rust",
+        }
+    }
+
+    pub fn from_name(name: &str) -> anyhow::Result<Backend> {
+        match name {
+            "c" => Ok(Backend::C),
+            "cuda" => Ok(Backend::Cuda),
+            "opencl" => Ok(Backend::OpenCl),
+            "rust" => Ok(Backend::Rust),
+            other => Err(anyhow::anyhow!("Unknown backend '{}' (expected \"c\", \"cuda\", \"opencl\" or \"rust\")", other)),
+        }
+    }
+}
+
+/// Static element count below which a node's loops run serially, since spawning an
+/// OpenMP team costs more than the work itself.
+pub const DEFAULT_PARALLEL_THRESHOLD: usize = 4096;
+
+/// Static element count up to which single-statement elementwise loops are written out
+/// as straight-line assignments.
+pub const DEFAULT_UNROLL_THRESHOLD: usize = 8;
+
+#[derive(Debug, Clone)]
+pub struct CodegenOptions {
+    /// Lower static MatMuls to `cblas_sgemm` calls.
+    pub blas: bool,
+    /// Nodes with a static size below this keep `simd` but drop `parallel for`.
+    pub parallel_threshold: usize,
+    /// Elementwise loops over at most this many static elements are fully unrolled (0 disables).
+    pub unroll_threshold: usize,
+    /// Route buffer accesses through `SION_AT` and scan every float result for NaN/Inf.
+    pub debug_checks: bool,
+    /// Emit the module as sub-functions of at most this many nodes each (0 disables).
+    pub split_functions: usize,
+    /// Precede each node's code with a `#line` pointing at its definition in the graph file.
+    pub line_directives: bool,
+}
+
+impl Default for CodegenOptions {
+    fn default() -> Self {
+        Self {
+            blas: false,
+            parallel_threshold: DEFAULT_PARALLEL_THRESHOLD,
+            unroll_threshold: DEFAULT_UNROLL_THRESHOLD,
+            debug_checks: false,
+            split_functions: 0,
+            line_directives: false,
+        }
+    }
+}
+
+/// Generates the source and header of one module function.
+pub fn compile_module(module_id: &str, ir: &LinearIR, opts: &CodegenOptions) -> CompiledModule {
+    CompiledModule {
+        id: module_id.to_string(),
+        c_source: generate_module_source(module_id, ir, opts),
+        h_source: generate_module_header(module_id, ir),
+        workspace_slots: ir.get_workspace_slots(),
+    }
+}
+
+/// C source of one module: its constants, the node loops and the module function.
+pub fn generate_module_source(module_id: &str, ir: &LinearIR, opts: &CodegenOptions) -> String {
     let mut c = String::new();
     
     // Header includes
     c.push_str("#include \"MOD_ID.h\"\n".replace("MOD_ID", module_id).as_str());
     c.push_str("#include <math.h>\n");
-    c.push_str("#ifdef _OPENMP\n#include <omp.h>\n#endif\n\n");
+    if opts.blas && ir.nodes.iter().any(is_static_gemm) {
+        c.push_str("#include <cblas.h>\n");
+    }
+    c.push_str("#ifdef _OPENMP\n#include <omp.h>\n#endif\n");
+    c.push_str(&ASSUME_ALIGNED.replace("ALIGNMENT", &WORKSPACE_ALIGNMENT.to_string()));
+    if opts.debug_checks {
+        c.push_str(DEBUG_CHECKS);
+    }
+    c.push('\n');
+
+    // Constant data at file scope, initialized once by the loader
+    for node in &ir.nodes {
+        if let Op::Constant { values, .. } = &node.op {
+            let literals: Vec<String> = values.iter().map(|v| float_literal(*v)).collect();
+            let rows: Vec<String> = literals.chunks(8).map(|row| format!("    {}", row.join(", "))).collect();
+            let mut decl = "static const TYPE DATA[COUNT] = {\nVALUES\n};\n".to_string();
+            decl = decl.replace("TYPE", node.dtype.to_c_type());
+            decl = decl.replace("DATA", &constant_data_name(module_id, &node.id));
+            decl = decl.replace("COUNT", &values.len().to_string());
+            decl = decl.replace("VALUES", &rows.join(",\n"));
+            c.push_str(&decl);
+        }
+    }
+    if ir.nodes.iter().any(|n| matches!(n.op, Op::Constant { .. })) {
+        c.push('\n');
+    }
 
     let args = get_function_args(ir);
-    let mut func_sig = "void FUNC_NAME_func(ARGS) { 
-".to_string();
+    let buffer_sizes = if opts.debug_checks { buffer_sizes(ir) } else { HashMap::new() };
+    if opts.split_functions == 0 || ir.nodes.len() <= opts.split_functions {
+        let mut func_sig = "void FUNC_NAME_func(ARGS) { \n".to_string();
+        func_sig = func_sig.replace("FUNC_NAME", module_id);
+        func_sig = func_sig.replace("ARGS", &args.join(", "));
+        c.push_str(&func_sig);
+        emit_locals(&mut c, module_id, ir, |_| true);
+        c.push('\n');
+        for node in &ir.nodes {
+            c.push_str(&node_block(node, ir, opts, &buffer_sizes));
+        }
+        c.push_str("}\n");
+        return c;
+    }
+
+    // Each part is its own function so gcc optimizes (and allocates registers for) a bounded
+    // amount of code at a time; parts declare only the locals their nodes touch
+    let mut calls = String::new();
+    let call_args = get_call_args(ir).join(", ");
+    for (idx, part) in ir.nodes.chunks(opts.split_functions).enumerate() {
+        let mut func_sig = "static void FUNC_NAME_func_partIDX(ARGS) {\n".to_string();
+        func_sig = func_sig.replace("FUNC_NAME", module_id);
+        func_sig = func_sig.replace("IDX", &idx.to_string());
+        func_sig = func_sig.replace("ARGS", &args.join(", "));
+        c.push_str(&func_sig);
+        let locals = part_locals(ir, part);
+        emit_locals(&mut c, module_id, ir, |node| locals.contains(node.id.as_str()));
+        c.push('\n');
+        for node in part {
+            c.push_str(&node_block(node, ir, opts, &buffer_sizes));
+        }
+        c.push_str("}\n\n");
+
+        let mut call = "    FUNC_NAME_func_partIDX(CALL_ARGS);\n".to_string();
+        call = call.replace("FUNC_NAME", module_id);
+        call = call.replace("IDX", &idx.to_string());
+        call = call.replace("CALL_ARGS", &call_args);
+        calls.push_str(&call);
+    }
+    let mut func_sig = "void FUNC_NAME_func(ARGS) {\n".to_string();
     func_sig = func_sig.replace("FUNC_NAME", module_id);
     func_sig = func_sig.replace("ARGS", &args.join(", "));
     c.push_str(&func_sig);
+    c.push_str(&calls);
+    c.push_str("}\n");
+    c
+}
 
-    // Workspace pointers casting
-    for node in &ir.nodes {
-        if matches!(node.op, Op::Input { .. } | Op::Output { .. }) { continue; }
-        let c_type = node.dtype.to_c_type();
+/// Pointer locals for the nodes accepted by `keep`: output aliases, constant data, Flatten
+/// views and workspace slots.
+fn emit_locals(c: &mut String, module_id: &str, ir: &LinearIR, keep: impl Fn(&LinearNode) -> bool) {
+    for node in ir.nodes.iter().filter(|node| keep(node)) {
+        if let Some(out_name) = &node.output_alias {
+            let mut alias = "    TYPE* restrict ID = out_NAME;\n".to_string();
+            alias = alias.replace("TYPE", node.dtype.to_c_type());
+            alias = alias.replace("ID", &sanitize_id(&node.id));
+            alias = alias.replace("NAME", &sanitize_id(out_name));
+            c.push_str(&alias);
+            continue;
+        }
+        if matches!(node.op, Op::Constant { .. }) {
+            let mut alias = "    const TYPE* restrict ID = DATA;\n".to_string();
+            alias = alias.replace("TYPE", node.dtype.to_c_type());
+            alias = alias.replace("DATA", &constant_data_name(module_id, &node.id));
+            alias = alias.replace("ID", &sanitize_id(&node.id));
+            c.push_str(&alias);
+            continue;
+        }
+        if matches!(node.op, Op::Flatten { .. }) {
+            // A view aliases its source buffer, so it takes that buffer's type
+            let mut view = "    const TYPE* ID = SRC;\n".to_string();
+            view = view.replace("TYPE", node.inputs[0].compute_dtype().to_c_type());
+            view = view.replace("ID", &sanitize_id(&node.id));
+            view = view.replace("SRC", &get_input_var(&node.inputs[0]));
+            c.push_str(&view);
+            continue;
+        }
+        if !node.has_workspace_slot() { continue; }
+        let c_type = node.compute_dtype().to_c_type();
         let id = sanitize_id(&node.id);
-        let mut cast = "    TYPE* restrict ID = (TYPE*)workspace[OFFSET];\n".to_string();
+        let mut cast = "    TYPE* restrict ID = (TYPE*)SION_ASSUME_ALIGNED(workspace[OFFSET]);\n".to_string();
         cast = cast.replace("TYPE", c_type);
         cast = cast.replace("ID", &id);
         cast = cast.replace("OFFSET", &node.offset.to_string());
         c.push_str(&cast);
     }
+}
+
+/// Ids of the nodes whose locals `part` reads or writes, following Flatten views back to the
+/// buffer they alias.
+fn part_locals<'a>(ir: &'a LinearIR, part: &'a [LinearNode]) -> HashSet<&'a str> {
+    let views: HashMap<&str, &str> = ir.nodes.iter()
+        .filter(|node| matches!(node.op, Op::Flatten { .. }))
+        .map(|node| (node.id.as_str(), node.inputs[0].node_id.as_str()))
+        .collect();
+    let mut locals = HashSet::new();
+    for node in part {
+        let ids = std::iter::once(node.id.as_str()).chain(node.inputs.iter().map(|input| input.node_id.as_str()));
+        for mut id in ids {
+            while locals.insert(id) && let Some(src) = views.get(id) {
+                id = src;
+            }
+        }
+    }
+    locals
+}
 
-    c.push_str("\n");
+/// Loop code for one node after the pragma, unrolling and debug-check rewrites, optionally
+/// preceded by a `#line` back to its graph file.
+fn node_block(node: &LinearNode, ir: &LinearIR, opts: &CodegenOptions, buffer_sizes: &HashMap<String, String>) -> String {
+    let mut block = String::new();
+    if opts.line_directives
+        && let Some(origin) = &node.origin {
+        let mut line = "#line LINE \"FILE\"\n".to_string();
+        line = line.replace("LINE", &origin.line.to_string());
+        line = line.replace("FILE", &origin.file.replace('\\', "/").replace('"', "\\\""));
+        block.push_str(&line);
+    }
 
-    for node in &ir.nodes {
-        emit_node_code(&mut c, node, ir);
+    let mut code = String::new();
+    emit_node_code(&mut code, node, ir, opts);
+    // Symbolic sizes are unknown until runtime and keep their pragmas
+    if node.shape.static_size().is_some_and(|size| size < opts.parallel_threshold) {
+        code = strip_parallel_pragmas(&code);
+    }
+    if let Some(size) = node.shape.static_size().filter(|&size| size <= opts.unroll_threshold) {
+        code = unroll_small_loops(&code, &node.shape.to_c_size_expr(), size);
     }
+    if opts.debug_checks {
+        code = checked_accesses(&code, buffer_sizes, &node.id);
+        if let Some(size) = finite_scan_size(node) {
+            let mut scan = "    SION_CHECK_FINITE(VAR, SIZE, \"NODE\");\n".to_string();
+            scan = scan.replace("VAR", &sanitize_id(&node.id));
+            scan = scan.replace("SIZE", &size);
+            scan = scan.replace("NODE", &node.id.replace('"', "'"));
+            code.push_str(&scan);
+        }
+    }
+    block.push_str(&code);
+    block
+}
 
-    c.push_str("}\n");
-    c
+/// `// node: <id> (<op>) shape: [..]` with the unsanitized, hierarchical node id, followed
+/// by the node's graph metadata when it has any.
+fn source_comment(node: &LinearNode) -> String {
+    let dims: Vec<String> = node.shape.dims.iter().map(|d| d.to_c_expr()).collect();
+    let mut comment = "    // node: ID (OP) shape: [DIMS]\n".to_string();
+    comment = comment.replace("DIMS", &dims.join(", "));
+    comment = comment.replace("OP", node.op.name());
+    comment = comment.replace("ID", &node.id.replace(['\n', '\r'], " "));
+    if let Some(metadata) = &node.metadata {
+        comment.push_str(&"    // metadata: JSON\n".replace("JSON", &metadata.to_string()));
+    }
+    comment
+}
+
+/// Rejects a program whose node or port ids would share a C variable name. `sanitize_id`
+/// is injective, but the `in_`/`out_` prefixes of ports can still meet a node id.
+pub fn check_c_names(ir: &LinearIR) -> anyhow::Result<()> {
+    let ports = ir.inputs.iter().map(|port| (format!("in_{}", sanitize_id(&port.name)), format!("inputs.{}", port.name)))
+        .chain(ir.outputs.iter().map(|port| (format!("out_{}", sanitize_id(&port.name)), format!("outputs.{}", port.name))));
+    let nodes = ir.nodes.iter().map(|node| (sanitize_id(&node.id), node.id.clone()));
+
+    let mut seen: HashMap<String, String> = HashMap::new();
+    for (name, id) in ports.chain(nodes) {
+        if let Some(other) = seen.get(&name)
+            && *other != id {
+            anyhow::bail!("Ids '{}' and '{}' both map to the C identifier '{}'", other, id, name);
+        }
+        seen.insert(name, id);
+    }
+    Ok(())
 }
 
+/// Declares the module function; `module_id` must already be a C identifier (see `sanitize_id`).
 pub fn generate_module_header(module_id: &str, ir: &LinearIR) -> String {
     let mut c = String::new();
     let guard = "MOD_ID_H".replace("MOD_ID", &module_id.to_uppercase());
@@ -47,8 +402,13 @@ pub fn generate_module_header(module_id: &str, ir: &LinearIR) -> String {
     header = header.replace("GUARD", &guard);
     c.push_str(&header);
 
+    if ir.inputs.iter().chain(ir.outputs.iter()).any(|p| p.dtype == DataType::F16) {
+        c.push_str(HALF_SUPPORT);
+        c.push('\n');
+    }
+
     let args = get_function_args(ir);
-    let mut decl = "void FUNC_NAME_func(ARGS);\n\n".to_string();
+    let mut decl = "#ifdef __cplusplus\nextern \"C\" {\n#endif\n\nvoid FUNC_NAME_func(ARGS);\n\n#ifdef __cplusplus\n}\n#endif\n\n".to_string();
     decl = decl.replace("FUNC_NAME", module_id);
     decl = decl.replace("ARGS", &args.join(", "));
     c.push_str(&decl);
@@ -77,75 +437,166 @@ fn get_function_args(ir: &LinearIR) -> Vec<String> {
     args
 }
 
-fn emit_node_code(c: &mut String, node: &LinearNode, _ir: &LinearIR) {
+/// Argument names matching `get_function_args`, for calling one module function from another.
+fn get_call_args(ir: &LinearIR) -> Vec<String> {
+    std::iter::once("workspace".to_string())
+        .chain(ir.inputs.iter().map(|port| "in_NAME".replace("NAME", &sanitize_id(&port.name))))
+        .chain(ir.outputs.iter().map(|port| "out_NAME".replace("NAME", &sanitize_id(&port.name))))
+        .collect()
+}
+
+fn emit_node_code(c: &mut String, node: &LinearNode, ir: &LinearIR, opts: &CodegenOptions) {
+    c.push_str(&source_comment(node));
     let node_var = sanitize_id(&node.id);
     let size_expr = node.shape.to_c_size_expr();
 
     match &node.op {
+        Op::Input { name } if node.dtype == DataType::F16 => {
+            let mut line = "    #pragma omp parallel for simd\n    for (int i = 0; i < SIZE; i++) { VAR[i] = sion_half_to_float(in_NAME[i]); }\n".to_string();
+            line = line.replace("SIZE", &size_expr);
+            line = line.replace("VAR", &node_var);
+            line = line.replace("NAME", &sanitize_id(name));
+            c.push_str(&line);
+        }
         Op::Input { name } => {
             c.push_str("    // Input NAME handled via args\n".replace("NAME", name).as_str());
         }
-        Op::Constant { values } => {
-            for (i, v) in values.iter().enumerate() {
-                let mut line = "    VAR[IDX] = VALf;\n".to_string();
-                line = line.replace("VAR", &node_var);
-                line = line.replace("IDX", &i.to_string());
-                line = line.replace("VAL", &v.to_string());
-                c.push_str(&line);
-            }
+        Op::Constant { .. } => {
+            c.push_str("    // Constant ID is static data\n".replace("ID", &node.id).as_str());
+        }
+        Op::RandomUniform { low, high, seed, .. } => {
+            let mut line = "    {\n        uint64_t rng = RNG_SEED;\n        for (int i = 0; i < SIZE; i++) {\n            XORSHIFT\n            float u = (float)(rng >> 40) * (1.0f / 16777216.0f);\n            VAR[i] = LOW + (HIGH - LOW) * u;\n        }\n    }\n".to_string();
+            line = line.replace("XORSHIFT", XORSHIFT_STEP);
+            line = line.replace("RNG_SEED", &rng_seed(*seed));
+            line = line.replace("SIZE", &size_expr);
+            line = line.replace("VAR", &node_var);
+            line = line.replace("LOW", &float_literal(*low));
+            line = line.replace("HIGH", &float_literal(*high));
+            c.push_str(&line);
+        }
+        Op::Iota { .. } => {
+            let mut line = "    #pragma omp parallel for simd\n    for (int i = 0; i < SIZE; i++) { VAR[i] = (TYPE)i; }\n".to_string();
+            line = line.replace("SIZE", &size_expr);
+            line = line.replace("VAR", &node_var);
+            line = line.replace("TYPE", node.dtype.to_c_type());
+            c.push_str(&line);
+        }
+        Op::RandomNormal { mean, std, seed, .. } => {
+            // Box-Muller transform over two uniform samples in (0, 1]
+            let mut line = "    {\n        uint64_t rng = RNG_SEED;\n        for (int i = 0; i < SIZE; i++) {\n            XORSHIFT\n            float u1 = (float)((rng >> 40) + 1) * (1.0f / 16777216.0f);\n            XORSHIFT\n            float u2 = (float)(rng >> 40) * (1.0f / 16777216.0f);\n            VAR[i] = MEAN + STD * sqrtf(-2.0f * logf(u1)) * cosf(6.2831853f * u2);\n        }\n    }\n".to_string();
+            line = line.replace("XORSHIFT", XORSHIFT_STEP);
+            line = line.replace("RNG_SEED", &rng_seed(*seed));
+            line = line.replace("SIZE", &size_expr);
+            line = line.replace("VAR", &node_var);
+            line = line.replace("MEAN", &float_literal(*mean));
+            line = line.replace("STD", &float_literal(*std));
+            c.push_str(&line);
         }
-        Op::Output { name } => {
+        Op::Output { name, .. } if ir.nodes.iter().any(|n| n.output_alias.as_deref() == Some(name.as_str())) => {
+            c.push_str("    // Output NAME written in place\n".replace("NAME", name).as_str());
+        }
+        Op::Output { name, layout } => {
             let src = get_input_var(&node.inputs[0]);
-            let mut line = "    #pragma omp parallel for simd\n    for (int i = 0; i < SIZE; i++) { out_NAME[i] = SRC[i]; }\n".to_string();
+            let mut line = if node.dtype == DataType::F16 {
+                "    #pragma omp parallel for simd\n    for (int i = 0; i < SIZE; i++) { out_NAME[OUT_IDX] = sion_float_to_half(SRC[i]); }\n".to_string()
+            } else {
+                "    #pragma omp parallel for simd\n    for (int i = 0; i < SIZE; i++) { out_NAME[OUT_IDX] = SRC[i]; }\n".to_string()
+            };
+            line = line.replace("OUT_IDX", &output_index_expr(*layout, &node.shape, "i"));
             line = line.replace("SIZE", &size_expr);
             line = line.replace("NAME", &sanitize_id(name));
             line = line.replace("SRC", &src);
             c.push_str(&line);
         }
-        Op::Add | Op::Sub | Op::Mul | Op::Div | Op::Min | Op::Max | Op::Pow => {
-            let left = get_input_var(&node.inputs[0]);
-            let right = get_input_var(&node.inputs[1]);
+        Op::Add | Op::Sub | Op::Mul | Op::Div | Op::Min | Op::Max | Op::Pow
+        | Op::Mod | Op::And | Op::Or | Op::Xor | Op::Shl | Op::Shr => {
+            // Each operand is indexed against the output shape so broadcast dims get stride 0
+            let left = indexed_input(&node.inputs[0], &node.shape, "i");
+            let right = indexed_input(&node.inputs[1], &node.shape, "i");
             let op_sym = match node.op {
                 Op::Add => "+",
                 Op::Sub => "-",
                 Op::Mul => "*",
                 Op::Div => "/",
+                Op::Mod if node.dtype.is_integer() => "%",
+                Op::And => "&",
+                Op::Or => "|",
+                Op::Xor => "^",
+                Op::Shl => "<<",
+                Op::Shr => ">>",
                 _ => "",
             };
 
             c.push_str("    #pragma omp parallel for simd\n");
-            if !op_sym.is_empty() {
-                let mut line = "    for (int i = 0; i < SIZE; i++) { VAR[i] = LEFT[i] SYM RIGHT[i]; }\n".to_string();
+            if let Op::Pow = node.op
+                && let Some(exponent) = small_integer_exponent(ir, &node.inputs[1]) {
+                // x*x*x is far cheaper than a pow call and exact for these exponents
+                let mut line = "    for (int i = 0; i < SIZE; i++) { VAR[i] = EXPR; }\n".to_string();
+                line = line.replace("EXPR", &repeated_product(&left, exponent));
                 line = line.replace("SIZE", &size_expr);
                 line = line.replace("VAR", &node_var);
-                line = line.replace("LEFT", &left);
+                c.push_str(&line);
+            } else if !op_sym.is_empty() {
+                // On integer dtypes this is C integer arithmetic: division truncates toward zero,
+                // the remainder takes the dividend's sign and dividing by zero is undefined
+                let mut line = "    for (int i = 0; i < SIZE; i++) { VAR[i] = BIN_LEFT SYM BIN_RIGHT; }\n".to_string();
+                line = line.replace("SIZE", &size_expr);
+                line = line.replace("VAR", &node_var);
+                line = line.replace("BIN_LEFT", &left);
                 line = line.replace("SYM", op_sym);
-                line = line.replace("RIGHT", &right);
+                line = line.replace("BIN_RIGHT", &right);
+                c.push_str(&line);
+            } else if node.dtype.is_integer() {
+                let expr = match node.op {
+                    Op::Min => "(BIN_LEFT < BIN_RIGHT ? BIN_LEFT : BIN_RIGHT)".to_string(),
+                    Op::Max => "(BIN_LEFT > BIN_RIGHT ? BIN_LEFT : BIN_RIGHT)".to_string(),
+                    Op::Pow => "(TYPE)pow((double)BIN_LEFT, (double)BIN_RIGHT)".replace("TYPE", node.dtype.to_c_type()),
+                    _ => unreachable!(),
+                };
+                let mut line = "    for (int i = 0; i < SIZE; i++) { VAR[i] = EXPR; }\n".to_string();
+                line = line.replace("EXPR", &expr);
+                line = line.replace("SIZE", &size_expr);
+                line = line.replace("VAR", &node_var);
+                line = line.replace("BIN_LEFT", &left);
+                line = line.replace("BIN_RIGHT", &right);
                 c.push_str(&line);
             } else {
                 let func = match node.op {
-                    Op::Min => "fminf",
-                    Op::Max => "fmaxf",
-                    Op::Pow => "powf",
+                    Op::Min => "fmin",
+                    Op::Max => "fmax",
+                    Op::Pow => "pow",
+                    Op::Mod => "fmod",
                     _ => unreachable!(),
                 };
-                let mut line = "    for (int i = 0; i < SIZE; i++) { VAR[i] = FUNC (LEFT[i], RIGHT[i]); }\n".to_string();
+                let mut line = "    for (int i = 0; i < SIZE; i++) { VAR[i] = FUNC (BIN_LEFT, BIN_RIGHT); }\n".to_string();
+                line = line.replace("FUNC", &math_fn(func, node.dtype));
                 line = line.replace("SIZE", &size_expr);
                 line = line.replace("VAR", &node_var);
-                line = line.replace("FUNC", func);
-                line = line.replace("LEFT", &left);
-                line = line.replace("RIGHT", &right);
+                line = line.replace("BIN_LEFT", &left);
+                line = line.replace("BIN_RIGHT", &right);
                 c.push_str(&line);
             }
         }
+        Op::Lerp => {
+            let a = indexed_input(&node.inputs[0], &node.shape, "i");
+            let b = indexed_input(&node.inputs[1], &node.shape, "i");
+            let t = indexed_input(&node.inputs[2], &node.shape, "i");
+            let mut line = "    #pragma omp parallel for simd\n    for (int i = 0; i < SIZE; i++) { VAR[i] = LERP_A + LERP_T * (LERP_B - LERP_A); }\n".to_string();
+            line = line.replace("SIZE", &size_expr);
+            line = line.replace("VAR", &node_var);
+            line = line.replace("LERP_A", &a);
+            line = line.replace("LERP_B", &b);
+            line = line.replace("LERP_T", &t);
+            c.push_str(&line);
+        }
         Op::Sin | Op::Abs | Op::Sqrt | Op::Square | Op::Exp | Op::Log => {
-            let src = get_input_var(&node.inputs[0]);
+            let src = indexed_input(&node.inputs[0], &node.shape, "i");
             let func = match node.op {
-                Op::Sin => "sinf",
-                Op::Abs => "fabsf",
-                Op::Sqrt => "sqrtf",
-                Op::Exp => "expf",
-                Op::Log => "logf",
+                Op::Sin => "sin",
+                Op::Abs => "fabs",
+                Op::Sqrt => "sqrt",
+                Op::Exp => "exp",
+                Op::Log => "log",
                 Op::Square => "",
                 _ => unreachable!(),
             };
@@ -154,18 +605,29 @@ fn emit_node_code(c: &mut String, node: &LinearNode, _ir: &LinearIR) {
                 let mut line = "    for (int i = 0; i < SIZE; i++) { VAR[i] = SRC[i] * SRC[i]; }\n".to_string();
                 line = line.replace("SIZE", &size_expr);
                 line = line.replace("VAR", &node_var);
-                line = line.replace("SRC", &src);
+                line = line.replace("SRC[i]", &src);
                 c.push_str(&line);
             } else {
                 let mut line = "    for (int i = 0; i < SIZE; i++) { VAR[i] = FUNC (SRC[i]); }\n".to_string();
+                line = line.replace("FUNC", &math_fn(func, node.dtype));
                 line = line.replace("SIZE", &size_expr);
                 line = line.replace("VAR", &node_var);
-                line = line.replace("FUNC", func);
-                line = line.replace("SRC", &src);
+                line = line.replace("SRC[i]", &src);
                 c.push_str(&line);
             }
         }
-        Op::Reshape { .. } => {
+        Op::Broadcast { .. } => {
+            // Size-1 source dims get stride 0 in the broadcast index
+            let src = indexed_input(&node.inputs[0], &node.shape, "i");
+            let mut line = "    #pragma omp parallel for simd\n    for (int i = 0; i < SIZE; i++) { VAR[i] = SRC[i]; }\n".to_string();
+            line = line.replace("SIZE", &size_expr);
+            line = line.replace("VAR", &node_var);
+            line = line.replace("SRC[i]", &src);
+            c.push_str(&line);
+        }
+        // Flatten only reinterprets the shape, its pointer was bound to the input above
+        Op::Flatten { .. } if node.output_alias.is_none() => {}
+        Op::Reshape { .. } | Op::Flatten { .. } | Op::Identity | Op::Dropout { .. } => {
             let src = get_input_var(&node.inputs[0]);
             let mut line = "    #pragma omp parallel for simd\n    for (int i = 0; i < SIZE; i++) { VAR[i] = SRC[i]; }\n".to_string();
             line = line.replace("SIZE", &size_expr);
@@ -173,29 +635,262 @@ fn emit_node_code(c: &mut String, node: &LinearNode, _ir: &LinearIR) {
             line = line.replace("SRC", &src);
             c.push_str(&line);
         }
-        Op::ReduceSum { axis } => {
+        Op::ReduceSum { axis, init, kahan } => {
+            let src = get_input_var(&node.inputs[0]);
+            let (outer_size, reduce_dim, inner_size) = axis_extents(&node.inputs[0].shape, *axis);
+
+            // Threads split the outputs only, each one is summed serially in a fixed order
+            let unroll = match node.inputs[0].shape.dims[*axis] {
+                Dim::Static(r) if !*kahan && r <= REDUCE_UNROLL_MAX => Some(r),
+                _ => None,
+            };
+            let mut loops = if let Some(r) = unroll {
+                // Small static axes are summed in one expression, same order as the loop
+                let mut terms = if *init == 0.0 { Vec::new() } else { vec!["INIT".to_string()] };
+                terms.extend((0..r).map(|k| format!("SRC[o * REDUCE * INNER + {} * INNER + i]", k)));
+                format!("    #pragma omp parallel for\n    for (int out = 0; out < OUTER * INNER; out++) {{\n        int o = out / INNER;\n        int i = out % INNER;\n        VAR[out] = {};\n    }}\n", terms.join(" + "))
+            } else if *kahan {
+                // Kahan summation carries the rounding error of each add into the next one
+                "    #pragma omp parallel for\n    for (int out = 0; out < OUTER * INNER; out++) {\n        int o = out / INNER;\n        int i = out % INNER;\n        SCALAR acc = INIT;\n        SCALAR comp = ZERO;\n        for (int r = 0; r < REDUCE; r++) {\n            SCALAR y = SRC[o * REDUCE * INNER + r * INNER + i] - comp;\n            SCALAR t = acc + y;\n            comp = (t - acc) - y;\n            acc = t;\n        }\n        VAR[out] = acc;\n    }\n".to_string()
+            } else {
+                "    #pragma omp parallel for\n    for (int out = 0; out < OUTER * INNER; out++) {\n        int o = out / INNER;\n        int i = out % INNER;\n        SCALAR acc = INIT;\n        for (int r = 0; r < REDUCE; r++) {\n            acc += SRC[o * REDUCE * INNER + r * INNER + i];\n        }\n        VAR[out] = acc;\n    }\n".to_string()
+            };
+            loops = loops.replace("SCALAR", scalar_type(node.dtype));
+            loops = loops.replace("ZERO", &typed_literal(0.0, node.dtype));
+            loops = loops.replace("INIT", &typed_literal(*init, node.dtype));
+            loops = loops.replace("OUTER", &outer_size);
+            loops = loops.replace("INNER", &inner_size);
+            loops = loops.replace("REDUCE", &reduce_dim);
+            loops = loops.replace("VAR", &node_var);
+            loops = loops.replace("SRC", &src);
+            c.push_str(&loops);
+        }
+        Op::ArgMax { axis } | Op::ArgMin { axis } => {
             let src = get_input_var(&node.inputs[0]);
-            let input_shape = &node.inputs[0].shape;
-            
-            let mut init = "    for (int i = 0; i < SIZE; i++) { VAR[i] = 0.0f; }\n".to_string();
-            init = init.replace("SIZE", &size_expr).replace("VAR", &node_var);
-            c.push_str(&init);
-            
-            let reduce_dim = input_shape.dims[*axis].to_c_expr();
-            let outer_size_raw = input_shape.dims[0..*axis].iter().map(|d| d.to_c_expr()).collect::<Vec<_>>().join(" * ");
-            let inner_size_raw = input_shape.dims[*axis+1..].iter().map(|d| d.to_c_expr()).collect::<Vec<_>>().join(" * ");
-            
-            let outer_size = if outer_size_raw.is_empty() { "1".to_string() } else { outer_size_raw };
-            let inner_size = if inner_size_raw.is_empty() { "1".to_string() } else { inner_size_raw };
-
-            let mut loops = "\n    for (int out = 0; out < OUTER * INNER; out++) {\n        int o = out / INNER;\n        int i = out % INNER;\n        for (int r = 0; r < REDUCE; r++) {\n            VAR[o * INNER + i] += SRC[o * REDUCE * INNER + r * INNER + i];\n        }\n    }\n".to_string();
+            let (outer_size, reduce_dim, inner_size) = axis_extents(&node.inputs[0].shape, *axis);
+
+            // Strict comparison keeps the first index on ties
+            let mut loops = "    #pragma omp parallel for\n    for (int out = 0; out < OUTER * INNER; out++) {\n        int o = out / INNER;\n        int i = out % INNER;\n        const SCALAR* lane = SRC + o * REDUCE * INNER + i;\n        int best = 0;\n        for (int r = 1; r < REDUCE; r++) {\n            if (lane[r * INNER] CMP lane[best * INNER]) best = r;\n        }\n        VAR[out] = best;\n    }\n".to_string();
+            // The lane points into the source buffer, so it takes the type that buffer is stored in
+            loops = loops.replace("SCALAR", node.inputs[0].compute_dtype().to_c_type());
+            loops = loops.replace("CMP", if matches!(node.op, Op::ArgMax { .. }) { ">" } else { "<" });
+            loops = loops.replace("OUTER", &outer_size);
+            loops = loops.replace("INNER", &inner_size);
+            loops = loops.replace("REDUCE", &reduce_dim);
+            loops = loops.replace("VAR", &node_var);
+            loops = loops.replace("SRC", &src);
+            c.push_str(&loops);
+        }
+        Op::ReduceVar { axis } | Op::ReduceStd { axis } => {
+            let src = get_input_var(&node.inputs[0]);
+            let (outer_size, reduce_dim, inner_size) = axis_extents(&node.inputs[0].shape, *axis);
+
+            // Two passes: the mean first, then the mean of squared deviations
+            let mut loops = "    #pragma omp parallel for\n    for (int out = 0; out < OUTER * INNER; out++) {\n        int o = out / INNER;\n        int i = out % INNER;\n        SCALAR mean = ZERO;\n        for (int r = 0; r < REDUCE; r++) {\n            mean += SRC[o * REDUCE * INNER + r * INNER + i];\n        }\n        mean /= (SCALAR)(REDUCE);\n        SCALAR sq_dev = ZERO;\n        for (int r = 0; r < REDUCE; r++) {\n            SCALAR d = SRC[o * REDUCE * INNER + r * INNER + i] - mean;\n            sq_dev += d * d;\n        }\n        VAR[out] = FINISH(sq_dev / (SCALAR)(REDUCE));\n    }\n".to_string();
+            loops = loops.replace("SCALAR", scalar_type(node.dtype));
+            loops = loops.replace("ZERO", &typed_literal(0.0, node.dtype));
+            loops = loops.replace("FINISH", &if matches!(node.op, Op::ReduceStd { .. }) { math_fn("sqrt", node.dtype) } else { String::new() });
+            loops = loops.replace("OUTER", &outer_size);
+            loops = loops.replace("INNER", &inner_size);
+            loops = loops.replace("REDUCE", &reduce_dim);
+            loops = loops.replace("VAR", &node_var);
+            loops = loops.replace("SRC", &src);
+            c.push_str(&loops);
+        }
+        Op::Normalize { axis, epsilon } => {
+            let src = get_input_var(&node.inputs[0]);
+            let (outer_size, reduce_dim, inner_size) = axis_extents(&node.inputs[0].shape, *axis);
+
+            let mut loops = "    #pragma omp parallel for\n    for (int out = 0; out < OUTER * INNER; out++) {\n        int o = out / INNER;\n        int i = out % INNER;\n        SCALAR sq_sum = ZERO;\n        for (int r = 0; r < REDUCE; r++) {\n            SCALAR v = SRC[o * REDUCE * INNER + r * INNER + i];\n            sq_sum += v * v;\n        }\n        SCALAR inv_norm = ONE / SQRT(sq_sum + EPSILON);\n        for (int r = 0; r < REDUCE; r++) {\n            VAR[o * REDUCE * INNER + r * INNER + i] = SRC[o * REDUCE * INNER + r * INNER + i] * inv_norm;\n        }\n    }\n".to_string();
+            loops = loops.replace("SCALAR", scalar_type(node.dtype));
+            loops = loops.replace("ZERO", &typed_literal(0.0, node.dtype));
+            loops = loops.replace("ONE", &typed_literal(1.0, node.dtype));
+            loops = loops.replace("SQRT", &math_fn("sqrt", node.dtype));
+            loops = loops.replace("EPSILON", &typed_literal(*epsilon, node.dtype));
+            loops = loops.replace("OUTER", &outer_size);
+            loops = loops.replace("INNER", &inner_size);
+            loops = loops.replace("REDUCE", &reduce_dim);
+            loops = loops.replace("VAR", &node_var);
+            loops = loops.replace("SRC", &src);
+            c.push_str(&loops);
+        }
+        Op::LayerNorm { axis, epsilon, affine } => {
+            let data = &node.inputs[node.op.port_index("input").unwrap_or(0)];
+            let src = get_input_var(data);
+            let (outer_size, reduce_dim, inner_size) = axis_extents(&data.shape, *axis);
+
+            // Mean, then variance around it, then the normalized lane
+            let mut loops = "    #pragma omp parallel for\n    for (int out = 0; out < OUTER * INNER; out++) {\n        int o = out / INNER;\n        int i = out % INNER;\n        SCALAR mean = ZERO;\n        for (int r = 0; r < REDUCE; r++) {\n            mean += SRC[o * REDUCE * INNER + r * INNER + i];\n        }\n        mean /= (SCALAR)(REDUCE);\n        SCALAR variance = ZERO;\n        for (int r = 0; r < REDUCE; r++) {\n            SCALAR d = SRC[o * REDUCE * INNER + r * INNER + i] - mean;\n            variance += d * d;\n        }\n        variance /= (SCALAR)(REDUCE);\n        SCALAR inv_std = ONE / SQRT(variance + EPSILON);\n        for (int r = 0; r < REDUCE; r++) {\n            int idx = o * REDUCE * INNER + r * INNER + i;\n            VAR[idx] = (SRC[idx] - mean) * inv_stdAFFINE;\n        }\n    }\n".to_string();
+            loops = loops.replace("SCALAR", scalar_type(node.dtype));
+            loops = loops.replace("ZERO", &typed_literal(0.0, node.dtype));
+            loops = loops.replace("ONE", &typed_literal(1.0, node.dtype));
+            loops = loops.replace("SQRT", &math_fn("sqrt", node.dtype));
+            loops = loops.replace("EPSILON", &typed_literal(*epsilon, node.dtype));
             loops = loops.replace("OUTER", &outer_size);
             loops = loops.replace("INNER", &inner_size);
             loops = loops.replace("REDUCE", &reduce_dim);
             loops = loops.replace("VAR", &node_var);
             loops = loops.replace("SRC", &src);
+            let affine_terms = if *affine {
+                let scale = &node.inputs[node.op.port_index("scale").unwrap_or(0)];
+                let bias = &node.inputs[node.op.port_index("bias").unwrap_or(0)];
+                format!(" * {} + {}", indexed_input(scale, &node.shape, "idx"), indexed_input(bias, &node.shape, "idx"))
+            } else {
+                String::new()
+            };
+            loops = loops.replace("AFFINE", &affine_terms);
+            c.push_str(&loops);
+        }
+        Op::Conv1D { stride, padding } => {
+            let src = get_input_var(&node.inputs[0]);
+            let kernel = get_input_var(&node.inputs[1]);
+            let x_dims = &node.inputs[0].shape.dims;
+            let w_dims = &node.inputs[1].shape.dims;
+
+            let mut loops = "    #pragma omp parallel for collapse(2)\n    for (int co = 0; co < C_OUT; co++) {\n        for (int x = 0; x < L_OUT; x++) {\n            SCALAR acc = ZERO;\n            for (int ci = 0; ci < C_IN; ci++) {\n                for (int k = 0; k < KSZ; k++) {\n                    int pos = x * STRIDE + k - PAD;\n                    if (pos < 0 || pos >= L_IN) continue;\n                    acc += SRC[ci * L_IN + pos] * KERNEL[(co * C_IN + ci) * KSZ + k];\n                }\n            }\n            VAR[co * L_OUT + x] = acc;\n        }\n    }\n".to_string();
+            loops = loops.replace("SCALAR", scalar_type(node.dtype));
+            loops = loops.replace("ZERO", &typed_literal(0.0, node.dtype));
+            loops = loops.replace("C_OUT", &w_dims[0].to_c_expr());
+            loops = loops.replace("L_OUT", &node.shape.dims[1].to_c_expr());
+            loops = loops.replace("C_IN", &x_dims[0].to_c_expr());
+            loops = loops.replace("L_IN", &x_dims[1].to_c_expr());
+            loops = loops.replace("KSZ", &w_dims[2].to_c_expr());
+            loops = loops.replace("STRIDE", &stride.to_string());
+            loops = loops.replace("PAD", &padding.to_string());
+            loops = loops.replace("SRC", &src);
+            loops = loops.replace("KERNEL", &kernel);
+            loops = loops.replace("VAR", &node_var);
+            c.push_str(&loops);
+        }
+        Op::DepthwiseConv2D { stride, padding } => {
+            let src = get_input_var(&node.inputs[0]);
+            let kernel = get_input_var(&node.inputs[1]);
+            let x_dims = &node.inputs[0].shape.dims;
+            let w_dims = &node.inputs[1].shape.dims;
+
+            let mut loops = "    #pragma omp parallel for collapse(2)\n    for (int ch = 0; ch < CHANNELS; ch++) {\n        for (int y = 0; y < H_OUT; y++) {\n            for (int x = 0; x < W_OUT; x++) {\n                SCALAR acc = ZERO;\n                for (int ky = 0; ky < KH; ky++) {\n                    int iy = y * STRIDE + ky - PAD;\n                    if (iy < 0 || iy >= H_IN) continue;\n                    for (int kx = 0; kx < KW; kx++) {\n                        int ix = x * STRIDE + kx - PAD;\n                        if (ix < 0 || ix >= W_IN) continue;\n                        acc += SRC[(ch * H_IN + iy) * W_IN + ix] * KERNEL[(ch * KH + ky) * KW + kx];\n                    }\n                }\n                VAR[(ch * H_OUT + y) * W_OUT + x] = acc;\n            }\n        }\n    }\n".to_string();
+            loops = loops.replace("SCALAR", scalar_type(node.dtype));
+            loops = loops.replace("ZERO", &typed_literal(0.0, node.dtype));
+            loops = loops.replace("CHANNELS", &x_dims[0].to_c_expr());
+            loops = loops.replace("H_OUT", &node.shape.dims[1].to_c_expr());
+            loops = loops.replace("W_OUT", &node.shape.dims[2].to_c_expr());
+            loops = loops.replace("H_IN", &x_dims[1].to_c_expr());
+            loops = loops.replace("W_IN", &x_dims[2].to_c_expr());
+            loops = loops.replace("KH", &w_dims[1].to_c_expr());
+            loops = loops.replace("KW", &w_dims[2].to_c_expr());
+            loops = loops.replace("STRIDE", &stride.to_string());
+            loops = loops.replace("PAD", &padding.to_string());
+            loops = loops.replace("SRC", &src);
+            loops = loops.replace("KERNEL", &kernel);
+            loops = loops.replace("VAR", &node_var);
+            c.push_str(&loops);
+        }
+        Op::Resize { scale, mode } => {
+            let src = get_input_var(&node.inputs[0]);
+            let in_dims = &node.inputs[0].shape.dims;
+            let mut loops = if mode == "bilinear" {
+                // Half-pixel centers, clamped to the border
+                "    #pragma omp parallel for\n    for (int ch = 0; ch < CHANNELS; ch++) {\n        for (int y = 0; y < H_OUT; y++) {\n            SCALAR sy = FMIN(FMAX((y + HALF) / SCALE_H - HALF, ZERO), (SCALAR)(H_IN - 1));\n            int y0 = (int)sy;\n            int y1 = y0 + 1 < H_IN ? y0 + 1 : H_IN - 1;\n            SCALAR wy = sy - y0;\n            for (int x = 0; x < W_OUT; x++) {\n                SCALAR sx = FMIN(FMAX((x + HALF) / SCALE_W - HALF, ZERO), (SCALAR)(W_IN - 1));\n                int x0 = (int)sx;\n                int x1 = x0 + 1 < W_IN ? x0 + 1 : W_IN - 1;\n                SCALAR wx = sx - x0;\n                const SCALAR* plane = SRC + ch * H_IN * W_IN;\n                SCALAR top = plane[y0 * W_IN + x0] * (ONE - wx) + plane[y0 * W_IN + x1] * wx;\n                SCALAR bottom = plane[y1 * W_IN + x0] * (ONE - wx) + plane[y1 * W_IN + x1] * wx;\n                VAR[(ch * H_OUT + y) * W_OUT + x] = top * (ONE - wy) + bottom * wy;\n            }\n        }\n    }\n".to_string()
+            } else {
+                "    #pragma omp parallel for\n    for (int ch = 0; ch < CHANNELS; ch++) {\n        for (int y = 0; y < H_OUT; y++) {\n            for (int x = 0; x < W_OUT; x++) {\n                VAR[(ch * H_OUT + y) * W_OUT + x] = SRC[(ch * H_IN + y / SCALE_H) * W_IN + x / SCALE_W];\n            }\n        }\n    }\n".to_string()
+            };
+            loops = loops.replace("SCALAR", scalar_type(node.dtype));
+            loops = loops.replace("FMIN", &math_fn("fmin", node.dtype));
+            loops = loops.replace("FMAX", &math_fn("fmax", node.dtype));
+            loops = loops.replace("HALF", &typed_literal(0.5, node.dtype));
+            loops = loops.replace("ZERO", &typed_literal(0.0, node.dtype));
+            loops = loops.replace("ONE", &typed_literal(1.0, node.dtype));
+            loops = loops.replace("CHANNELS", &in_dims[0].to_c_expr());
+            loops = loops.replace("H_OUT", &node.shape.dims[1].to_c_expr());
+            loops = loops.replace("W_OUT", &node.shape.dims[2].to_c_expr());
+            loops = loops.replace("H_IN", &in_dims[1].to_c_expr());
+            loops = loops.replace("W_IN", &in_dims[2].to_c_expr());
+            loops = loops.replace("SCALE_H", &scale.0.to_string());
+            loops = loops.replace("SCALE_W", &scale.1.to_string());
+            loops = loops.replace("SRC", &src);
+            loops = loops.replace("VAR", &node_var);
+            c.push_str(&loops);
+        }
+        Op::Diag => {
+            let src = get_input_var(&node.inputs[0]);
+            let mut line = "    for (int i = 0; i < DIM; i++) { VAR[i] = SRC[i * DIM + i]; }\n".to_string();
+            line = line.replace("DIM", &node.inputs[0].shape.dims[0].to_c_expr());
+            line = line.replace("VAR", &node_var);
+            line = line.replace("SRC", &src);
+            c.push_str(&line);
+        }
+        Op::Trace => {
+            let src = get_input_var(&node.inputs[0]);
+            let mut line = "    VAR[0] = ZERO;\n    for (int i = 0; i < DIM; i++) { VAR[0] += SRC[i * DIM + i]; }\n".to_string();
+            line = line.replace("ZERO", &typed_literal(0.0, node.dtype));
+            line = line.replace("DIM", &node.inputs[0].shape.dims[0].to_c_expr());
+            line = line.replace("VAR", &node_var);
+            line = line.replace("SRC", &src);
+            c.push_str(&line);
+        }
+        Op::GatherND => {
+            let data = get_input_var(&node.inputs[0]);
+            let indices = get_input_var(&node.inputs[1]);
+            let data_dims = &node.inputs[0].shape.dims;
+            let rank = data_dims.len();
+
+            c.push_str("    #pragma omp parallel for\n");
+            c.push_str(&"    for (int n = 0; n < SIZE; n++) {\n        int flat = 0;\n".replace("SIZE", &size_expr));
+            for (axis, dim) in data_dims.iter().enumerate() {
+                let stride = data_dims[axis + 1..].iter().map(|d| d.to_c_expr()).collect::<Vec<_>>().join(" * ");
+                let stride = if stride.is_empty() { "1".to_string() } else { stride };
+                let mut line = "        { int coord = (int)IDX[n * RANK + AXIS]; coord = coord < 0 ? 0 : (coord >= (DIM) ? (DIM) - 1 : coord); flat += coord * (STRIDE); }\n".to_string();
+                line = line.replace("IDX", &indices);
+                line = line.replace("RANK", &rank.to_string());
+                line = line.replace("AXIS", &axis.to_string());
+                line = line.replace("DIM", &dim.to_c_expr());
+                line = line.replace("STRIDE", &stride);
+                c.push_str(&line);
+            }
+            let mut line = "        VAR[n] = DATA[flat];\n    }\n".to_string();
+            line = line.replace("VAR", &node_var);
+            line = line.replace("DATA", &data);
+            c.push_str(&line);
+        }
+        Op::MatMul if node.inputs[1].shape.dims.len() == 1 => {
+            let left = get_input_var(&node.inputs[0]);
+            let right = get_input_var(&node.inputs[1]);
+            let a_shape = &node.inputs[0].shape;
+
+            // Accumulate each row in a register instead of read-modify-writing the output
+            let mut loops = "    #pragma omp parallel for\n    for (int i = 0; i < ROWS; i++) {\n        const SCALAR* row = LEFT + i * COLS;\n        SCALAR acc = ZERO;\n        for (int l = 0; l < COLS; l++) {\n            acc += row[l] * RIGHT[l];\n        }\n        VAR[i] = acc;\n    }\n".to_string();
+            loops = loops.replace("SCALAR", scalar_type(node.dtype));
+            loops = loops.replace("ZERO", &typed_literal(0.0, node.dtype));
+            loops = loops.replace("ROWS", &a_shape.dims[0].to_c_expr());
+            loops = loops.replace("COLS", &a_shape.dims[1].to_c_expr());
+            loops = loops.replace("VAR", &node_var);
+            loops = loops.replace("LEFT", &left);
+            loops = loops.replace("RIGHT", &right);
             c.push_str(&loops);
         }
+        Op::MatMul if opts.blas && is_static_gemm(node) => {
+            let left = get_input_var(&node.inputs[0]);
+            let right = get_input_var(&node.inputs[1]);
+            let a_shape = &node.inputs[0].shape;
+            let b_shape = &node.inputs[1].shape;
+
+            // Row-major, so the leading dimensions are the row lengths K, N and N
+            let mut call = "    for (int b = 0; b < (SIZE) / (GEMM_M * GEMM_N); b++) {\n        GEMM_FN(CblasRowMajor, CblasNoTrans, CblasNoTrans, GEMM_M, GEMM_N, GEMM_K, GEMM_ONE, LEFT + BATCH_A * GEMM_M * GEMM_K, GEMM_K, RIGHT + BATCH_B * GEMM_K * GEMM_N, GEMM_N, GEMM_ZERO, VAR + b * GEMM_M * GEMM_N, GEMM_N);\n    }\n".to_string();
+            call = call.replace("GEMM_FN", if node.dtype == DataType::F64 { "cblas_dgemm" } else { "cblas_sgemm" });
+            call = call.replace("GEMM_ONE", &typed_literal(1.0, node.dtype));
+            call = call.replace("GEMM_ZERO", &typed_literal(0.0, node.dtype));
+            call = call.replace("SIZE", &size_expr);
+            call = call.replace("GEMM_M", &a_shape.dims[a_shape.dims.len() - 2].to_c_expr());
+            call = call.replace("GEMM_K", &a_shape.dims[a_shape.dims.len() - 1].to_c_expr());
+            call = call.replace("GEMM_N", &b_shape.dims[b_shape.dims.len() - 1].to_c_expr());
+            let (batch_a, batch_b) = matmul_batch_indices(node);
+            call = call.replace("BATCH_A", &batch_a);
+            call = call.replace("BATCH_B", &batch_b);
+            call = call.replace("VAR", &node_var);
+            call = call.replace("LEFT", &left);
+            call = call.replace("RIGHT", &right);
+            c.push_str(&call);
+        }
         Op::MatMul => {
             let left = get_input_var(&node.inputs[0]);
             let right = get_input_var(&node.inputs[1]);
@@ -205,95 +900,479 @@ fn emit_node_code(c: &mut String, node: &LinearNode, _ir: &LinearIR) {
             let m = a_shape.dims[a_shape.dims.len() - 2].to_c_expr();
             let k = a_shape.dims[a_shape.dims.len() - 1].to_c_expr();
             let n = b_shape.dims[b_shape.dims.len() - 1].to_c_expr();
-            
-            let mut init = "    for (int i = 0; i < SIZE; i++) { VAR[i] = 0.0f; }\n".to_string();
-            init = init.replace("SIZE", &size_expr).replace("VAR", &node_var);
-            c.push_str(&init);
 
-            let mut loops = "\n    int batch_size = (SIZE) / ((M) * (N));\n    for (int b = 0; b < batch_size; b++) {\n        for (int i = 0; i < M; i++) {\n            for (int j = 0; j < N; j++) {\n                for (int l = 0; l < K; l++) {\n                    VAR[b * M * N + i * N + j] += LEFT[b * M * K + i * K + l] * RIGHT[b * K * N + l * N + j];\n                }\n            }\n        }\n    }\n".to_string();
+            let dims_static = [&a_shape.dims[a_shape.dims.len() - 2], &a_shape.dims[a_shape.dims.len() - 1], &b_shape.dims[b_shape.dims.len() - 1]]
+                .iter()
+                .all(|d| matches!(d, Dim::Static(v) if *v >= BLOCKED_MATMUL_MIN_DIM));
+            let mut loops = if dims_static {
+                // Tiled over (i, j, l); each output element accumulates one K tile in a register,
+                // so the output is zeroed first and revisited once per K tile
+                "    for (int i = 0; i < SIZE; i++) { VAR[i] = ZERO; }\n\n    int batch_VAR = (SIZE) / ((M) * (N));\n    for (int b = 0; b < batch_VAR; b++) {\n        #pragma omp parallel for collapse(2) schedule(static)\n        for (int ii = 0; ii < M; ii += TILE_M) {\n            for (int jj = 0; jj < N; jj += TILE_N) {\n                for (int ll = 0; ll < K; ll += TILE_K) {\n                    int i_end = ii + TILE_M < M ? ii + TILE_M : M;\n                    int l_end = ll + TILE_K < K ? ll + TILE_K : K;\n                    int j_end = jj + TILE_N < N ? jj + TILE_N : N;\n                    for (int i = ii; i < i_end; i++) {\n                        for (int j = jj; j < j_end; j++) {\n                            SCALAR acc = VAR[b * M * N + i * N + j];\n                            for (int l = ll; l < l_end; l++) {\n                                acc += LEFT[BATCH_A * M * K + i * K + l] * RIGHT[BATCH_B * K * N + l * N + j];\n                            }\n                            VAR[b * M * N + i * N + j] = acc;\n                        }\n                    }\n                }\n            }\n        }\n    }\n"
+                    .replace("TILE_M", &MATMUL_TILE_M.to_string())
+                    .replace("TILE_N", &MATMUL_TILE_N.to_string())
+                    .replace("TILE_K", &MATMUL_TILE_K.to_string())
+            } else {
+                // Accumulate the whole dot product in a register and store each element once
+                "    int batch_VAR = (SIZE) / ((M) * (N));\n    for (int b = 0; b < batch_VAR; b++) {\n        #pragma omp parallel for collapse(2) schedule(static)\n        for (int i = 0; i < M; i++) {\n            for (int j = 0; j < N; j++) {\n                SCALAR acc = ZERO;\n                for (int l = 0; l < K; l++) {\n                    acc += LEFT[BATCH_A * M * K + i * K + l] * RIGHT[BATCH_B * K * N + l * N + j];\n                }\n                VAR[b * M * N + i * N + j] = acc;\n            }\n        }\n    }\n".to_string()
+            };
+            loops = transposed_operand_reads(loops, node, ("M", "K", "N"));
+            loops = loops.replace("SCALAR", scalar_type(node.dtype));
+            loops = loops.replace("ZERO", &typed_literal(0.0, node.dtype));
             loops = loops.replace("SIZE", &size_expr);
             loops = loops.replace("M", &m);
             loops = loops.replace("N", &n);
             loops = loops.replace("K", &k);
+            // Filled after the single-letter dims so symbolic batch dims named M, N or K survive
+            let (batch_a, batch_b) = matmul_batch_indices(node);
+            loops = loops.replace("BATCH_A", &batch_a);
+            loops = loops.replace("BATCH_B", &batch_b);
             loops = loops.replace("VAR", &node_var);
             loops = loops.replace("LEFT", &left);
             loops = loops.replace("RIGHT", &right);
             c.push_str(&loops);
         }
-        Op::Split { parts, .. } => {
+        Op::Split { axis, parts } => {
+            // The slot holds the parts back to back, each one a row-major tensor of the part
+            // shape, so port p starts at p * SIZE (see `get_input_var`)
             let src = get_input_var(&node.inputs[0]);
-            let mut line = "    #pragma omp parallel for simd\n    for (int i = 0; i < SIZE * PARTS; i++) { VAR[i] = SRC[i]; }\n".to_string();
+            let (outer_size, split_len, inner_size) = axis_extents(&node.shape, *axis);
+            let mut line = if outer_size == "1" {
+                "    #pragma omp parallel for simd\n    for (int i = 0; i < SIZE * PARTS; i++) { VAR[i] = SRC[i]; }\n".to_string()
+            } else {
+                "    #pragma omp parallel for\n    for (int i = 0; i < SIZE * PARTS; i++) {\n        int p = i / (SIZE);\n        int j = i % (SIZE);\n        int o = j / (SPLIT_LEN * INNER);\n        int r = (j / INNER) % SPLIT_LEN;\n        int k = j % INNER;\n        VAR[i] = SRC[(o * AXIS_DIM + p * SPLIT_LEN + r) * INNER + k];\n    }\n".to_string()
+            };
+            line = line.replace("SPLIT_LEN", &format!("({})", split_len));
+            line = line.replace("INNER", &format!("({})", inner_size));
+            line = line.replace("AXIS_DIM", &format!("({})", node.inputs[0].shape.dims[*axis].to_c_expr()));
             line = line.replace("SIZE", &size_expr);
             line = line.replace("PARTS", &parts.to_string());
             line = line.replace("VAR", &node_var);
             line = line.replace("SRC", &src);
             c.push_str(&line);
         }
+        Op::Window { axis, size, stride } => {
+            // Window w is a row-major copy of the input with `axis` cut down to SIZE elements
+            // starting at w * STRIDE
+            let src = get_input_var(&node.inputs[0]);
+            let (outer_size, axis_dim, inner_size) = axis_extents(&node.inputs[0].shape, *axis);
+            let mut line = "    #pragma omp parallel for\n    for (int i = 0; i < SIZE; i++) {\n        int w = i / (OUTER * WIN_LEN * INNER);\n        int j = i % (OUTER * WIN_LEN * INNER);\n        int o = j / (WIN_LEN * INNER);\n        int r = (j / INNER) % WIN_LEN;\n        int k = j % INNER;\n        VAR[i] = SRC[(o * AXIS_DIM + w * STRIDE + r) * INNER + k];\n    }\n".to_string();
+            line = line.replace("OUTER", &format!("({})", outer_size));
+            line = line.replace("WIN_LEN", &size.to_string());
+            line = line.replace("INNER", &format!("({})", inner_size));
+            line = line.replace("AXIS_DIM", &format!("({})", axis_dim));
+            line = line.replace("STRIDE", &stride.to_string());
+            line = line.replace("SIZE", &size_expr);
+            line = line.replace("VAR", &node_var);
+            line = line.replace("SRC", &src);
+            c.push_str(&line);
+        }
         Op::Transpose { permutation } => {
             let src = get_input_var(&node.inputs[0]);
             let in_shape = &node.inputs[0].shape;
-            
-            for (i, _) in in_shape.dims.iter().enumerate() {
-                let mut line = "    for (int dIDX = 0; dIDX < DIM; dIDX++) { \n".to_string();
-                line = line.replace("IDX", &i.to_string());
-                line = line.replace("DIM", &in_shape.dims[i].to_c_expr());
-                c.push_str(&line);
-            }
-            
-            let mut in_idx = "0".to_string();
-            let mut stride = "1".to_string();
-            for i in (0..in_shape.dims.len()).rev() {
-                let mut term = "((IN_IDX) + (dIDX) * (STRIDE))".to_string();
-                term = term.replace("IN_IDX", &in_idx).replace("IDX", &i.to_string()).replace("STRIDE", &stride);
-                in_idx = term;
-                
-                let mut next_stride = "((STRIDE) * (DIM))".to_string();
-                next_stride = next_stride.replace("STRIDE", &stride).replace("DIM", &in_shape.dims[i].to_c_expr());
-                stride = next_stride;
+
+            // Static matrices go through a stack tile so both sides are accessed in cache-sized blocks
+            if let [Dim::Static(rows), Dim::Static(cols)] = in_shape.dims.as_slice()
+                && permutation.as_slice() == [1, 0] {
+                let mut loops = "    // Transpose path: blocked TILExTILE tiles\n    #pragma omp parallel for\n    for (int t = 0; t < TILES_R * TILES_C; t++) {\n        int ii = (t / TILES_C) * TILE;\n        int jj = (t % TILES_C) * TILE;\n        int i_end = ii + TILE < ROWS ? ii + TILE : ROWS;\n        int j_end = jj + TILE < COLS ? jj + TILE : COLS;\n        SCALAR tile[TILE][TILE];\n        for (int i = ii; i < i_end; i++) {\n            for (int j = jj; j < j_end; j++) { tile[j - jj][i - ii] = SRC[i * COLS + j]; }\n        }\n        for (int j = jj; j < j_end; j++) {\n            for (int i = ii; i < i_end; i++) { VAR[j * ROWS + i] = tile[j - jj][i - ii]; }\n        }\n    }\n".to_string();
+                loops = loops.replace("SCALAR", node.compute_dtype().to_c_type());
+                loops = loops.replace("TILES_R", &rows.div_ceil(TRANSPOSE_TILE).to_string());
+                loops = loops.replace("TILES_C", &cols.div_ceil(TRANSPOSE_TILE).to_string());
+                loops = loops.replace("TILE", &TRANSPOSE_TILE.to_string());
+                loops = loops.replace("ROWS", &rows.to_string());
+                loops = loops.replace("COLS", &cols.to_string());
+                loops = loops.replace("VAR", &node_var);
+                loops = loops.replace("SRC", &src);
+                c.push_str(&loops);
+            } else {
+                // The loop nest is perfect, so the two outermost loops can be collapsed
+                c.push_str("    // Transpose path: nested loops\n");
+                match in_shape.dims.len() {
+                    0 => {}
+                    1 => c.push_str("    #pragma omp parallel for\n"),
+                    _ => c.push_str("    #pragma omp parallel for collapse(2)\n"),
+                }
+                for (i, _) in in_shape.dims.iter().enumerate() {
+                    let mut line = "    for (int dIDX = 0; dIDX < DIM; dIDX++) { \n".to_string();
+                    line = line.replace("IDX", &i.to_string());
+                    line = line.replace("DIM", &in_shape.dims[i].to_c_expr());
+                    c.push_str(&line);
+                }
+
+                let mut in_idx = "0".to_string();
+                let mut stride = "1".to_string();
+                for i in (0..in_shape.dims.len()).rev() {
+                    let mut term = "((IN_IDX) + (dIDX) * (STRIDE))".to_string();
+                    term = term.replace("IN_IDX", &in_idx).replace("IDX", &i.to_string()).replace("STRIDE", &stride);
+                    in_idx = term;
+
+                    let mut next_stride = "((STRIDE) * (DIM))".to_string();
+                    next_stride = next_stride.replace("STRIDE", &stride).replace("DIM", &in_shape.dims[i].to_c_expr());
+                    stride = next_stride;
+                }
+
+                let mut out_idx = "0".to_string();
+                let mut out_stride = "1".to_string();
+                for i in (0..permutation.len()).rev() {
+                    let target_axis = permutation[i];
+                    let mut term = "((OUT_IDX) + (dIDX) * (STRIDE))".to_string();
+                    term = term.replace("OUT_IDX", &out_idx).replace("IDX", &target_axis.to_string()).replace("STRIDE", &out_stride);
+                    out_idx = term;
+
+                    let mut next_stride = "((STRIDE) * (DIM))".to_string();
+                    next_stride = next_stride.replace("STRIDE", &out_stride).replace("DIM", &in_shape.dims[target_axis].to_c_expr());
+                    out_stride = next_stride;
+                }
+
+                let mut copy_line = "    VAR[OUT_IDX] = SRC[IN_IDX];\n".to_string();
+                copy_line = copy_line.replace("VAR", &node_var);
+                copy_line = copy_line.replace("OUT_IDX", &out_idx);
+                copy_line = copy_line.replace("SRC", &src);
+                copy_line = copy_line.replace("IN_IDX", &in_idx);
+                c.push_str(&copy_line);
+
+                for _ in &in_shape.dims {
+                    c.push_str("    }\n");
+                }
             }
+        }
+    }
+}
 
-            let mut out_idx = "0".to_string();
-            let mut out_stride = "1".to_string();
-            for i in (0..permutation.len()).rev() {
-                let target_axis = permutation[i];
-                let mut term = "((OUT_IDX) + (dIDX) * (STRIDE))".to_string();
-                term = term.replace("OUT_IDX", &out_idx).replace("IDX", &target_axis.to_string()).replace("STRIDE", &out_stride);
-                out_idx = term;
-
-                let mut next_stride = "((STRIDE) * (DIM))".to_string();
-                next_stride = next_stride.replace("STRIDE", &out_stride).replace("DIM", &in_shape.dims[target_axis].to_c_expr());
-                out_stride = next_stride;
+/// Element count behind every buffer name a node body can index, for `SION_AT`.
+fn buffer_sizes(ir: &LinearIR) -> HashMap<String, String> {
+    let mut sizes = HashMap::new();
+    for port in &ir.inputs {
+        sizes.insert(format!("in_{}", sanitize_id(&port.name)), port.shape.to_c_size_expr());
+    }
+    for port in &ir.outputs {
+        sizes.insert(format!("out_{}", sanitize_id(&port.name)), port.shape.to_c_size_expr());
+    }
+    for node in &ir.nodes {
+        let size = match &node.op {
+            Op::Constant { values, .. } => values.len().to_string(),
+            _ if node.has_workspace_slot() => node.padded_slot_size_expr(),
+            Op::Flatten { .. } => node.shape.to_c_size_expr(),
+            _ if node.output_alias.is_some() => node.shape.to_c_size_expr(),
+            _ => continue,
+        };
+        sizes.insert(sanitize_id(&node.id), size);
+    }
+    sizes
+}
+
+/// Rewrites `BUF[IDX]` into `SION_AT(BUF, IDX, SIZE, "node")` for every known buffer.
+/// Accesses through local pointers (`lane`, `plane`, split parts) are left as they are.
+fn checked_accesses(code: &str, sizes: &HashMap<String, String>, node_id: &str) -> String {
+    let bytes = code.as_bytes();
+    let is_ident = |b: u8| b.is_ascii_alphanumeric() || b == b'_';
+    let mut out = String::new();
+    let mut pos = 0;
+    while pos < bytes.len() {
+        if !is_ident(bytes[pos]) {
+            let ch = code[pos..].chars().next().unwrap_or_default();
+            out.push(ch);
+            pos += ch.len_utf8().max(1);
+            continue;
+        }
+        let end = pos + bytes[pos..].iter().take_while(|b| is_ident(**b)).count();
+        let name = &code[pos..end];
+        let Some(size) = sizes.get(name).filter(|_| bytes.get(end) == Some(&b'[')) else {
+            out.push_str(name);
+            pos = end;
+            continue;
+        };
+        let mut depth = 0;
+        let mut close = end;
+        for (i, b) in bytes.iter().enumerate().skip(end) {
+            match b {
+                b'[' => depth += 1,
+                b']' => depth -= 1,
+                _ => {}
+            }
+            if depth == 0 {
+                close = i;
+                break;
             }
+        }
+        let index = checked_accesses(&code[end + 1..close], sizes, node_id);
+        out.push_str(&format!("SION_AT({}, {}, {}, \"{}\")", name, index, size, node_id.replace('"', "'")));
+        pos = close + 1;
+    }
+    out
+}
+
+/// Elements of a node's result scanned for NaN/Inf, or None when it has no float buffer of its own.
+fn finite_scan_size(node: &LinearNode) -> Option<String> {
+    if node.dtype.is_integer() || matches!(node.op, Op::Input { .. } | Op::Output { .. } | Op::Constant { .. } | Op::Flatten { .. }) {
+        return None;
+    }
+    if !node.has_workspace_slot() && node.output_alias.is_none() {
+        return None;
+    }
+    Some(node.slot_size_expr())
+}
+
+/// File-scope array holding a constant's values. Modules are included into one runtime
+/// translation unit, so the name carries the module id.
+pub(crate) fn constant_data_name(module_id: &str, node_id: &str) -> String {
+    format!("{}_{}_data", module_id, sanitize_id(node_id))
+}
+
+/// Turns `parallel for simd` into plain `simd` and drops other `parallel for` pragmas.
+fn strip_parallel_pragmas(code: &str) -> String {
+    let mut out = String::new();
+    for line in code.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("#pragma omp parallel for simd") {
+            out.push_str(&line.replace("#pragma omp parallel for simd", "#pragma omp simd"));
+        } else if trimmed.starts_with("#pragma omp parallel for") {
+            continue;
+        } else {
+            out.push_str(line);
+        }
+        out.push('\n');
+    }
+    out
+}
 
-            let mut copy_line = "    VAR[OUT_IDX] = SRC[IN_IDX];\n".to_string();
-            copy_line = copy_line.replace("VAR", &node_var);
-            copy_line = copy_line.replace("OUT_IDX", &out_idx);
-            copy_line = copy_line.replace("SRC", &src);
-            copy_line = copy_line.replace("IN_IDX", &in_idx);
-            c.push_str(&copy_line);
-            
-            for _ in &in_shape.dims {
-                c.push_str("    }\n");
+/// Replaces the single-statement loops `for (int i = 0; i < SIZE; i++) { STMT }` over a
+/// node's static size with `count` copies of STMT, dropping the pragma in front of them.
+fn unroll_small_loops(code: &str, size_expr: &str, count: usize) -> String {
+    let header = format!("for (int i = 0; i < {}; i++) {{ ", size_expr);
+    let unrollable = |line: &str| -> Option<String> {
+        let body = line.trim_start().strip_prefix(&header)?.strip_suffix(" }")?;
+        (!body.contains('{')).then(|| body.to_string())
+    };
+
+    let lines: Vec<&str> = code.lines().collect();
+    let mut out = String::new();
+    for (idx, line) in lines.iter().enumerate() {
+        if line.trim_start().starts_with("#pragma omp") && lines.get(idx + 1).is_some_and(|next| unrollable(next).is_some()) {
+            continue;
+        }
+        let Some(body) = unrollable(line) else {
+            out.push_str(line);
+            out.push('\n');
+            continue;
+        };
+        let indent = &line[..line.len() - line.trim_start().len()];
+        for k in 0..count {
+            out.push_str(indent);
+            out.push_str(&replace_ident(&body, "i", &k.to_string()));
+            out.push('\n');
+        }
+    }
+    out
+}
+
+/// A batched matrix-matrix product whose M, N and K are static, so it can be lowered to sgemm.
+fn is_static_gemm(node: &LinearNode) -> bool {
+    if !matches!(node.op, Op::MatMul) || node.inputs.len() != 2 || node.inputs.iter().any(|i| i.view.is_some()) {
+        return false;
+    }
+    let a = &node.inputs[0].shape.dims;
+    let b = &node.inputs[1].shape.dims;
+    a.len() >= 2 && b.len() >= 2
+        && [&a[a.len() - 2], &a[a.len() - 1], &b[b.len() - 1]].iter().all(|d| matches!(d, Dim::Static(_)))
+}
+
+/// Edge of the square block a static rank-2 Transpose is staged through.
+const TRANSPOSE_TILE: usize = 32;
+
+/// Largest static reduce axis that ReduceSum unrolls into a single sum expression.
+const REDUCE_UNROLL_MAX: usize = 16;
+
+/// MatMuls whose M, N and K are all static and at least this large use the tiled loop nest.
+const BLOCKED_MATMUL_MIN_DIM: usize = 64;
+const MATMUL_TILE_M: usize = 64;
+const MATMUL_TILE_N: usize = 64;
+const MATMUL_TILE_K: usize = 8;
+
+/// xorshift64 step on `rng`; identical on every platform for a given seed.
+const XORSHIFT_STEP: &str = "rng ^= rng << 13; rng ^= rng >> 7; rng ^= rng << 17;";
+
+fn rng_seed(seed: u64) -> String {
+    format!("{}ULL", rng_state(seed))
+}
+
+fn rng_state(seed: u64) -> u64 {
+    // Mixed with a constant so that seed 0 does not produce the all-zero xorshift state
+    let state = seed ^ 0x9E3779B97F4A7C15;
+    if state == 0 { 1 } else { state }
+}
+
+/// Builds the index into a (possibly broadcast) input for the flat output index `flat`.
+/// Input dims of size 1 that are expanded in the output get a stride of zero.
+fn broadcast_index_expr(input: &Shape, output: &Shape, flat: &str) -> String {
+    if input == output {
+        return flat.to_string();
+    }
+    let offset = output.dims.len().saturating_sub(input.dims.len());
+    let mut terms = Vec::new();
+    let mut in_stride = "1".to_string();
+    for j in (0..input.dims.len()).rev() {
+        let k = j + offset;
+        if input.dims[j] != Dim::Static(1) {
+            let out_stride = output.dims[k + 1..].iter().map(|d| d.to_c_expr()).collect::<Vec<_>>().join(" * ");
+            let mut coord = if out_stride.is_empty() { flat.to_string() } else { format!("({} / ({}))", flat, out_stride) };
+            if k > 0 {
+                coord = format!("({} % ({}))", coord, output.dims[k].to_c_expr());
             }
+            terms.push(if in_stride == "1" { coord } else { format!("{} * ({})", coord, in_stride) });
         }
+        in_stride = if in_stride == "1" { input.dims[j].to_c_expr() } else { format!("{} * {}", in_stride, input.dims[j].to_c_expr()) };
     }
+    if terms.is_empty() { "0".to_string() } else { terms.join(" + ") }
+}
+
+/// Offset in the output buffer of the element at row-major index `flat`. Column-major
+/// recovers each coordinate from `flat` and weights it by the product of the preceding dims.
+pub(crate) fn output_index_expr(layout: Layout, shape: &Shape, flat: &str) -> String {
+    if layout == Layout::RowMajor || shape.dims.len() < 2 {
+        return flat.to_string();
+    }
+    let join = |dims: &[Dim]| dims.iter().map(|d| d.to_c_expr()).collect::<Vec<_>>().join(" * ");
+    let mut terms = Vec::new();
+    for (k, dim) in shape.dims.iter().enumerate() {
+        let mut coord = if k + 1 == shape.dims.len() {
+            flat.to_string()
+        } else {
+            format!("({} / ({}))", flat, join(&shape.dims[k + 1..]))
+        };
+        if k > 0 {
+            coord = format!("({} % ({}))", coord, dim.to_c_expr());
+        }
+        terms.push(if k == 0 { coord } else { format!("{} * ({})", coord, join(&shape.dims[..k])) });
+    }
+    terms.join(" + ")
+}
+
+fn indexed_input(input: &InputConnection, output: &Shape, flat: &str) -> String {
+    format!("{}[{}]", get_input_var(input), input_index_expr(input, output, flat))
+}
+
+/// Index into `input` of the element read for output element `flat`: broadcast dims get
+/// stride 0 and transposed views map the coordinates back onto the source layout.
+pub(crate) fn input_index_expr(input: &InputConnection, output: &Shape, flat: &str) -> String {
+    let idx = broadcast_index_expr(&input.shape, output, flat);
+    let Some(view) = &input.view else {
+        return idx;
+    };
+    let join = |dims: &[Dim]| dims.iter().map(|d| d.to_c_expr()).collect::<Vec<_>>().join(" * ");
+    let mut terms = Vec::new();
+    for (k, &axis) in view.permutation.iter().enumerate() {
+        let mut coord = if k + 1 == input.shape.dims.len() {
+            format!("({})", idx)
+        } else {
+            format!("(({}) / ({}))", idx, join(&input.shape.dims[k + 1..]))
+        };
+        if k > 0 {
+            coord = format!("({} % ({}))", coord, input.shape.dims[k].to_c_expr());
+        }
+        let stride = join(&view.source_shape.dims[axis + 1..]);
+        terms.push(if stride.is_empty() { coord } else { format!("{} * ({})", coord, stride) });
+    }
+    terms.join(" + ")
+}
+
+/// Rewrites the MatMul operand reads `LEFT[BATCH_A * M * K + i * K + l]` and
+/// `RIGHT[BATCH_B * K * N + l * N + j]` of a loop template for operands that are transposed views,
+/// which store the last two axes swapped. `dims` are the template's (M, K, N) placeholders.
+pub(crate) fn transposed_operand_reads(loops: String, node: &LinearNode, dims: (&str, &str, &str)) -> String {
+    let (m, k, n) = dims;
+    let mut loops = loops;
+    if node.inputs[0].view.is_some() {
+        loops = loops.replace(&format!("LEFT[BATCH_A * {m} * {k} + i * {k} + l]"), &format!("LEFT[BATCH_A * {m} * {k} + l * {m} + i]"));
+    }
+    if node.inputs[1].view.is_some() {
+        loops = loops.replace(&format!("RIGHT[BATCH_B * {k} * {n} + l * {n} + j]"), &format!("RIGHT[BATCH_B * {k} * {n} + j * {k} + l]"));
+    }
+    loops
+}
+
+/// Index of the matrix each MatMul operand reads for output batch `b`, filling a template's
+/// `BATCH_A`/`BATCH_B`. Operand batch dims broadcast against the output's like elementwise
+/// operands, so a batch of 1 (or none) always reads matrix 0.
+pub(crate) fn matmul_batch_indices(node: &LinearNode) -> (String, String) {
+    let out = &node.shape.dims;
+    let out_batch = Shape { dims: out[..out.len() - 2].to_vec() };
+    let operand = |input: &InputConnection| {
+        let dims = &input.shape.dims;
+        let idx = broadcast_index_expr(&Shape { dims: dims[..dims.len() - 2].to_vec() }, &out_batch, "b");
+        if idx.contains(' ') { format!("({})", idx) } else { idx }
+    };
+    (operand(&node.inputs[0]), operand(&node.inputs[1]))
+}
+
+/// Splits a shape around `axis` into C expressions for (outer size, axis size, inner size).
+fn axis_extents(shape: &Shape, axis: usize) -> (String, String, String) {
+    let join = |dims: &[Dim]| {
+        if dims.is_empty() {
+            "1".to_string()
+        } else {
+            dims.iter().map(|d| d.to_c_expr()).collect::<Vec<_>>().join(" * ")
+        }
+    };
+    (join(&shape.dims[..axis]), shape.dims[axis].to_c_expr(), join(&shape.dims[axis + 1..]))
+}
+
+/// Largest exponent `Pow` expands into repeated multiplication.
+pub const POW_EXPANSION_MAX_EXPONENT: u32 = 4;
+
+/// Exponent of a `Pow` whose right operand is a Constant holding one small positive integer
+/// (in every element), so codegen can multiply instead of calling `pow`.
+pub(crate) fn small_integer_exponent(ir: &LinearIR, exponent: &InputConnection) -> Option<u32> {
+    let source = ir.nodes.iter().find(|n| n.id == exponent.node_id)?;
+    let Op::Constant { values, .. } = &source.op else {
+        return None;
+    };
+    let first = *values.first()?;
+    let integral = first.fract() == 0.0 && first >= 1.0 && first <= POW_EXPANSION_MAX_EXPONENT as f32;
+    (integral && values.iter().all(|v| *v == first)).then_some(first as u32)
+}
+
+/// `base * base * ...` with `exponent` factors, parenthesized.
+pub(crate) fn repeated_product(base: &str, exponent: u32) -> String {
+    format!("({})", vec![base; exponent as usize].join(" * "))
+}
+
+/// `float` literal; constant folding can produce infinities and NaN, which have no literal form.
+fn float_literal(v: f32) -> String {
+    if v.is_nan() {
+        "NAN".to_string()
+    } else if v.is_infinite() {
+        if v > 0.0 { "INFINITY".to_string() } else { "-INFINITY".to_string() }
+    } else {
+        format!("{:?}f", v)
+    }
+}
+
+/// C scalar type a node computes in: `double` for F64, `float` for everything else.
+fn scalar_type(dtype: DataType) -> &'static str {
+    if dtype == DataType::F64 { "double" } else { "float" }
+}
+
+/// `<math.h>` function of the matching precision, e.g. `sin` for F64 and `sinf` otherwise.
+fn math_fn(name: &str, dtype: DataType) -> String {
+    if dtype == DataType::F64 { name.to_string() } else { format!("{}f", name) }
+}
+
+/// Floating literal of the matching precision.
+fn typed_literal(v: f32, dtype: DataType) -> String {
+    if dtype == DataType::F64 && v.is_finite() { format!("{:?}", v) } else { float_literal(v) }
 }
 
 fn get_input_var(input: &InputConnection) -> String {
-    let base = if let Some(in_name) = input.node_id.strip_prefix("inputs.") {
+    let base = if let Some(in_name) = input.node_id.strip_prefix("inputs.")
+        && !input.dtype.is_emulated() {
         "in_NAME".replace("NAME", &sanitize_id(in_name))
     } else {
         sanitize_id(&input.node_id)
     };
 
-    if let Ok(idx) = input.src_port.parse::<usize>() {
-        if idx > 0 {
-            let mut term = "(BASE + IDX * (SIZE))".to_string();
-            term = term.replace("BASE", &base);
-            term = term.replace("IDX", &idx.to_string());
-            term = term.replace("SIZE", &input.shape.to_c_size_expr());
-            return term;
-        }
+    // Later output ports follow the earlier ones in the source's slot
+    match &input.port_offset {
+        Some(offset) => format!("({} + ({}))", base, offset),
+        None => base,
     }
-    base
 }
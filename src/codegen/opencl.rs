@@ -0,0 +1,320 @@
+//! OpenCL backend. Each node becomes one or more kernels (see `kernels::node_segments`),
+//! collected into one OpenCL C program per module that is built when the runtime starts.
+//! Workspace slots are `cl_mem` buffers allocated by the runtime; module inputs are
+//! written to the device on entry and outputs read back on exit.
+
+use super::kernels::{collect_dim_vars, node_segments, Segment};
+use super::{constant_data_name, float_literal, generate_module_header, get_input_var, CompiledModule};
+use crate::core::op::Op;
+use crate::core::types::DataType;
+use crate::core::utils::sanitize_id;
+use crate::linearizer::ir::LinearIR;
+
+/// Host helpers shared by all OpenCL modules, emitted into runtime.c.
+pub const OPENCL_SUPPORT: &str = r#"#define CL_TARGET_OPENCL_VERSION 120
+#include <CL/cl.h>
+#include <stdio.h>
+
+#define SION_CL_CHECK(call) do { \
+    cl_int sion_cl_err = (call); \
+    if (sion_cl_err != CL_SUCCESS) { \
+        fprintf(stderr, "OpenCL error %d at %s:%d\n", (int)sion_cl_err, __FILE__, __LINE__); \
+        exit(1); \
+    } \
+} while (0)
+
+static cl_context sion_cl_context = NULL;
+static cl_command_queue sion_cl_queue = NULL;
+static cl_device_id sion_cl_device = NULL;
+
+static void sion_cl_init(void) {
+    if (sion_cl_context) return;
+    cl_platform_id platform;
+    cl_int err;
+    SION_CL_CHECK(clGetPlatformIDs(1, &platform, NULL));
+    if (clGetDeviceIDs(platform, CL_DEVICE_TYPE_GPU, 1, &sion_cl_device, NULL) != CL_SUCCESS) {
+        SION_CL_CHECK(clGetDeviceIDs(platform, CL_DEVICE_TYPE_ALL, 1, &sion_cl_device, NULL));
+    }
+    sion_cl_context = clCreateContext(NULL, 1, &sion_cl_device, NULL, NULL, &err);
+    SION_CL_CHECK(err);
+    sion_cl_queue = clCreateCommandQueue(sion_cl_context, sion_cl_device, 0, &err);
+    SION_CL_CHECK(err);
+}
+
+static cl_program sion_cl_build(const char* source) {
+    cl_int err;
+    cl_program program = clCreateProgramWithSource(sion_cl_context, 1, &source, NULL, &err);
+    SION_CL_CHECK(err);
+    if (clBuildProgram(program, 1, &sion_cl_device, NULL, NULL, NULL) != CL_SUCCESS) {
+        char log[16384];
+        clGetProgramBuildInfo(program, sion_cl_device, CL_PROGRAM_BUILD_LOG, sizeof(log), log, NULL);
+        fprintf(stderr, "OpenCL program build failed:\n%s\n", log);
+        exit(1);
+    }
+    return program;
+}
+
+static cl_kernel sion_cl_kernel(cl_program program, const char* name) {
+    cl_int err;
+    cl_kernel kernel = clCreateKernel(program, name, &err);
+    SION_CL_CHECK(err);
+    return kernel;
+}
+
+static cl_mem sion_cl_alloc(size_t bytes) {
+    cl_int err;
+    cl_mem buffer = clCreateBuffer(sion_cl_context, CL_MEM_READ_WRITE, bytes ? bytes : 1, NULL, &err);
+    SION_CL_CHECK(err);
+    return buffer;
+}
+
+static cl_mem sion_cl_constant(const void* data, size_t bytes) {
+    cl_int err;
+    cl_mem buffer = clCreateBuffer(sion_cl_context, CL_MEM_READ_ONLY | CL_MEM_COPY_HOST_PTR, bytes, (void*)data, &err);
+    SION_CL_CHECK(err);
+    return buffer;
+}
+
+/* Port buffers grow on demand and are kept for the lifetime of the process */
+static cl_mem sion_cl_port(cl_mem* buffer, size_t* capacity, size_t bytes) {
+    if (!*buffer || *capacity < bytes) {
+        if (*buffer) SION_CL_CHECK(clReleaseMemObject(*buffer));
+        *buffer = sion_cl_alloc(bytes);
+        *capacity = bytes;
+    }
+    return *buffer;
+}
+
+static void sion_cl_write(cl_mem buffer, const void* src, size_t bytes) {
+    if (bytes) SION_CL_CHECK(clEnqueueWriteBuffer(sion_cl_queue, buffer, CL_FALSE, 0, bytes, src, 0, NULL, NULL));
+}
+
+static void sion_cl_read(void* dst, cl_mem buffer, size_t bytes) {
+    if (bytes) SION_CL_CHECK(clEnqueueReadBuffer(sion_cl_queue, buffer, CL_TRUE, 0, bytes, dst, 0, NULL, NULL));
+}
+
+static void sion_cl_launch(cl_kernel kernel, const cl_mem* buffers, int buffer_count, const int32_t* dims, int dim_count, size_t work_items) {
+    if (work_items == 0) return;
+    for (int a = 0; a < buffer_count; a++) {
+        SION_CL_CHECK(clSetKernelArg(kernel, a, sizeof(cl_mem), &buffers[a]));
+    }
+    for (int d = 0; d < dim_count; d++) {
+        SION_CL_CHECK(clSetKernelArg(kernel, buffer_count + d, sizeof(cl_int), &dims[d]));
+    }
+    SION_CL_CHECK(clEnqueueNDRangeKernel(sion_cl_queue, kernel, 1, NULL, &work_items, NULL, 0, NULL, NULL));
+}
+"#;
+
+/// Maps the C math and integer names used by node bodies onto OpenCL C.
+const KERNEL_PRELUDE: &str = "#define sinf sin
+#define cosf cos
+#define fabsf fabs
+#define sqrtf sqrt
+#define expf exp
+#define logf log
+#define powf pow
+#define fminf fmin
+#define fmaxf fmax
+typedef int int32_t;
+typedef ulong uint64_t;
+";
+
+pub fn compile_module(module_id: &str, ir: &LinearIR) -> anyhow::Result<CompiledModule> {
+    Ok(CompiledModule {
+        id: module_id.to_string(),
+        c_source: generate_module_source(module_id, ir)?,
+        h_source: generate_module_header(module_id, ir),
+        workspace_slots: ir.get_workspace_slots(),
+    })
+}
+
+pub fn generate_module_source(module_id: &str, ir: &LinearIR) -> anyhow::Result<String> {
+    if let Some(port) = ir.inputs.iter().chain(ir.outputs.iter()).find(|p| p.dtype.is_emulated()) {
+        anyhow::bail!("OpenCL backend does not support {:?} port '{}' in module '{}'", port.dtype, port.name, module_id);
+    }
+
+    let dim_vars: Vec<String> = collect_dim_vars(ir).into_iter().collect();
+    let mut c = String::new();
+    let mut setup = String::new();
+    let mut init = String::new();
+
+    // Kernel arguments: every device buffer a node body can refer to, in a fixed order
+    let mut params = Vec::new();
+    let mut args = Vec::new();
+    for (port_idx, port) in ir.inputs.iter().enumerate() {
+        let name = sanitize_id(&port.name);
+        let mut write = "    cl_mem dev_in_NAME = sion_cl_port(&MOD_cl_ports[IDX], &MOD_cl_port_bytes[IDX], sizeof(TYPE) * (SIZE));\n    if (in_NAME) sion_cl_write(dev_in_NAME, in_NAME, sizeof(TYPE) * (SIZE));\n".to_string();
+        write = write.replace("MOD", module_id);
+        write = write.replace("IDX", &port_idx.to_string());
+        write = write.replace("TYPE", port.dtype.to_c_type());
+        write = write.replace("SIZE", &port.shape.to_c_size_expr());
+        write = write.replace("NAME", &name);
+        setup.push_str(&write);
+        params.push(format!("__global {}* in_{}", port.dtype.to_c_type(), name));
+        args.push(format!("dev_in_{}", name));
+    }
+    for (port_idx, port) in ir.outputs.iter().enumerate() {
+        let name = sanitize_id(&port.name);
+        let mut alloc = "    cl_mem dev_out_NAME = sion_cl_port(&MOD_cl_ports[IDX], &MOD_cl_port_bytes[IDX], sizeof(TYPE) * (SIZE));\n".to_string();
+        alloc = alloc.replace("MOD", module_id);
+        alloc = alloc.replace("IDX", &(ir.inputs.len() + port_idx).to_string());
+        alloc = alloc.replace("TYPE", port.dtype.to_c_type());
+        alloc = alloc.replace("SIZE", &port.shape.to_c_size_expr());
+        alloc = alloc.replace("NAME", &name);
+        setup.push_str(&alloc);
+        params.push(format!("__global {}* out_{}", port.dtype.to_c_type(), name));
+        args.push(format!("dev_out_{}", name));
+    }
+
+    // Flatten views are pointer aliases declared at the top of every kernel
+    let mut views = String::new();
+    let mut const_count = 0;
+    for node in &ir.nodes {
+        let id = sanitize_id(&node.id);
+        let c_type = node.compute_dtype().to_c_type();
+        if let Some(out_name) = &node.output_alias {
+            args.push(format!("dev_out_{}", sanitize_id(out_name)));
+        } else if let Op::Constant { values, .. } = &node.op {
+            let literals: Vec<String> = values.iter().map(|v| float_literal(*v)).collect();
+            let rows: Vec<String> = literals.chunks(8).map(|row| format!("    {}", row.join(", "))).collect();
+            let data = constant_data_name(module_id, &node.id);
+            let mut decl = "static const TYPE DATA[COUNT] = {\nVALUES\n};\n".to_string();
+            decl = decl.replace("TYPE", c_type);
+            decl = decl.replace("DATA", &data);
+            decl = decl.replace("COUNT", &values.len().to_string());
+            decl = decl.replace("VALUES", &rows.join(",\n"));
+            c.push_str(&decl);
+
+            init.push_str(&format!("    {}_cl_constants[{}] = sion_cl_constant({}, sizeof({}));\n", module_id, const_count, data, data));
+            args.push(format!("{}_cl_constants[{}]", module_id, const_count));
+            const_count += 1;
+        } else if matches!(node.op, Op::Flatten { .. }) {
+            let mut view = "    __global TYPE* ID = SRC;\n".to_string();
+            view = view.replace("TYPE", node.inputs[0].compute_dtype().to_c_type());
+            view = view.replace("ID", &id);
+            view = view.replace("SRC", &get_input_var(&node.inputs[0]));
+            views.push_str(&view);
+            continue;
+        } else if node.has_workspace_slot() {
+            args.push(format!("(cl_mem)workspace[{}]", node.offset));
+        } else {
+            continue;
+        }
+        params.push(format!("__global {}* {}", c_type, id));
+    }
+    params.extend(dim_vars.iter().map(|v| format!("int {}", v)));
+
+    // Kernel source and launches
+    let mut source = String::new();
+    if ir.nodes.iter().any(|n| n.dtype == DataType::F64) {
+        source.push_str("#pragma OPENCL EXTENSION cl_khr_fp64 : enable\n");
+    }
+    source.push_str(KERNEL_PRELUDE);
+    let mut launches = String::new();
+    let mut kernel_names = Vec::new();
+    for node in &ir.nodes {
+        let id = sanitize_id(&node.id);
+        for (k, segment) in node_segments(node, ir).iter().enumerate() {
+            let kernel_name = format!("k_{}_{}", id, k);
+            let (kernel, work_items) = match segment {
+                Segment::Parallel { var, count, body } => {
+                    let mut kernel = "\n__kernel void KERNEL(PARAMS) {\n    int LOOP_VAR = get_global_id(0);\n    if (LOOP_VAR >= (COUNT)) return;\nVIEWSBODY}\n".to_string();
+                    kernel = kernel.replace("KERNEL", &kernel_name);
+                    kernel = kernel.replace("LOOP_VAR", var);
+                    kernel = kernel.replace("COUNT", count);
+                    kernel = kernel.replace("PARAMS", &params.join(", "));
+                    kernel = kernel.replace("VIEWS", &views);
+                    kernel = kernel.replace("BODY", &global_pointers(body));
+                    (kernel, count.clone())
+                }
+                Segment::Serial(body) => {
+                    let mut kernel = "\n__kernel void KERNEL(PARAMS) {\nVIEWSBODY}\n".to_string();
+                    kernel = kernel.replace("KERNEL", &kernel_name);
+                    kernel = kernel.replace("PARAMS", &params.join(", "));
+                    kernel = kernel.replace("VIEWS", &views);
+                    kernel = kernel.replace("BODY", &global_pointers(body));
+                    (kernel, "1".to_string())
+                }
+            };
+            source.push_str(&kernel);
+
+            let mut launch = "    sion_cl_launch(MOD_cl_kernels[IDX], args, ARG_COUNT, dims, DIM_COUNT, (size_t)(WORK_ITEMS));\n".to_string();
+            launch = launch.replace("MOD", module_id);
+            launch = launch.replace("IDX", &kernel_names.len().to_string());
+            launch = launch.replace("ARG_COUNT", &args.len().to_string());
+            launch = launch.replace("DIM_COUNT", &dim_vars.len().to_string());
+            launch = launch.replace("WORK_ITEMS", &work_items);
+            launches.push_str(&launch);
+            kernel_names.push(kernel_name);
+        }
+    }
+
+    // Module state
+    let port_count = ir.inputs.len() + ir.outputs.len();
+    let literal: Vec<String> = source.lines()
+        .map(|line| format!("    \"{}\\n\"", line.replace('\\', "\\\\").replace('"', "\\\"")))
+        .collect();
+    let mut state = "static const char* MOD_cl_source =\nSOURCE;\nstatic cl_program MOD_cl_program = NULL;\nstatic cl_kernel MOD_cl_kernels[KERNELS];\nstatic cl_mem MOD_cl_ports[PORTS];\nstatic size_t MOD_cl_port_bytes[PORTS];\nstatic cl_mem MOD_cl_constants[CONSTANTS];\n\n".to_string();
+    state = state.replace("KERNELS", &kernel_names.len().max(1).to_string());
+    state = state.replace("PORTS", &port_count.max(1).to_string());
+    state = state.replace("CONSTANTS", &const_count.max(1).to_string());
+    state = state.replace("MOD", module_id);
+    state = state.replace("SOURCE", &literal.join("\n"));
+    if const_count > 0 {
+        c.push('\n');
+    }
+    c.push_str(&state);
+
+    // Built once when the runtime starts
+    c.push_str(&"void MOD_cl_init(void) {\n    if (MOD_cl_program) return;\n    MOD_cl_program = sion_cl_build(MOD_cl_source);\n".replace("MOD", module_id));
+    for (idx, name) in kernel_names.iter().enumerate() {
+        c.push_str(&format!("    {}_cl_kernels[{}] = sion_cl_kernel({}_cl_program, \"{}\");\n", module_id, idx, module_id, name));
+    }
+    c.push_str(&init);
+    c.push_str("}\n\n");
+
+    let mut func = "void FUNC_NAME_func(ARGS) {\n".to_string();
+    func = func.replace("FUNC_NAME", module_id);
+    func = func.replace("ARGS", &super::get_function_args(ir).join(", "));
+    c.push_str(&func);
+    c.push_str(&setup);
+    c.push_str(&"    cl_mem args[] = { ARGS };\n".replace("ARGS", &args.join(", ")));
+    if dim_vars.is_empty() {
+        c.push_str("    const int32_t* dims = NULL;\n");
+    } else {
+        c.push_str(&"    int32_t dims[] = { DIMS };\n".replace("DIMS", &dim_vars.join(", ")));
+    }
+    c.push('\n');
+    c.push_str(&launches);
+    c.push('\n');
+    for port in &ir.outputs {
+        let mut read = "    sion_cl_read(out_NAME, dev_out_NAME, sizeof(TYPE) * (SIZE));\n".to_string();
+        read = read.replace("TYPE", port.dtype.to_c_type());
+        read = read.replace("SIZE", &port.shape.to_c_size_expr());
+        read = read.replace("NAME", &sanitize_id(&port.name));
+        c.push_str(&read);
+    }
+    c.push_str("}\n");
+    Ok(c)
+}
+
+/// Local pointers in node bodies point into device buffers, which OpenCL C requires to be
+/// spelled out as the global address space, whatever their element type.
+fn global_pointers(body: &str) -> String {
+    body.split_inclusive('\n')
+        .map(|line| {
+            let statement = line.trim_start();
+            let indent = &line[..line.len() - statement.len()];
+            if is_pointer_declaration(statement) { format!("{}__global {}", indent, statement) } else { line.to_string() }
+        })
+        .collect()
+}
+
+/// `[const ]TYPE* NAME = ...`, the shape every local pointer in a node body takes.
+fn is_pointer_declaration(statement: &str) -> bool {
+    let is_ident = |s: &str| !s.is_empty() && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    let unqualified = statement.strip_prefix("const ").unwrap_or(statement);
+    unqualified.split_once("* ")
+        .and_then(|(ty, rest)| Some((ty, rest.split_once(" = ")?.0)))
+        .is_some_and(|(ty, name)| is_ident(ty) && is_ident(name))
+}
@@ -0,0 +1,638 @@
+//! Rust backend. Each module becomes a safe `PROG_func` over slices with the same argument
+//! order as the C version. Workspace slots are `Vec<f32>`s owned by the generated runtime,
+//! and symbolic dims are read from the runtime's `vars` atomics on entry.
+
+use super::kernels::collect_dim_vars;
+use super::{axis_extents, input_index_expr, matmul_batch_indices, output_index_expr, repeated_product, rng_state, small_integer_exponent, transposed_operand_reads, CompiledModule, XORSHIFT_STEP};
+use crate::core::op::Op;
+use crate::core::types::{DataType, Shape};
+use crate::core::utils::sanitize_id;
+use crate::linearizer::ir::{InputConnection, LinearIR, LinearNode};
+
+/// Half conversion helpers emitted into runtime.rs, bit-for-bit equal to `HALF_SUPPORT`.
+pub const HALF_SUPPORT: &str = r#"pub fn sion_half_to_float(h: u16) -> f32 {
+    let sign = ((h & 0x8000) as u32) << 16;
+    let exp = ((h >> 10) & 0x1F) as u32;
+    let mant = (h & 0x3FF) as u32;
+    if exp == 0 {
+        f32::from_bits((mant as f32 * 5.9604644775390625e-8f32).to_bits() | sign)
+    } else if exp == 31 {
+        f32::from_bits(sign | 0x7F80_0000 | (mant << 13))
+    } else {
+        f32::from_bits(sign | ((exp + 112) << 23) | (mant << 13))
+    }
+}
+
+pub fn sion_float_to_half(v: f32) -> u16 {
+    let mut bits = v.to_bits();
+    let sign = bits & 0x8000_0000;
+    bits ^= sign;
+    let out = if bits >= 0x4780_0000 {
+        if bits > 0x7F80_0000 { 0x7E00 } else { 0x7C00 }
+    } else if bits < 0x3880_0000 {
+        let denorm_magic = 126u32 << 23;
+        let sum = f32::from_bits(bits) + f32::from_bits(denorm_magic);
+        (sum.to_bits() - denorm_magic) as u16
+    } else {
+        let mant_odd = (bits >> 13) & 1;
+        (bits.wrapping_add(0xC800_0FFF).wrapping_add(mant_odd) >> 13) as u16
+    };
+    out | (sign >> 16) as u16
+}
+"#;
+
+pub fn rust_type(dtype: DataType) -> &'static str {
+    match dtype {
+        DataType::F16 => "u16",
+        DataType::F32 => "f32",
+        DataType::F64 => "f64",
+        DataType::I32 => "i32",
+        DataType::I64 => "i64",
+        DataType::U32 => "u32",
+    }
+}
+
+/// `f32` literal; constant folding can produce infinities and NaN, which have no literal form.
+pub fn float_literal(v: f32) -> String {
+    if v.is_nan() {
+        "f32::NAN".to_string()
+    } else if v.is_infinite() {
+        if v > 0.0 { "f32::INFINITY".to_string() } else { "f32::NEG_INFINITY".to_string() }
+    } else {
+        format!("{:?}f32", v)
+    }
+}
+
+pub fn compile_module(module_id: &str, ir: &LinearIR) -> anyhow::Result<CompiledModule> {
+    // Workspace slots are f32 vectors; other port dtypes are only converted at the boundary
+    let computes_natively = |n: &&LinearNode| matches!(n.op, Op::Input { .. } | Op::Output { .. } | Op::ArgMax { .. } | Op::ArgMin { .. });
+    if let Some(node) = ir.nodes.iter().filter(|n| !computes_natively(n)).find(|n| n.dtype != DataType::F32) {
+        anyhow::bail!("Rust backend does not support {:?} compute in node '{}' of module '{}'", node.dtype, node.id, module_id);
+    }
+    Ok(CompiledModule {
+        id: module_id.to_string(),
+        c_source: generate_module_source(module_id, ir),
+        // Rust modules are declared with `mod` by the generated main.rs
+        h_source: String::new(),
+        workspace_slots: ir.get_workspace_slots(),
+    })
+}
+
+pub fn generate_module_source(module_id: &str, ir: &LinearIR) -> String {
+    let mut c = String::new();
+    c.push_str("#![allow(non_snake_case, non_upper_case_globals, unused_variables, unused_mut, unused_parens, clippy::all)]\n\n");
+
+    let dim_vars = collect_dim_vars(ir);
+    if !dim_vars.is_empty() {
+        c.push_str("use crate::runtime::vars;\nuse std::sync::atomic::Ordering;\n");
+    }
+    let half_in = ir.inputs.iter().any(|p| p.dtype == DataType::F16);
+    let half_out = ir.outputs.iter().any(|p| p.dtype == DataType::F16);
+    match (half_in, half_out) {
+        (true, true) => c.push_str("use crate::runtime::{sion_float_to_half, sion_half_to_float};\n"),
+        (true, false) => c.push_str("use crate::runtime::sion_half_to_float;\n"),
+        (false, true) => c.push_str("use crate::runtime::sion_float_to_half;\n"),
+        (false, false) => {}
+    }
+    if !dim_vars.is_empty() || half_in || half_out {
+        c.push('\n');
+    }
+
+    // Constant data at file scope
+    for node in &ir.nodes {
+        if let Op::Constant { values, .. } = &node.op {
+            let literals: Vec<String> = values.iter().map(|v| float_literal(*v)).collect();
+            let rows: Vec<String> = literals.chunks(8).map(|row| format!("    {}", row.join(", "))).collect();
+            let mut decl = "static CONST_ID: [f32; COUNT] = [\nVALUES\n];\n".to_string();
+            decl = decl.replace("ID", &sanitize_id(&node.id));
+            decl = decl.replace("COUNT", &values.len().to_string());
+            decl = decl.replace("VALUES", &rows.join(",\n"));
+            c.push_str(&decl);
+        }
+    }
+    if ir.nodes.iter().any(|n| matches!(n.op, Op::Constant { .. })) {
+        c.push('\n');
+    }
+
+    let mut func_sig = "pub fn FUNC_NAME_func(ARGS) {\n".to_string();
+    func_sig = func_sig.replace("FUNC_NAME", module_id);
+    func_sig = func_sig.replace("ARGS", &get_function_args(ir).join(", "));
+    c.push_str(&func_sig);
+
+    for var in &dim_vars {
+        c.push_str(&"    let VAR = vars::VAR.load(Ordering::Relaxed);\n".replace("VAR", var));
+    }
+
+    // Compute happens in f32, so other storage types are converted once on entry
+    for port in &ir.inputs {
+        if port.dtype != DataType::F32 && !port.dtype.is_emulated() {
+            c.push_str(&"    let in_NAME: Vec<f32> = in_NAME.iter().map(|&v| v as f32).collect();\n".replace("NAME", &sanitize_id(&port.name)));
+        }
+    }
+
+    // Split the workspace into disjoint slot borrows
+    let slot_count = ir.nodes.iter().filter(|n| n.has_workspace_slot()).count();
+    if slot_count > 0 {
+        let slots: Vec<String> = (0..slot_count).map(|i| format!("ws_{}", i)).collect();
+        let mut split = "    let [SLOTS, ..] = workspace else {\n        panic!(\"FUNC_NAME_func expects COUNT workspace slots\");\n    };\n".to_string();
+        split = split.replace("SLOTS", &slots.join(", "));
+        split = split.replace("FUNC_NAME", module_id);
+        split = split.replace("COUNT", &slot_count.to_string());
+        c.push_str(&split);
+    }
+
+    for node in &ir.nodes {
+        let id = sanitize_id(&node.id);
+        if let Some(out_name) = &node.output_alias {
+            let mut alias = "    let ID: &mut [TYPE] = &mut out_NAME[..];\n".to_string();
+            alias = alias.replace("TYPE", rust_type(node.dtype));
+            alias = alias.replace("ID", &id);
+            alias = alias.replace("NAME", &sanitize_id(out_name));
+            c.push_str(&alias);
+        } else if matches!(node.op, Op::Constant { .. }) {
+            c.push_str(&"    let ID: &[f32] = &CONST_ID;\n".replace("ID", &id));
+        } else if node.has_workspace_slot() {
+            let mut slot = "    let ID: &mut [f32] = &mut ws_OFFSET[..];\n".to_string();
+            slot = slot.replace("ID", &id);
+            slot = slot.replace("OFFSET", &node.offset.to_string());
+            c.push_str(&slot);
+        }
+    }
+    c.push('\n');
+
+    for node in &ir.nodes {
+        emit_node_code(&mut c, node, ir);
+    }
+
+    c.push_str("}\n");
+    c
+}
+
+fn get_function_args(ir: &LinearIR) -> Vec<String> {
+    let mut args = vec!["workspace: &mut [Vec<f32>]".to_string()];
+    for input in &ir.inputs {
+        args.push(format!("in_{}: &[{}]", sanitize_id(&input.name), rust_type(input.dtype)));
+    }
+    for port in &ir.outputs {
+        args.push(format!("out_{}: &mut [{}]", sanitize_id(&port.name), rust_type(port.dtype)));
+    }
+    args
+}
+
+fn emit_node_code(c: &mut String, node: &LinearNode, ir: &LinearIR) {
+    let node_var = sanitize_id(&node.id);
+    let size_expr = node.shape.to_c_size_expr();
+
+    match &node.op {
+        Op::Input { name } if node.dtype == DataType::F16 => {
+            let mut line = "    for i in 0..SIZE { VAR[i] = sion_half_to_float(in_NAME[i]); }\n".to_string();
+            line = line.replace("SIZE", &size_expr);
+            line = line.replace("VAR", &node_var);
+            line = line.replace("NAME", &sanitize_id(name));
+            c.push_str(&line);
+        }
+        Op::Input { name } => {
+            c.push_str("    // Input NAME handled via args\n".replace("NAME", name).as_str());
+        }
+        Op::Constant { .. } => {
+            c.push_str("    // Constant ID is static data\n".replace("ID", &node.id).as_str());
+        }
+        Op::RandomUniform { low, high, seed, .. } => {
+            let mut line = "    {\n        let mut rng: u64 = RNG_SEED;\n        for i in 0..SIZE {\n            XORSHIFT\n            let u = (rng >> 40) as f32 * (1.0f32 / 16777216.0f32);\n            VAR[i] = LOW + (HIGH - LOW) * u;\n        }\n    }\n".to_string();
+            line = line.replace("XORSHIFT", XORSHIFT_STEP);
+            line = line.replace("RNG_SEED", &format!("{}u64", rng_state(*seed)));
+            line = line.replace("SIZE", &size_expr);
+            line = line.replace("VAR", &node_var);
+            line = line.replace("LOW", &float_literal(*low));
+            line = line.replace("HIGH", &float_literal(*high));
+            c.push_str(&line);
+        }
+        Op::Iota { .. } => {
+            let mut line = "    for i in 0..SIZE { VAR[i] = i as TYPE; }\n".to_string();
+            line = line.replace("SIZE", &size_expr);
+            line = line.replace("VAR", &node_var);
+            line = line.replace("TYPE", rust_type(node.dtype));
+            c.push_str(&line);
+        }
+        Op::RandomNormal { mean, std, seed, .. } => {
+            // Box-Muller transform over two uniform samples in (0, 1]
+            let mut line = "    {\n        let mut rng: u64 = RNG_SEED;\n        for i in 0..SIZE {\n            XORSHIFT\n            let u1 = ((rng >> 40) + 1) as f32 * (1.0f32 / 16777216.0f32);\n            XORSHIFT\n            let u2 = (rng >> 40) as f32 * (1.0f32 / 16777216.0f32);\n            VAR[i] = MEAN + STD * (-2.0f32 * u1.ln()).sqrt() * (6.2831853f32 * u2).cos();\n        }\n    }\n".to_string();
+            line = line.replace("XORSHIFT", XORSHIFT_STEP);
+            line = line.replace("RNG_SEED", &format!("{}u64", rng_state(*seed)));
+            line = line.replace("SIZE", &size_expr);
+            line = line.replace("VAR", &node_var);
+            line = line.replace("MEAN", &float_literal(*mean));
+            line = line.replace("STD", &float_literal(*std));
+            c.push_str(&line);
+        }
+        Op::Output { name, .. } if ir.nodes.iter().any(|n| n.output_alias.as_deref() == Some(name.as_str())) => {
+            c.push_str("    // Output NAME written in place\n".replace("NAME", name).as_str());
+        }
+        Op::Output { name, layout } => {
+            let src = get_input_var(&node.inputs[0]);
+            let mut line = match node.dtype {
+                DataType::F16 => "    for i in 0..SIZE { out_NAME[OUT_IDX] = sion_float_to_half(SRC[i]); }\n".to_string(),
+                DataType::F32 => "    for i in 0..SIZE { out_NAME[OUT_IDX] = SRC[i]; }\n".to_string(),
+                dtype => "    for i in 0..SIZE { out_NAME[OUT_IDX] = SRC[i] as TYPE; }\n".replace("TYPE", rust_type(dtype)),
+            };
+            line = line.replace("OUT_IDX", &output_index_expr(*layout, &node.shape, "i"));
+            line = line.replace("SIZE", &size_expr);
+            line = line.replace("NAME", &sanitize_id(name));
+            line = line.replace("SRC", &src);
+            c.push_str(&line);
+        }
+        Op::Add | Op::Sub | Op::Mul | Op::Div | Op::Min | Op::Max | Op::Pow
+        | Op::Mod | Op::And | Op::Or | Op::Xor | Op::Shl | Op::Shr => {
+            // Each operand is indexed against the output shape so broadcast dims get stride 0
+            let left = indexed_input(&node.inputs[0], &node.shape, "i");
+            let right = indexed_input(&node.inputs[1], &node.shape, "i");
+            let expanded_pow = match node.op {
+                Op::Pow => small_integer_exponent(ir, &node.inputs[1]).map(|exponent| repeated_product("BIN_LEFT", exponent)),
+                _ => None,
+            };
+            let expr = match node.op {
+                Op::Add => "BIN_LEFT + BIN_RIGHT",
+                Op::Sub => "BIN_LEFT - BIN_RIGHT",
+                Op::Mul => "BIN_LEFT * BIN_RIGHT",
+                Op::Div => "BIN_LEFT / BIN_RIGHT",
+                Op::Min => "BIN_LEFT.min(BIN_RIGHT)",
+                Op::Max => "BIN_LEFT.max(BIN_RIGHT)",
+                Op::Pow => expanded_pow.as_deref().unwrap_or("BIN_LEFT.powf(BIN_RIGHT)"),
+                Op::Mod => "BIN_LEFT % BIN_RIGHT",
+                Op::And => "BIN_LEFT & BIN_RIGHT",
+                Op::Or => "BIN_LEFT | BIN_RIGHT",
+                Op::Xor => "BIN_LEFT ^ BIN_RIGHT",
+                Op::Shl => "BIN_LEFT << BIN_RIGHT",
+                Op::Shr => "BIN_LEFT >> BIN_RIGHT",
+                _ => unreachable!(),
+            };
+            let mut line = "    for i in 0..SIZE { VAR[i] = EXPR; }\n".to_string();
+            line = line.replace("EXPR", expr);
+            line = line.replace("SIZE", &size_expr);
+            line = line.replace("VAR", &node_var);
+            line = line.replace("BIN_LEFT", &left);
+            line = line.replace("BIN_RIGHT", &right);
+            c.push_str(&line);
+        }
+        Op::Lerp => {
+            let a = indexed_input(&node.inputs[0], &node.shape, "i");
+            let b = indexed_input(&node.inputs[1], &node.shape, "i");
+            let t = indexed_input(&node.inputs[2], &node.shape, "i");
+            let mut line = "    for i in 0..SIZE { VAR[i] = LERP_A + LERP_T * (LERP_B - LERP_A); }\n".to_string();
+            line = line.replace("SIZE", &size_expr);
+            line = line.replace("VAR", &node_var);
+            line = line.replace("LERP_A", &a);
+            line = line.replace("LERP_B", &b);
+            line = line.replace("LERP_T", &t);
+            c.push_str(&line);
+        }
+        Op::Sin | Op::Abs | Op::Sqrt | Op::Square | Op::Exp | Op::Log => {
+            let src = indexed_input(&node.inputs[0], &node.shape, "i");
+            let expr = match node.op {
+                Op::Sin => "SRC[i].sin()",
+                Op::Abs => "SRC[i].abs()",
+                Op::Sqrt => "SRC[i].sqrt()",
+                Op::Exp => "SRC[i].exp()",
+                Op::Log => "SRC[i].ln()",
+                Op::Square => "SRC[i] * SRC[i]",
+                _ => unreachable!(),
+            };
+            let mut line = "    for i in 0..SIZE { VAR[i] = EXPR; }\n".to_string();
+            line = line.replace("EXPR", expr);
+            line = line.replace("SIZE", &size_expr);
+            line = line.replace("VAR", &node_var);
+            line = line.replace("SRC[i]", &src);
+            c.push_str(&line);
+        }
+        Op::Broadcast { .. } => {
+            let src = indexed_input(&node.inputs[0], &node.shape, "i");
+            let mut line = "    for i in 0..SIZE { VAR[i] = SRC[i]; }\n".to_string();
+            line = line.replace("SIZE", &size_expr);
+            line = line.replace("VAR", &node_var);
+            line = line.replace("SRC[i]", &src);
+            c.push_str(&line);
+        }
+        // Flatten only reinterprets the shape; the view is bound once its input is computed
+        Op::Flatten { .. } if node.output_alias.is_none() => {
+            let mut view = "    let ID: &[f32] = &SRC[..];\n".to_string();
+            view = view.replace("ID", &node_var);
+            view = view.replace("SRC", &get_input_var(&node.inputs[0]));
+            c.push_str(&view);
+        }
+        Op::Reshape { .. } | Op::Flatten { .. } | Op::Identity | Op::Dropout { .. } => {
+            let src = get_input_var(&node.inputs[0]);
+            let mut line = "    for i in 0..SIZE { VAR[i] = SRC[i]; }\n".to_string();
+            line = line.replace("SIZE", &size_expr);
+            line = line.replace("VAR", &node_var);
+            line = line.replace("SRC", &src);
+            c.push_str(&line);
+        }
+        Op::ReduceSum { axis, init, kahan } => {
+            let src = get_input_var(&node.inputs[0]);
+            let (outer_size, reduce_dim, inner_size) = axis_extents(&node.inputs[0].shape, *axis);
+
+            let mut loops = if *kahan {
+                "    for out in 0..OUTER * INNER {\n        let o = out / INNER;\n        let i = out % INNER;\n        let mut acc = INIT;\n        let mut comp = 0.0f32;\n        for r in 0..REDUCE {\n            let y = SRC[o * REDUCE * INNER + r * INNER + i] - comp;\n            let t = acc + y;\n            comp = (t - acc) - y;\n            acc = t;\n        }\n        VAR[out] = acc;\n    }\n".to_string()
+            } else {
+                "    for out in 0..OUTER * INNER {\n        let o = out / INNER;\n        let i = out % INNER;\n        let mut acc = INIT;\n        for r in 0..REDUCE {\n            acc += SRC[o * REDUCE * INNER + r * INNER + i];\n        }\n        VAR[out] = acc;\n    }\n".to_string()
+            };
+            loops = loops.replace("INIT", &float_literal(*init));
+            loops = loops.replace("OUTER", &outer_size);
+            loops = loops.replace("INNER", &inner_size);
+            loops = loops.replace("REDUCE", &reduce_dim);
+            loops = loops.replace("VAR", &node_var);
+            loops = loops.replace("SRC", &src);
+            c.push_str(&loops);
+        }
+        Op::ArgMax { axis } | Op::ArgMin { axis } => {
+            let src = get_input_var(&node.inputs[0]);
+            let (outer_size, reduce_dim, inner_size) = axis_extents(&node.inputs[0].shape, *axis);
+            // The index lands in f32 workspace or directly in an integer output port
+            let index_type = if node.output_alias.is_some() { rust_type(node.dtype) } else { "f32" };
+
+            // Strict comparison keeps the first index on ties
+            let mut loops = "    for out in 0..OUTER * INNER {\n        let o = out / INNER;\n        let i = out % INNER;\n        let lane = &SRC[o * REDUCE * INNER + i..];\n        let mut best = 0;\n        for r in 1..REDUCE {\n            if lane[r * INNER] CMP lane[best * INNER] { best = r; }\n        }\n        VAR[out] = best as INDEX_TYPE;\n    }\n".to_string();
+            loops = loops.replace("CMP", if matches!(node.op, Op::ArgMax { .. }) { ">" } else { "<" });
+            loops = loops.replace("INDEX_TYPE", index_type);
+            loops = loops.replace("OUTER", &outer_size);
+            loops = loops.replace("INNER", &inner_size);
+            loops = loops.replace("REDUCE", &reduce_dim);
+            loops = loops.replace("VAR", &node_var);
+            loops = loops.replace("SRC", &src);
+            c.push_str(&loops);
+        }
+        Op::ReduceVar { axis } | Op::ReduceStd { axis } => {
+            let src = get_input_var(&node.inputs[0]);
+            let (outer_size, reduce_dim, inner_size) = axis_extents(&node.inputs[0].shape, *axis);
+
+            // Two passes: the mean first, then the mean of squared deviations
+            let mut loops = "    for out in 0..OUTER * INNER {\n        let o = out / INNER;\n        let i = out % INNER;\n        let mut mean = 0.0f32;\n        for r in 0..REDUCE {\n            mean += SRC[o * REDUCE * INNER + r * INNER + i];\n        }\n        mean /= (REDUCE) as f32;\n        let mut sq_dev = 0.0f32;\n        for r in 0..REDUCE {\n            let d = SRC[o * REDUCE * INNER + r * INNER + i] - mean;\n            sq_dev += d * d;\n        }\n        VAR[out] = (sq_dev / (REDUCE) as f32)FINISH;\n    }\n".to_string();
+            loops = loops.replace("FINISH", if matches!(node.op, Op::ReduceStd { .. }) { ".sqrt()" } else { "" });
+            loops = loops.replace("OUTER", &outer_size);
+            loops = loops.replace("INNER", &inner_size);
+            loops = loops.replace("REDUCE", &reduce_dim);
+            loops = loops.replace("VAR", &node_var);
+            loops = loops.replace("SRC", &src);
+            c.push_str(&loops);
+        }
+        Op::Normalize { axis, epsilon } => {
+            let src = get_input_var(&node.inputs[0]);
+            let (outer_size, reduce_dim, inner_size) = axis_extents(&node.inputs[0].shape, *axis);
+
+            let mut loops = "    for out in 0..OUTER * INNER {\n        let o = out / INNER;\n        let i = out % INNER;\n        let mut sq_sum = 0.0f32;\n        for r in 0..REDUCE {\n            let v = SRC[o * REDUCE * INNER + r * INNER + i];\n            sq_sum += v * v;\n        }\n        let inv_norm = 1.0f32 / (sq_sum + EPSILON).sqrt();\n        for r in 0..REDUCE {\n            VAR[o * REDUCE * INNER + r * INNER + i] = SRC[o * REDUCE * INNER + r * INNER + i] * inv_norm;\n        }\n    }\n".to_string();
+            loops = loops.replace("OUTER", &outer_size);
+            loops = loops.replace("INNER", &inner_size);
+            loops = loops.replace("REDUCE", &reduce_dim);
+            loops = loops.replace("EPSILON", &float_literal(*epsilon));
+            loops = loops.replace("VAR", &node_var);
+            loops = loops.replace("SRC", &src);
+            c.push_str(&loops);
+        }
+        Op::LayerNorm { axis, epsilon, affine } => {
+            let data = &node.inputs[node.op.port_index("input").unwrap_or(0)];
+            let src = get_input_var(data);
+            let (outer_size, reduce_dim, inner_size) = axis_extents(&data.shape, *axis);
+
+            // Mean, then variance around it, then the normalized lane
+            let mut loops = "    for out in 0..OUTER * INNER {\n        let o = out / INNER;\n        let i = out % INNER;\n        let mut mean = 0.0f32;\n        for r in 0..REDUCE {\n            mean += SRC[o * REDUCE * INNER + r * INNER + i];\n        }\n        mean /= (REDUCE) as f32;\n        let mut variance = 0.0f32;\n        for r in 0..REDUCE {\n            let d = SRC[o * REDUCE * INNER + r * INNER + i] - mean;\n            variance += d * d;\n        }\n        variance /= (REDUCE) as f32;\n        let inv_std = 1.0f32 / (variance + EPSILON).sqrt();\n        for r in 0..REDUCE {\n            let idx = o * REDUCE * INNER + r * INNER + i;\n            VAR[idx] = (SRC[idx] - mean) * inv_stdAFFINE;\n        }\n    }\n".to_string();
+            loops = loops.replace("OUTER", &outer_size);
+            loops = loops.replace("INNER", &inner_size);
+            loops = loops.replace("REDUCE", &reduce_dim);
+            loops = loops.replace("EPSILON", &float_literal(*epsilon));
+            loops = loops.replace("VAR", &node_var);
+            loops = loops.replace("SRC", &src);
+            let affine_terms = if *affine {
+                let scale = &node.inputs[node.op.port_index("scale").unwrap_or(0)];
+                let bias = &node.inputs[node.op.port_index("bias").unwrap_or(0)];
+                format!(" * {} + {}", indexed_input(scale, &node.shape, "idx"), indexed_input(bias, &node.shape, "idx"))
+            } else {
+                String::new()
+            };
+            loops = loops.replace("AFFINE", &affine_terms);
+            c.push_str(&loops);
+        }
+        Op::Conv1D { stride, padding } => {
+            let src = get_input_var(&node.inputs[0]);
+            let kernel = get_input_var(&node.inputs[1]);
+            let x_dims = &node.inputs[0].shape.dims;
+            let w_dims = &node.inputs[1].shape.dims;
+
+            let mut loops = "    for co in 0..C_OUT {\n        for x in 0..L_OUT {\n            let mut acc = 0.0f32;\n            for ci in 0..C_IN {\n                for k in 0..KSZ {\n                    let pos = (x * STRIDE + k) as isize - PAD;\n                    if pos < 0 || pos >= (L_IN) as isize { continue; }\n                    acc += SRC[ci * L_IN + pos as usize] * KERNEL[(co * C_IN + ci) * KSZ + k];\n                }\n            }\n            VAR[co * L_OUT + x] = acc;\n        }\n    }\n".to_string();
+            loops = loops.replace("C_OUT", &w_dims[0].to_c_expr());
+            loops = loops.replace("L_OUT", &node.shape.dims[1].to_c_expr());
+            loops = loops.replace("C_IN", &x_dims[0].to_c_expr());
+            loops = loops.replace("L_IN", &x_dims[1].to_c_expr());
+            loops = loops.replace("KSZ", &w_dims[2].to_c_expr());
+            loops = loops.replace("STRIDE", &stride.to_string());
+            loops = loops.replace("PAD", &padding.to_string());
+            loops = loops.replace("SRC", &src);
+            loops = loops.replace("KERNEL", &kernel);
+            loops = loops.replace("VAR", &node_var);
+            c.push_str(&loops);
+        }
+        Op::DepthwiseConv2D { stride, padding } => {
+            let src = get_input_var(&node.inputs[0]);
+            let kernel = get_input_var(&node.inputs[1]);
+            let x_dims = &node.inputs[0].shape.dims;
+            let w_dims = &node.inputs[1].shape.dims;
+
+            let mut loops = "    for ch in 0..CHANNELS {\n        for y in 0..H_OUT {\n            for x in 0..W_OUT {\n                let mut acc = 0.0f32;\n                for ky in 0..KH {\n                    let iy = (y * STRIDE + ky) as isize - PAD;\n                    if iy < 0 || iy >= (H_IN) as isize { continue; }\n                    for kx in 0..KW {\n                        let ix = (x * STRIDE + kx) as isize - PAD;\n                        if ix < 0 || ix >= (W_IN) as isize { continue; }\n                        acc += SRC[(ch * H_IN + iy as usize) * W_IN + ix as usize] * KERNEL[(ch * KH + ky) * KW + kx];\n                    }\n                }\n                VAR[(ch * H_OUT + y) * W_OUT + x] = acc;\n            }\n        }\n    }\n".to_string();
+            loops = loops.replace("CHANNELS", &x_dims[0].to_c_expr());
+            loops = loops.replace("H_OUT", &node.shape.dims[1].to_c_expr());
+            loops = loops.replace("W_OUT", &node.shape.dims[2].to_c_expr());
+            loops = loops.replace("H_IN", &x_dims[1].to_c_expr());
+            loops = loops.replace("W_IN", &x_dims[2].to_c_expr());
+            loops = loops.replace("KH", &w_dims[1].to_c_expr());
+            loops = loops.replace("KW", &w_dims[2].to_c_expr());
+            loops = loops.replace("STRIDE", &stride.to_string());
+            loops = loops.replace("PAD", &padding.to_string());
+            loops = loops.replace("SRC", &src);
+            loops = loops.replace("KERNEL", &kernel);
+            loops = loops.replace("VAR", &node_var);
+            c.push_str(&loops);
+        }
+        Op::Resize { scale, mode } => {
+            let src = get_input_var(&node.inputs[0]);
+            let in_dims = &node.inputs[0].shape.dims;
+            let mut loops = if mode == "bilinear" {
+                // Half-pixel centers, clamped to the border
+                "    for ch in 0..CHANNELS {\n        let plane = &SRC[ch * H_IN * W_IN..];\n        for y in 0..H_OUT {\n            let sy = ((y as f32 + 0.5f32) / SCALE_H as f32 - 0.5f32).max(0.0f32).min(((H_IN) - 1) as f32);\n            let y0 = sy as usize;\n            let y1 = if y0 + 1 < H_IN { y0 + 1 } else { H_IN - 1 };\n            let wy = sy - y0 as f32;\n            for x in 0..W_OUT {\n                let sx = ((x as f32 + 0.5f32) / SCALE_W as f32 - 0.5f32).max(0.0f32).min(((W_IN) - 1) as f32);\n                let x0 = sx as usize;\n                let x1 = if x0 + 1 < W_IN { x0 + 1 } else { W_IN - 1 };\n                let wx = sx - x0 as f32;\n                let top = plane[y0 * W_IN + x0] * (1.0f32 - wx) + plane[y0 * W_IN + x1] * wx;\n                let bottom = plane[y1 * W_IN + x0] * (1.0f32 - wx) + plane[y1 * W_IN + x1] * wx;\n                VAR[(ch * H_OUT + y) * W_OUT + x] = top * (1.0f32 - wy) + bottom * wy;\n            }\n        }\n    }\n".to_string()
+            } else {
+                "    for ch in 0..CHANNELS {\n        for y in 0..H_OUT {\n            for x in 0..W_OUT {\n                VAR[(ch * H_OUT + y) * W_OUT + x] = SRC[(ch * H_IN + y / SCALE_H) * W_IN + x / SCALE_W];\n            }\n        }\n    }\n".to_string()
+            };
+            loops = loops.replace("CHANNELS", &in_dims[0].to_c_expr());
+            loops = loops.replace("H_OUT", &node.shape.dims[1].to_c_expr());
+            loops = loops.replace("W_OUT", &node.shape.dims[2].to_c_expr());
+            loops = loops.replace("H_IN", &in_dims[1].to_c_expr());
+            loops = loops.replace("W_IN", &in_dims[2].to_c_expr());
+            loops = loops.replace("SCALE_H", &scale.0.to_string());
+            loops = loops.replace("SCALE_W", &scale.1.to_string());
+            loops = loops.replace("SRC", &src);
+            loops = loops.replace("VAR", &node_var);
+            c.push_str(&loops);
+        }
+        Op::Diag => {
+            let src = get_input_var(&node.inputs[0]);
+            let mut line = "    for i in 0..DIM { VAR[i] = SRC[i * DIM + i]; }\n".to_string();
+            line = line.replace("DIM", &node.inputs[0].shape.dims[0].to_c_expr());
+            line = line.replace("VAR", &node_var);
+            line = line.replace("SRC", &src);
+            c.push_str(&line);
+        }
+        Op::Trace => {
+            let src = get_input_var(&node.inputs[0]);
+            let mut line = "    VAR[0] = 0.0f32;\n    for i in 0..DIM { VAR[0] += SRC[i * DIM + i]; }\n".to_string();
+            line = line.replace("DIM", &node.inputs[0].shape.dims[0].to_c_expr());
+            line = line.replace("VAR", &node_var);
+            line = line.replace("SRC", &src);
+            c.push_str(&line);
+        }
+        Op::GatherND => {
+            let data = get_input_var(&node.inputs[0]);
+            let indices = get_input_var(&node.inputs[1]);
+            let data_dims = &node.inputs[0].shape.dims;
+            let rank = data_dims.len();
+
+            c.push_str(&"    for n in 0..SIZE {\n        let mut flat = 0;\n".replace("SIZE", &size_expr));
+            for (axis, dim) in data_dims.iter().enumerate() {
+                let stride = data_dims[axis + 1..].iter().map(|d| d.to_c_expr()).collect::<Vec<_>>().join(" * ");
+                let stride = if stride.is_empty() { "1".to_string() } else { stride };
+                let mut line = "        { let coord = IDX[n * RANK + AXIS] as i64; let coord = if coord < 0 { 0 } else if coord >= (DIM) as i64 { (DIM) as i64 - 1 } else { coord }; flat += coord as usize * (STRIDE); }\n".to_string();
+                line = line.replace("IDX", &indices);
+                line = line.replace("RANK", &rank.to_string());
+                line = line.replace("AXIS", &axis.to_string());
+                line = line.replace("DIM", &dim.to_c_expr());
+                line = line.replace("STRIDE", &stride);
+                c.push_str(&line);
+            }
+            let mut line = "        VAR[n] = DATA[flat];\n    }\n".to_string();
+            line = line.replace("VAR", &node_var);
+            line = line.replace("DATA", &data);
+            c.push_str(&line);
+        }
+        Op::MatMul if node.inputs[1].shape.dims.len() == 1 => {
+            let left = get_input_var(&node.inputs[0]);
+            let right = get_input_var(&node.inputs[1]);
+            let a_shape = &node.inputs[0].shape;
+
+            let mut loops = "    for i in 0..ROWS {\n        let row = &LEFT[i * COLS..];\n        let mut acc = 0.0f32;\n        for l in 0..COLS {\n            acc += row[l] * RIGHT[l];\n        }\n        VAR[i] = acc;\n    }\n".to_string();
+            loops = loops.replace("ROWS", &a_shape.dims[0].to_c_expr());
+            loops = loops.replace("COLS", &a_shape.dims[1].to_c_expr());
+            loops = loops.replace("VAR", &node_var);
+            loops = loops.replace("LEFT", &left);
+            loops = loops.replace("RIGHT", &right);
+            c.push_str(&loops);
+        }
+        Op::MatMul => {
+            let left = get_input_var(&node.inputs[0]);
+            let right = get_input_var(&node.inputs[1]);
+            let a_shape = &node.inputs[0].shape;
+            let b_shape = &node.inputs[1].shape;
+
+            let mut loops = "    let batch_VAR = (SIZE) / ((GEMM_M) * (GEMM_N));\n    for b in 0..batch_VAR {\n        for i in 0..GEMM_M {\n            for j in 0..GEMM_N {\n                let mut acc = 0.0f32;\n                for l in 0..GEMM_K {\n                    acc += LEFT[BATCH_A * GEMM_M * GEMM_K + i * GEMM_K + l] * RIGHT[BATCH_B * GEMM_K * GEMM_N + l * GEMM_N + j];\n                }\n                VAR[b * GEMM_M * GEMM_N + i * GEMM_N + j] = acc;\n            }\n        }\n    }\n".to_string();
+            loops = transposed_operand_reads(loops, node, ("GEMM_M", "GEMM_K", "GEMM_N"));
+            loops = loops.replace("SIZE", &size_expr);
+            loops = loops.replace("GEMM_M", &a_shape.dims[a_shape.dims.len() - 2].to_c_expr());
+            loops = loops.replace("GEMM_K", &a_shape.dims[a_shape.dims.len() - 1].to_c_expr());
+            loops = loops.replace("GEMM_N", &b_shape.dims[b_shape.dims.len() - 1].to_c_expr());
+            let (batch_a, batch_b) = matmul_batch_indices(node);
+            loops = loops.replace("BATCH_A", &batch_a);
+            loops = loops.replace("BATCH_B", &batch_b);
+            loops = loops.replace("VAR", &node_var);
+            loops = loops.replace("LEFT", &left);
+            loops = loops.replace("RIGHT", &right);
+            c.push_str(&loops);
+        }
+        Op::Split { axis, parts } => {
+            // Same slot layout as the C backend: part p starts at p * SIZE
+            let src = get_input_var(&node.inputs[0]);
+            let (outer_size, split_len, inner_size) = axis_extents(&node.shape, *axis);
+            let mut line = if outer_size == "1" {
+                "    for i in 0..SIZE * PARTS { VAR[i] = SRC[i]; }\n".to_string()
+            } else {
+                "    for i in 0..SIZE * PARTS {\n        let p = i / (SIZE);\n        let j = i % (SIZE);\n        let o = j / (SPLIT_LEN * INNER);\n        let r = (j / INNER) % SPLIT_LEN;\n        let k = j % INNER;\n        VAR[i] = SRC[(o * AXIS_DIM + p * SPLIT_LEN + r) * INNER + k];\n    }\n".to_string()
+            };
+            line = line.replace("SPLIT_LEN", &format!("({})", split_len));
+            line = line.replace("INNER", &format!("({})", inner_size));
+            line = line.replace("AXIS_DIM", &format!("({})", node.inputs[0].shape.dims[*axis].to_c_expr()));
+            line = line.replace("SIZE", &size_expr);
+            line = line.replace("PARTS", &parts.to_string());
+            line = line.replace("VAR", &node_var);
+            line = line.replace("SRC", &src);
+            c.push_str(&line);
+        }
+        Op::Window { axis, size, stride } => {
+            // Same layout as the C backend: window w starts at input offset w * STRIDE on `axis`
+            let src = get_input_var(&node.inputs[0]);
+            let (outer_size, axis_dim, inner_size) = axis_extents(&node.inputs[0].shape, *axis);
+            let mut line = "    for i in 0..SIZE {\n        let w = i / (OUTER * WIN_LEN * INNER);\n        let j = i % (OUTER * WIN_LEN * INNER);\n        let o = j / (WIN_LEN * INNER);\n        let r = (j / INNER) % WIN_LEN;\n        let k = j % INNER;\n        VAR[i] = SRC[(o * AXIS_DIM + w * STRIDE + r) * INNER + k];\n    }\n".to_string();
+            line = line.replace("OUTER", &format!("({})", outer_size));
+            line = line.replace("WIN_LEN", &size.to_string());
+            line = line.replace("INNER", &format!("({})", inner_size));
+            line = line.replace("AXIS_DIM", &format!("({})", axis_dim));
+            line = line.replace("STRIDE", &stride.to_string());
+            line = line.replace("SIZE", &size_expr);
+            line = line.replace("VAR", &node_var);
+            line = line.replace("SRC", &src);
+            c.push_str(&line);
+        }
+        Op::Transpose { permutation } => {
+            let src = get_input_var(&node.inputs[0]);
+            let in_shape = &node.inputs[0].shape;
+
+            for (i, dim) in in_shape.dims.iter().enumerate() {
+                let mut line = "    for dIDX in 0..DIM {\n".to_string();
+                line = line.replace("IDX", &i.to_string());
+                line = line.replace("DIM", &dim.to_c_expr());
+                c.push_str(&line);
+            }
+
+            let mut in_idx = "0".to_string();
+            let mut stride = "1".to_string();
+            for i in (0..in_shape.dims.len()).rev() {
+                in_idx = "((IN_IDX) + (dIDX) * (STRIDE))".replace("IN_IDX", &in_idx).replace("IDX", &i.to_string()).replace("STRIDE", &stride);
+                stride = "((STRIDE) * (DIM))".replace("STRIDE", &stride).replace("DIM", &in_shape.dims[i].to_c_expr());
+            }
+
+            let mut out_idx = "0".to_string();
+            let mut out_stride = "1".to_string();
+            for i in (0..permutation.len()).rev() {
+                let target_axis = permutation[i];
+                out_idx = "((OUT_IDX) + (dIDX) * (STRIDE))".replace("OUT_IDX", &out_idx).replace("IDX", &target_axis.to_string()).replace("STRIDE", &out_stride);
+                out_stride = "((STRIDE) * (DIM))".replace("STRIDE", &out_stride).replace("DIM", &in_shape.dims[target_axis].to_c_expr());
+            }
+
+            let mut copy_line = "    VAR[OUT_IDX] = SRC[IN_IDX];\n".to_string();
+            copy_line = copy_line.replace("VAR", &node_var);
+            copy_line = copy_line.replace("OUT_IDX", &out_idx);
+            copy_line = copy_line.replace("SRC", &src);
+            copy_line = copy_line.replace("IN_IDX", &in_idx);
+            c.push_str(&copy_line);
+
+            for _ in &in_shape.dims {
+                c.push_str("    }\n");
+            }
+        }
+    }
+}
+
+fn indexed_input(input: &InputConnection, output: &Shape, flat: &str) -> String {
+    format!("{}[{}]", get_input_var(input), input_index_expr(input, output, flat))
+}
+
+/// Like the C version, but later ports of a multi-output node are subslices instead of
+/// pointer offsets.
+fn get_input_var(input: &InputConnection) -> String {
+    let base = if let Some(in_name) = input.node_id.strip_prefix("inputs.")
+        && !input.dtype.is_emulated() {
+        "in_NAME".replace("NAME", &sanitize_id(in_name))
+    } else {
+        sanitize_id(&input.node_id)
+    };
+
+    // Later output ports follow the earlier ones in the source's slot
+    match &input.port_offset {
+        Some(offset) => format!("{}[({})..]", base, offset),
+        None => base,
+    }
+}
@@ -1,25 +1,141 @@
 use serde::{Deserialize, Serialize};
-use crate::core::types::Dim;
+use crate::core::types::{DataType, Dim, Layout, ReduceMode};
 use anyhow::{Context, anyhow};
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum Op {
     // Unary
     Sin, Abs, Sqrt, Square, Exp, Log,
+    Identity,
+    /// Inference-only: compiled as Identity, `rate` is kept for graph compatibility.
+    Dropout { rate: f32 },
     // Binary
     Add, Sub, Mul, Div, Min, Max, Pow,
+    /// Remainder with the sign of the dividend (C `%` on integers, `fmod` on floats).
+    Mod,
+    /// Bitwise ops, integer dtypes only. Shifts by a negative count or by at least the
+    /// bit width are undefined.
+    And, Or, Xor, Shl, Shr,
+    // Ternary
+    Lerp,
     // Special
     Input { name: String },
-    Constant { values: Vec<f32> },
+    Constant { values: Vec<f32>, shape: Option<Vec<Dim>> },
+    RandomUniform { low: f32, high: f32, seed: u64, shape: Vec<Dim> },
+    RandomNormal { mean: f32, std: f32, seed: u64, shape: Vec<Dim> },
+    /// `[0, 1, ..., len - 1]` as a 1-D tensor of `dtype`.
+    Iota { len: Dim, dtype: DataType },
     Transpose { permutation: Vec<usize> },
-    ReduceSum { axis: usize },
+    /// `init` biases the accumulator; `kahan` switches to compensated summation.
+    ReduceSum { axis: usize, init: f32, kahan: bool },
+    ReduceVar { axis: usize },
+    ReduceStd { axis: usize },
+    ArgMax { axis: usize },
+    ArgMin { axis: usize },
+    Normalize { axis: usize, epsilon: f32 },
+    /// `(x - mean) / sqrt(var + epsilon)` over `axis`. `affine` is set when `scale` and
+    /// `bias` inputs are linked, which then broadcast against the input.
+    LayerNorm { axis: usize, epsilon: f32, affine: bool },
     MatMul,
+    Diag,
+    Trace,
+    Conv1D { stride: usize, padding: usize },
+    DepthwiseConv2D { stride: usize, padding: usize },
+    GatherND,
+    Resize { scale: (usize, usize), mode: String },
     Split { axis: usize, parts: usize },
-    Output { name: String },
+    /// Overlapping windows of `size` along `axis`, `stride` apart, stacked on a new leading
+    /// window-count dimension.
+    Window { axis: usize, size: usize, stride: usize },
+    Output { name: String, layout: Layout },
     Reshape { new_shape: Vec<Dim> },
+    /// Merges the dims from `start_axis` onward into one. With `merge_leading` (the older
+    /// `axis` form) the dims before it merge too, giving `[prod(..axis), prod(axis..)]`.
+    Flatten { start_axis: usize, merge_leading: bool },
+    /// Materializes the input expanded to `shape`; size-1 input dims repeat along the target.
+    Broadcast { shape: Vec<Dim> },
 }
 
 impl Op {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Op::Sin => "Sin",
+            Op::Abs => "Abs",
+            Op::Sqrt => "Sqrt",
+            Op::Square => "Square",
+            Op::Exp => "Exp",
+            Op::Log => "Log",
+            Op::Identity => "Identity",
+            Op::Dropout { .. } => "Dropout",
+            Op::Add => "Add",
+            Op::Sub => "Sub",
+            Op::Mul => "Mul",
+            Op::Div => "Div",
+            Op::Min => "Min",
+            Op::Max => "Max",
+            Op::Pow => "Pow",
+            Op::Mod => "Mod",
+            Op::And => "And",
+            Op::Or => "Or",
+            Op::Xor => "Xor",
+            Op::Shl => "Shl",
+            Op::Shr => "Shr",
+            Op::Lerp => "Lerp",
+            Op::Input { .. } => "Input",
+            Op::Constant { .. } => "Constant",
+            Op::RandomUniform { .. } => "RandomUniform",
+            Op::RandomNormal { .. } => "RandomNormal",
+            Op::Iota { .. } => "Iota",
+            Op::Transpose { .. } => "Transpose",
+            Op::ReduceSum { .. } => "ReduceSum",
+            Op::ReduceVar { .. } => "ReduceVar",
+            Op::ReduceStd { .. } => "ReduceStd",
+            Op::ArgMax { .. } => "ArgMax",
+            Op::ArgMin { .. } => "ArgMin",
+            Op::Normalize { .. } => "Normalize",
+            Op::LayerNorm { .. } => "LayerNorm",
+            Op::MatMul => "MatMul",
+            Op::Conv1D { .. } => "Conv1D",
+            Op::DepthwiseConv2D { .. } => "DepthwiseConv2D",
+            Op::GatherND => "GatherND",
+            Op::Diag => "Diag",
+            Op::Trace => "Trace",
+            Op::Resize { .. } => "Resize",
+            Op::Split { .. } => "Split",
+            Op::Window { .. } => "Window",
+            Op::Output { .. } => "Output",
+            Op::Reshape { .. } => "Reshape",
+            Op::Flatten { .. } => "Flatten",
+            Op::Broadcast { .. } => "Broadcast",
+        }
+    }
+
+    /// Operand index of the input linked to `port`.
+    pub fn port_index(&self, port: &str) -> Option<usize> {
+        self.input_ports().iter().position(|p| *p == port)
+    }
+
+    /// Named input ports in operand order, which is sorted by name like incoming links are.
+    /// Every port takes exactly one link.
+    pub fn input_ports(&self) -> &'static [&'static str] {
+        match self {
+            Op::Input { .. } | Op::Constant { .. } | Op::RandomUniform { .. } | Op::RandomNormal { .. }
+            | Op::Iota { .. } => &[],
+            Op::Add | Op::Sub | Op::Mul | Op::Div | Op::Min | Op::Max | Op::Pow | Op::Mod
+            | Op::And | Op::Or | Op::Xor | Op::Shl | Op::Shr | Op::MatMul => &["left", "right"],
+            Op::Lerp => &["a", "b", "t"],
+            Op::Conv1D { .. } | Op::DepthwiseConv2D { .. } => &["input", "kernel"],
+            Op::GatherND => &["data", "indices"],
+            Op::LayerNorm { affine: true, .. } => &["bias", "input", "scale"],
+            Op::Sin | Op::Abs | Op::Sqrt | Op::Square | Op::Exp | Op::Log | Op::Identity
+            | Op::Dropout { .. } | Op::Transpose { .. } | Op::ReduceSum { .. } | Op::ReduceVar { .. }
+            | Op::ReduceStd { .. } | Op::ArgMax { .. } | Op::ArgMin { .. } | Op::Normalize { .. }
+            | Op::LayerNorm { affine: false, .. }
+            | Op::Diag | Op::Trace | Op::Resize { .. } | Op::Split { .. } | Op::Window { .. } | Op::Output { .. }
+            | Op::Reshape { .. } | Op::Flatten { .. } | Op::Broadcast { .. } => &["input"],
+        }
+    }
+
     pub fn from_json_value(json: &serde_json::Value) -> anyhow::Result<Self> {
         let (name, params) = if let Some(s) = json.as_str() {
             (s, serde_json::json!({}))
@@ -42,6 +158,11 @@ impl Op {
             "Square" => Ok(Op::Square),
             "Exp" => Ok(Op::Exp),
             "Log" => Ok(Op::Log),
+            "Identity" => Ok(Op::Identity),
+            "Dropout" => {
+                let rate = params.get("rate").and_then(|v| v.as_f64()).unwrap_or(0.5) as f32;
+                Ok(Op::Dropout { rate })
+            }
             "Add" => Ok(Op::Add),
             "Sub" => Ok(Op::Sub),
             "Mul" => Ok(Op::Mul),
@@ -49,25 +170,135 @@ impl Op {
             "Min" => Ok(Op::Min),
             "Max" => Ok(Op::Max),
             "Pow" => Ok(Op::Pow),
+            "Mod" => Ok(Op::Mod),
+            "And" => Ok(Op::And),
+            "Or" => Ok(Op::Or),
+            "Xor" => Ok(Op::Xor),
+            "Shl" => Ok(Op::Shl),
+            "Shr" => Ok(Op::Shr),
+            "Lerp" => Ok(Op::Lerp),
             "MatMul" => Ok(Op::MatMul),
+            "GatherND" => Ok(Op::GatherND),
+            "Diag" => Ok(Op::Diag),
+            "Trace" => Ok(Op::Trace),
+            "Conv1D" | "DepthwiseConv2D" => {
+                let stride = params.get("stride").and_then(|v| v.as_u64()).unwrap_or(1) as usize;
+                let padding = params.get("padding").and_then(|v| v.as_u64()).unwrap_or(0) as usize;
+                if name == "Conv1D" {
+                    Ok(Op::Conv1D { stride, padding })
+                } else {
+                    Ok(Op::DepthwiseConv2D { stride, padding })
+                }
+            }
+            "Resize" => {
+                let scale: (usize, usize) = serde_json::from_value(params.get("scale").cloned().unwrap_or(serde_json::json!([1, 1])))
+                    .context("Failed to parse Resize scale, expected [scale_h, scale_w]")?;
+                let mode = params.get("mode").and_then(|v| v.as_str()).unwrap_or("nearest").to_string();
+                if mode != "nearest" && mode != "bilinear" {
+                    return Err(anyhow!("Unknown Resize mode '{}', expected 'nearest' or 'bilinear'", mode));
+                }
+                Ok(Op::Resize { scale, mode })
+            }
             "Split" => {
                 let axis = params.get("axis").and_then(|v| v.as_u64()).unwrap_or(0) as usize;
                 let parts = params.get("parts").and_then(|v| v.as_u64()).unwrap_or(2) as usize;
                 Ok(Op::Split { axis, parts })
             }
+            "Window" => {
+                let axis = params.get("axis").and_then(|v| v.as_u64()).unwrap_or(0) as usize;
+                let size = params.get("size").and_then(|v| v.as_u64())
+                    .context("Window requires a 'size'")? as usize;
+                let stride = params.get("stride").and_then(|v| v.as_u64()).unwrap_or(1) as usize;
+                if size == 0 || stride == 0 {
+                    return Err(anyhow!("Window size and stride must be positive, found size {} stride {}", size, stride));
+                }
+                Ok(Op::Window { axis, size, stride })
+            }
             "Reshape" => {
                 let new_shape: Vec<Dim> = serde_json::from_value(params.get("new_shape").cloned().unwrap_or_default())
                     .context("Failed to parse Reshape new_shape")?;
                 Ok(Op::Reshape { new_shape })
             }
+            "Broadcast" => {
+                let shape: Vec<Dim> = serde_json::from_value(params.get("shape").cloned().unwrap_or_default())
+                    .context("Failed to parse Broadcast shape")?;
+                Ok(Op::Broadcast { shape })
+            }
+            "Transpose" => {
+                let permutation: Vec<usize> = serde_json::from_value(params.get("permutation").cloned().unwrap_or_default())
+                    .context("Failed to parse Transpose permutation")?;
+                Ok(Op::Transpose { permutation })
+            }
+            "Flatten" => {
+                let axis = |key: &str| params.get(key).and_then(|v| v.as_u64()).map(|v| v as usize);
+                match (axis("start_axis"), axis("axis")) {
+                    (Some(_), Some(_)) => Err(anyhow!("Flatten takes either 'start_axis' or the older 'axis', not both")),
+                    (None, Some(axis)) => Ok(Op::Flatten { start_axis: axis, merge_leading: true }),
+                    (start_axis, None) => Ok(Op::Flatten { start_axis: start_axis.unwrap_or(1), merge_leading: false }),
+                }
+            }
             "ReduceSum" => {
                 let axis = params.get("axis").and_then(|v| v.as_u64()).unwrap_or(0) as usize;
-                Ok(Op::ReduceSum { axis })
+                let init = params.get("init").and_then(|v| v.as_f64()).unwrap_or(0.0) as f32;
+                let kahan = match params.get("reduce_mode").and_then(|v| v.as_str()) {
+                    Some(mode) => ReduceMode::from_name(mode)? == ReduceMode::Kahan,
+                    None => params.get("kahan").and_then(|v| v.as_bool()).unwrap_or(false),
+                };
+                Ok(Op::ReduceSum { axis, init, kahan })
+            }
+            "ReduceVar" | "ReduceStd" => {
+                let axis = params.get("axis").and_then(|v| v.as_u64()).unwrap_or(0) as usize;
+                if name == "ReduceVar" {
+                    Ok(Op::ReduceVar { axis })
+                } else {
+                    Ok(Op::ReduceStd { axis })
+                }
+            }
+            "ArgMax" | "ArgMin" => {
+                let axis = params.get("axis").and_then(|v| v.as_u64()).unwrap_or(0) as usize;
+                if name == "ArgMax" {
+                    Ok(Op::ArgMax { axis })
+                } else {
+                    Ok(Op::ArgMin { axis })
+                }
+            }
+            "Normalize" | "L2Normalize" => {
+                let axis = params.get("axis").and_then(|v| v.as_u64()).unwrap_or(0) as usize;
+                let epsilon = params.get("epsilon").and_then(|v| v.as_f64()).unwrap_or(1e-12) as f32;
+                Ok(Op::Normalize { axis, epsilon })
+            }
+            "LayerNorm" => {
+                let axis = params.get("axis").and_then(|v| v.as_u64())
+                    .context("LayerNorm requires an 'axis'")? as usize;
+                let epsilon = params.get("epsilon").and_then(|v| v.as_f64()).unwrap_or(1e-5) as f32;
+                Ok(Op::LayerNorm { axis, epsilon, affine: false })
             }
             "Constant" => {
                 let values: Vec<f32> = serde_json::from_value(params.get("values").cloned().unwrap_or_default())
                     .context("Failed to parse Constant values")?;
-                Ok(Op::Constant { values })
+                let shape: Option<Vec<Dim>> = serde_json::from_value(params.get("shape").cloned().unwrap_or_default())
+                    .context("Failed to parse Constant shape")?;
+                Ok(Op::Constant { values, shape })
+            }
+            "RandomUniform" | "RandomNormal" => {
+                let shape: Vec<Dim> = serde_json::from_value(params.get("shape").cloned().unwrap_or_default())
+                    .with_context(|| format!("Failed to parse {} shape", name))?;
+                let seed = params.get("seed").and_then(|v| v.as_u64()).unwrap_or(0);
+                let param = |key: &str, default: f32| params.get(key).and_then(|v| v.as_f64()).map(|v| v as f32).unwrap_or(default);
+                if name == "RandomUniform" {
+                    Ok(Op::RandomUniform { low: param("low", 0.0), high: param("high", 1.0), seed, shape })
+                } else {
+                    Ok(Op::RandomNormal { mean: param("mean", 0.0), std: param("std", 1.0), seed, shape })
+                }
+            }
+            "Iota" => {
+                let len: Dim = serde_json::from_value(params.get("len").cloned().context("Iota requires a 'len'")?)
+                    .context("Failed to parse Iota len")?;
+                let dtype = DataType::from_name(params.get("dtype").and_then(|v| v.as_str()).unwrap_or("f32"))?;
+                if dtype == DataType::F16 {
+                    return Err(anyhow!("Iota cannot produce f16 indices; use f32 or an integer dtype"));
+                }
+                Ok(Op::Iota { len, dtype })
             }
             "Input" => {
                 let name = params.get("name").and_then(|v| v.as_str()).unwrap_or("unknown").to_string();
@@ -75,7 +306,7 @@ impl Op {
             }
             "Output" => {
                 let name = params.get("name").and_then(|v| v.as_str()).unwrap_or("unknown").to_string();
-                Ok(Op::Output { name })
+                Ok(Op::Output { name, layout: Layout::RowMajor })
             }
             _ => Err(anyhow!("Unknown op: {}", name)),
         }
@@ -2,6 +2,7 @@ use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum DataType {
+    F16,
     F32,
     F64,
     I32,
@@ -10,8 +11,9 @@ pub enum DataType {
 }
 
 impl DataType {
-    pub fn to_c_type(&self) -> &'static str {
+    pub fn to_c_type(self) -> &'static str {
         match self {
+            DataType::F16 => "sion_half",
             DataType::F32 => "float",
             DataType::F64 => "double",
             DataType::I32 => "int32_t",
@@ -19,6 +21,89 @@ impl DataType {
             DataType::U32 => "uint32_t",
         }
     }
+
+    pub fn size_bytes(self) -> usize {
+        match self {
+            DataType::F16 => 2,
+            DataType::F32 | DataType::I32 | DataType::U32 => 4,
+            DataType::F64 | DataType::I64 => 8,
+        }
+    }
+
+    pub fn is_integer(self) -> bool {
+        matches!(self, DataType::I32 | DataType::I64 | DataType::U32)
+    }
+
+    /// Storage-only types: values are converted to float for compute and back on store.
+    pub fn is_emulated(self) -> bool {
+        matches!(self, DataType::F16)
+    }
+
+    /// Canonical manifest name, accepted back by `from_name`.
+    pub fn name(self) -> &'static str {
+        match self {
+            DataType::F16 => "f16",
+            DataType::F32 => "f32",
+            DataType::F64 => "f64",
+            DataType::I32 => "i32",
+            DataType::I64 => "i64",
+            DataType::U32 => "u32",
+        }
+    }
+
+    pub fn from_name(name: &str) -> anyhow::Result<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "half" | "f16" | "float16" => Ok(DataType::F16),
+            "float" | "f32" | "float32" => Ok(DataType::F32),
+            "double" | "f64" | "float64" => Ok(DataType::F64),
+            "int" | "i32" | "int32" => Ok(DataType::I32),
+            "i64" | "int64" => Ok(DataType::I64),
+            "u32" | "uint32" => Ok(DataType::U32),
+            _ => Err(anyhow::anyhow!(
+                "Unknown dtype '{}' (expected f16/half/float16, f32/float/float32, f64/double/float64, i32/int/int32, i64/int64 or u32/uint32)",
+                name)),
+        }
+    }
+}
+
+/// Element order of an output buffer. Column-major reverses the axis order, so the first
+/// axis varies fastest; for a matrix this stores the transpose.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Layout {
+    #[default]
+    RowMajor,
+    ColumnMajor,
+}
+
+impl Layout {
+    pub fn from_name(name: &str) -> anyhow::Result<Self> {
+        match name {
+            "row_major" => Ok(Layout::RowMajor),
+            "column_major" => Ok(Layout::ColumnMajor),
+            _ => Err(anyhow::anyhow!("Unknown layout '{}' (expected \"row_major\" or \"column_major\")", name)),
+        }
+    }
+}
+
+/// Accumulation strategy for ReduceSum. Every mode reduces each output serially, so
+/// results do not depend on OpenMP scheduling; Kahan also compensates rounding error.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ReduceMode {
+    #[default]
+    Default,
+    Kahan,
+}
+
+impl ReduceMode {
+    pub fn from_name(name: &str) -> anyhow::Result<Self> {
+        match name {
+            "default" => Ok(ReduceMode::Default),
+            "kahan" => Ok(ReduceMode::Kahan),
+            _ => Err(anyhow::anyhow!("Unknown reduce_mode '{}' (expected \"default\" or \"kahan\")", name)),
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
@@ -53,6 +138,14 @@ impl Shape {
             .collect::<Vec<_>>()
             .join(" * ")
     }
+
+    /// Element count when every dim is static.
+    pub fn static_size(&self) -> Option<usize> {
+        self.dims.iter().try_fold(1, |acc, d| match d {
+            Dim::Static(v) => Some(acc * v),
+            Dim::Variable(_) => None,
+        })
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -66,4 +159,12 @@ pub struct Port {
 pub struct WorkspaceSlot {
     pub shape: Shape,
     pub dtype: DataType,
-}
\ No newline at end of file
+    /// Allocated element count, padded to the workspace alignment.
+    pub size_expr: String,
+}
+/// Where a node was defined: the graph file and the 1-based line of its `"id"`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SourceLocation {
+    pub file: String,
+    pub line: usize,
+}
@@ -1,3 +1,110 @@
+use petgraph::algo::tarjan_scc;
+use petgraph::graph::DiGraph;
+use std::path::Path;
+
+/// Turns a manifest or graph id into a valid C identifier. The mapping is injective so
+/// distinct ids never share a name: ASCII letters and digits are kept, as is a `_` followed
+/// by one of them (or ending the id). Any other character, a `_` that would start a `__`
+/// run, and a leading digit are written as `__<hex code point>_`, e.g. `sub/conv` becomes
+/// `sub__2f_conv` and `2d-blur` becomes `__32_d__2d_blur`.
 pub fn sanitize_id(id: &str) -> String {
-    id.replace("/", "_").replace(".", "_")
-}
\ No newline at end of file
+    let chars: Vec<char> = id.chars().collect();
+    let mut out = String::with_capacity(id.len());
+    for (i, &c) in chars.iter().enumerate() {
+        let plain = match c {
+            '_' => chars.get(i + 1).is_none_or(|next| next.is_ascii_alphanumeric()),
+            c if c.is_ascii_digit() => i > 0,
+            c => c.is_ascii_alphanumeric(),
+        };
+        if plain {
+            out.push(c);
+        } else {
+            out.push_str(&format!("__{:x}_", c as u32));
+        }
+    }
+    if out.is_empty() {
+        out.push_str("__");
+    }
+    out
+}
+
+/// Replaces whole-identifier occurrences of `ident` in a C expression.
+pub fn replace_ident(text: &str, ident: &str, with: &str) -> String {
+    let is_ident = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    let mut out = String::new();
+    let mut token = String::new();
+    for c in text.chars().chain(std::iter::once(' ')) {
+        if is_ident(c) {
+            token.push(c);
+            continue;
+        }
+        out.push_str(if token == ident { with } else { &token });
+        token.clear();
+        out.push(c);
+    }
+    out.pop();
+    out
+}
+
+/// Names the file and `line:column` of a JSON parse failure and quotes the offending line,
+/// so a typo in one of many graph files can be found without guessing.
+pub fn json_parse_context(err: anyhow::Error, path: &Path, content: &str) -> anyhow::Error {
+    let Some((line, column)) = err.downcast_ref::<serde_json::Error>().map(|e| (e.line(), e.column())) else {
+        return err.context(format!("Failed to parse {}", path.display()));
+    };
+    let mut message = format!("Failed to parse {}:{}:{}", path.display(), line, column);
+    // Minified files are one long line, which is not worth quoting
+    if let Some(text) = content.lines().nth(line.saturating_sub(1)).filter(|t| t.len() <= 160) {
+        let caret: String = text.chars().take(column.saturating_sub(1)).map(|c| if c == '\t' { '\t' } else { ' ' }).collect();
+        message.push_str(&format!("\n    {}\n    {}^", text.trim_end(), caret));
+    }
+    err.context(message)
+}
+
+/// Finds a strongly connected component forming a cycle and returns the labels of its nodes.
+///
+/// ```
+/// use sion_flow_rt::core::op::Op;
+/// use sion_flow_rt::core::types::{Dim, Shape};
+/// use sion_flow_rt::inliner::builder::GraphBuilder;
+/// use sion_flow_rt::inliner::raw_ir::RawEdge;
+///
+/// let mut builder = GraphBuilder::new();
+/// let x = builder.input("x", Shape { dims: vec![Dim::Static(4)] });
+/// let a = builder.named_op("a", Op::Sin, &[x]);
+/// let b = builder.named_op("b", Op::Abs, &[a]);
+/// let c = builder.named_op("c", Op::Sqrt, &[b]);
+/// builder.output("out", &c);
+///
+/// // Feed c back into a, closing the loop a -> b -> c -> a
+/// let (mut raw, input_specs) = builder.build();
+/// let find = |id: &str| raw.graph.node_indices().find(|&i| raw.graph[i].id == id).unwrap();
+/// let (from, to) = (find("c"), find("a"));
+/// raw.graph.add_edge(from, to, RawEdge { src_port: "output".to_string(), dst_port: "input".to_string() });
+///
+/// let err = sion_flow_rt::resolver::resolve_module(raw, input_specs).unwrap_err().to_string();
+/// assert!(err.contains("a, b, c"), "{}", err);
+/// ```
+pub fn find_cycle<N, E>(graph: &DiGraph<N, E>, label: impl Fn(&N) -> String) -> Vec<String> {
+    for scc in tarjan_scc(graph) {
+        let is_cycle = scc.len() > 1 || graph.contains_edge(scc[0], scc[0]);
+        if is_cycle {
+            let mut ids: Vec<String> = scc.iter().map(|&idx| label(&graph[idx])).collect();
+            ids.sort();
+            return ids;
+        }
+    }
+    Vec::new()
+}
+
+/// Widens an IEEE 754 binary16 bit pattern to f32.
+pub fn half_to_f32(h: u16) -> f32 {
+    let sign = ((h & 0x8000) as u32) << 16;
+    let exp = ((h >> 10) & 0x1F) as u32;
+    let mant = (h & 0x3FF) as u32;
+    match exp {
+        0 => f32::from_bits((mant as f32 * 5.960_464_5e-8_f32).to_bits() | sign),
+        31 => f32::from_bits(sign | 0x7F80_0000 | (mant << 13)),
+        _ => f32::from_bits(sign | ((exp + 112) << 23) | (mant << 13)),
+    }
+}
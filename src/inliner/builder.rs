@@ -0,0 +1,109 @@
+//! Programmatic construction of a `RawIR`, bypassing the JSON graph format. The result
+//! feeds straight into `resolver::resolve_module` together with the collected input specs.
+//!
+//! ```
+//! use sion_flow_rt::core::op::Op;
+//! use sion_flow_rt::core::types::{Dim, Shape};
+//! use sion_flow_rt::inliner::builder::GraphBuilder;
+//!
+//! let mut builder = GraphBuilder::new();
+//! let x = builder.input("x", Shape { dims: vec![Dim::Static(4)] });
+//! let y = builder.input("y", Shape { dims: vec![Dim::Static(4)] });
+//! let sum = builder.op(Op::Add, &[x.clone(), y]);
+//! let twice = builder.op(Op::Add, &[sum, x]);
+//! builder.output("out", &twice);
+//!
+//! let (raw, input_specs) = builder.build();
+//! let resolved = sion_flow_rt::resolver::resolve_module(raw, input_specs).unwrap();
+//! let linear = sion_flow_rt::linearizer::linearize(resolved).unwrap();
+//! let opts = sion_flow_rt::codegen::CodegenOptions::default();
+//! let source = sion_flow_rt::codegen::generate_module_source("twice", &linear, &opts);
+//! assert!(source.contains("void twice_func("));
+//! for node in linear.nodes.iter().filter(|n| !matches!(n.op, Op::Input { .. })) {
+//!     assert!(source.contains(&format!("// node: {} ({})", node.id, node.op.name())));
+//! }
+//! ```
+
+use crate::core::op::Op;
+use crate::core::types::{DataType, Port, Shape};
+use crate::inliner::json::JsonPort;
+use crate::inliner::raw_ir::{RawEdge, RawIR, RawNode};
+use petgraph::graph::NodeIndex;
+use std::collections::HashMap;
+
+/// One output port of a node added through a `GraphBuilder`.
+#[derive(Debug, Clone)]
+pub struct NodeRef {
+    node: NodeIndex,
+    port: String,
+}
+
+impl NodeRef {
+    /// The `index`-th output of a multi-output node (e.g. one half of a `Split`).
+    pub fn port(&self, index: usize) -> NodeRef {
+        NodeRef { node: self.node, port: index.to_string() }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct GraphBuilder {
+    raw: RawIR,
+    input_specs: HashMap<String, Port>,
+    next_id: usize,
+}
+
+impl GraphBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Declares an f32 graph input.
+    pub fn input(&mut self, name: &str, shape: Shape) -> NodeRef {
+        self.typed_input(name, shape, DataType::F32)
+    }
+
+    pub fn typed_input(&mut self, name: &str, shape: Shape, dtype: DataType) -> NodeRef {
+        self.raw.inputs.push(JsonPort { name: name.to_string(), dtype: Some(dtype.name().to_string()), shape: None });
+        self.input_specs.insert(name.to_string(), Port { name: name.to_string(), shape, dtype });
+        self.add_node("inputs.NAME".replace("NAME", name), Op::Input { name: name.to_string() }, &[])
+    }
+
+    /// Adds `op` reading `inputs` in the order of `Op::input_ports`, under a generated id
+    /// such as `add0`.
+    pub fn op(&mut self, op: Op, inputs: &[NodeRef]) -> NodeRef {
+        let id = format!("{}{}", op.name().to_ascii_lowercase(), self.next_id);
+        self.next_id += 1;
+        self.add_node(id, op, inputs)
+    }
+
+    /// Adds `op` under an explicit node id.
+    pub fn named_op(&mut self, id: &str, op: Op, inputs: &[NodeRef]) -> NodeRef {
+        self.add_node(id.to_string(), op, inputs)
+    }
+
+    /// Declares a graph output produced by `source`; its dtype is inferred.
+    pub fn output(&mut self, name: &str, source: &NodeRef) {
+        self.raw.outputs.push(JsonPort { name: name.to_string(), dtype: None, shape: None });
+        let op = Op::Output { name: name.to_string(), layout: Default::default() };
+        self.add_node("outputs.NAME".replace("NAME", name), op, std::slice::from_ref(source));
+    }
+
+    /// The assembled graph and the input specs `resolve_module` expects alongside it.
+    pub fn build(self) -> (RawIR, HashMap<String, Port>) {
+        (self.raw, self.input_specs)
+    }
+
+    fn add_node(&mut self, id: String, op: Op, inputs: &[NodeRef]) -> NodeRef {
+        let ports = op.input_ports();
+        let node = self.raw.graph.add_node(RawNode { id, op, origin: None, metadata: None });
+        for (i, input) in inputs.iter().enumerate() {
+            // Surplus operands get placeholder ports, which resolve_module rejects
+            let dst_port = ports.get(i).map(|p| p.to_string()).unwrap_or_else(|| format!("extra{}", i));
+            self.raw.graph.add_edge(input.node, node, RawEdge {
+                src_port: input.port.clone(),
+                dst_port,
+            });
+        }
+        NodeRef { node, port: "output".to_string() }
+    }
+}
@@ -33,14 +33,21 @@ pub struct JsonNode {
     pub id: String,
     pub op: Option<serde_json::Value>, // Мы десериализуем операцию позже, когда поймем её тип
     pub subgraph: Option<String>,
+    /// Free-form annotations carried through to the generated code's node comments.
+    /// Other unknown keys (e.g. `"doc"`) are accepted and ignored.
+    pub metadata: Option<serde_json::Value>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct JsonGraph {
     pub imports: Option<HashMap<String, String>>,
+    #[serde(default)]
     pub inputs: Vec<JsonPort>,
+    #[serde(default)]
     pub outputs: Vec<JsonPort>,
+    #[serde(default)]
     pub nodes: Vec<JsonNode>,
+    #[serde(default)]
     pub links: Vec<(String, String)>,
 }
 
@@ -1,14 +1,18 @@
 pub mod json;
 pub mod raw_ir;
 pub mod paths;
+pub mod builder;
 
 use crate::inliner::json::{JsonGraph};
 use crate::inliner::raw_ir::{RawIR, RawNode, RawEdge};
-use crate::inliner::paths::resolve_subgraph_path;
+use crate::inliner::paths::{resolve_data_path, resolve_subgraph_path};
 use crate::manifest::Manifest;
 use crate::core::op::Op;
+use crate::core::types::{DataType, Layout, SourceLocation};
+use crate::core::utils::json_parse_context;
+use anyhow::Context;
 use std::collections::HashMap;
-use std::path::{Path};
+use std::path::{Path, PathBuf};
 use petgraph::graph::NodeIndex;
 
 #[derive(Default)]
@@ -17,20 +21,64 @@ struct InterfaceMapping {
     outputs: HashMap<String, (NodeIndex, String)>,
 }
 
+#[derive(Debug, Clone)]
+pub struct InlineOptions {
+    /// Roots tried in order when a subgraph is not found next to the importing file.
+    pub search_roots: Vec<PathBuf>,
+    /// Maximum nesting of subgraph imports before inlining is aborted.
+    pub max_depth: usize,
+}
+
+impl Default for InlineOptions {
+    fn default() -> Self {
+        Self {
+            search_roots: vec![PathBuf::from(paths::DEFAULT_LIB_ROOT)],
+            max_depth: 64,
+        }
+    }
+}
+
+/// State shared by every level of the recursive inlining.
+struct InlineContext<'a> {
+    manifest: &'a Manifest,
+    opts: &'a InlineOptions,
+    synthetic_vars: &'a mut HashMap<String, String>,
+    /// Parsed subgraphs keyed by resolved path, so a shared leaf graph is read once.
+    graph_cache: HashMap<PathBuf, JsonGraph>,
+    /// Raw text of the cached subgraphs, for locating node definitions.
+    source_cache: HashMap<PathBuf, String>,
+    /// Subgraph files currently being inlined, outermost first.
+    active_path: Vec<PathBuf>,
+}
+
 pub fn load_and_inline(
     root_graph: JsonGraph,
     base_path: &Path,
     manifest: &Manifest,
+    opts: &InlineOptions,
+    output_layouts: &HashMap<String, Layout>,
     synthetic_vars: &mut HashMap<String, String>,
 ) -> anyhow::Result<RawIR> {
     let mut raw_ir = RawIR::new();
-    let mapping = inline_recursive_graph(root_graph, base_path, "", &mut raw_ir, manifest, synthetic_vars)?;
+    let mut ctx = InlineContext {
+        manifest,
+        opts,
+        synthetic_vars,
+        graph_cache: HashMap::new(),
+        source_cache: HashMap::new(),
+        active_path: Vec::new(),
+    };
+    let mapping = inline_recursive_graph(root_graph, base_path, "", &mut raw_ir, &mut ctx)?;
 
-    // Bridge top-level inputs to the graph
-    for (port_name, consumers) in mapping.inputs {
+    // Bridge top-level inputs to the graph (sorted for a stable node order)
+    let mut inputs: Vec<_> = mapping.inputs.into_iter().collect();
+    inputs.sort_by(|a, b| a.0.cmp(&b.0));
+    for (port_name, consumers) in inputs {
         let input_node = raw_ir.graph.add_node(RawNode {
             id: "inputs.NAME".replace("NAME", &port_name),
             op: Op::Input { name: port_name.clone() },
+            origin: None,
+            metadata: None,
         });
         for (dst_node, dst_port) in consumers {
             raw_ir.graph.add_edge(input_node, dst_node, RawEdge {
@@ -41,10 +89,14 @@ pub fn load_and_inline(
     }
 
     // Bridge top-level outputs to the graph
-    for (port_name, (src_node, src_port)) in mapping.outputs {
+    let mut outputs: Vec<_> = mapping.outputs.into_iter().collect();
+    outputs.sort_by(|a, b| a.0.cmp(&b.0));
+    for (port_name, (src_node, src_port)) in outputs {
         let output_node = raw_ir.graph.add_node(RawNode {
             id: "outputs.NAME".replace("NAME", &port_name),
-            op: Op::Output { name: port_name.clone() },
+            op: Op::Output { name: port_name.clone(), layout: output_layouts.get(&port_name).copied().unwrap_or_default() },
+            origin: None,
+            metadata: None,
         });
         raw_ir.graph.add_edge(src_node, output_node, RawEdge {
             src_port,
@@ -59,13 +111,36 @@ fn inline_recursive(
     path: &Path,
     prefix: &str,
     raw_ir: &mut RawIR,
-    manifest: &Manifest,
-    synthetic_vars: &mut HashMap<String, String>,
+    ctx: &mut InlineContext,
 ) -> anyhow::Result<InterfaceMapping> {
-    let content = std::fs::read_to_string(path)
-        .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", path.display(), e))?;
-    let graph_def = JsonGraph::from_json(&content)?;
-    inline_recursive_graph(graph_def, path, prefix, raw_ir, manifest, synthetic_vars)
+    let key = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    if let Some(start) = ctx.active_path.iter().position(|p| p == &key) {
+        let mut cycle: Vec<_> = ctx.active_path[start..].iter().map(|p| p.display().to_string()).collect();
+        cycle.push(key.display().to_string());
+        return Err(anyhow::anyhow!("Recursive subgraph import detected: {}", cycle.join(" -> ")));
+    }
+    if ctx.active_path.len() >= ctx.opts.max_depth {
+        let mut chain: Vec<_> = ctx.active_path.iter().map(|p| p.display().to_string()).collect();
+        chain.push(key.display().to_string());
+        return Err(anyhow::anyhow!("Subgraph nesting exceeds the maximum inline depth of {}: {}", ctx.opts.max_depth, chain.join(" -> ")));
+    }
+
+    let graph_def = match ctx.graph_cache.get(path) {
+        Some(cached) => cached.clone(),
+        None => {
+            let content = std::fs::read_to_string(path)
+                .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", path.display(), e))?;
+            let parsed = JsonGraph::from_json(&content)
+                .map_err(|e| json_parse_context(e, path, &content))?;
+            ctx.graph_cache.insert(path.to_path_buf(), parsed.clone());
+            ctx.source_cache.insert(path.to_path_buf(), content);
+            parsed
+        }
+    };
+    ctx.active_path.push(key);
+    let mapping = inline_recursive_graph(graph_def, path, prefix, raw_ir, ctx);
+    ctx.active_path.pop();
+    mapping
 }
 
 fn inline_recursive_graph(
@@ -73,8 +148,7 @@ fn inline_recursive_graph(
     path: &Path,
     prefix: &str,
     raw_ir: &mut RawIR,
-    manifest: &Manifest,
-    synthetic_vars: &mut HashMap<String, String>,
+    ctx: &mut InlineContext,
 ) -> anyhow::Result<InterfaceMapping> {
     if prefix.is_empty() {
         raw_ir.inputs = graph_def.inputs.clone();
@@ -83,6 +157,11 @@ fn inline_recursive_graph(
 
     let mut sub_mappings: HashMap<String, InterfaceMapping> = HashMap::new();
     let mut primitive_nodes: HashMap<String, NodeIndex> = HashMap::new();
+    let source = match ctx.source_cache.get(path) {
+        Some(source) => source.clone(),
+        None => std::fs::read_to_string(path).unwrap_or_default(),
+    };
+    let node_lines = node_id_lines(&source);
 
     for node_def in &graph_def.nodes {
         let full_id = if prefix.is_empty() { node_def.id.clone() } else { "PRE/ID".replace("PRE", prefix).replace("ID", &node_def.id) };
@@ -90,7 +169,9 @@ fn inline_recursive_graph(
         if let Some(sub_path_raw) = &node_def.subgraph {
             let mut actual_path_str = sub_path_raw.clone();
             if let Some(imports) = &graph_def.imports {
-                for (key, val) in imports {
+                let mut sorted_imports: Vec<_> = imports.iter().collect();
+                sorted_imports.sort();
+                for (key, val) in sorted_imports {
                     if sub_path_raw.starts_with(key) {
                         actual_path_str = sub_path_raw.replace(key, val);
                         break;
@@ -98,61 +179,135 @@ fn inline_recursive_graph(
                 }
             }
             
-            let sub_full_path = resolve_subgraph_path(path, &actual_path_str);
-            let mapping = inline_recursive(&sub_full_path, &full_id, raw_ir, manifest, synthetic_vars)?;
+            let sub_full_path = resolve_subgraph_path(path, &actual_path_str, &ctx.opts.search_roots);
+            let mapping = inline_recursive(&sub_full_path, &full_id, raw_ir, ctx)?;
             sub_mappings.insert(node_def.id.clone(), mapping);
         } else if let Some(op_val) = &node_def.op {
             let mut normalized_json = op_val.clone();
-            normalize_op_json(&mut normalized_json, manifest, synthetic_vars);
+            normalize_op_json(&mut normalized_json, ctx.manifest, ctx.synthetic_vars);
+            load_constant_file(&mut normalized_json, path, &full_id)?;
             
             let op = Op::from_json_value(&normalized_json)?;
             let node_idx = raw_ir.graph.add_node(RawNode {
                 id: full_id.clone(),
                 op,
+                origin: Some(SourceLocation {
+                    file: path.display().to_string(),
+                    line: node_lines.get(node_def.id.as_str()).copied().unwrap_or(1),
+                }),
+                metadata: node_def.metadata.clone(),
             });
             primitive_nodes.insert(node_def.id.clone(), node_idx);
         }
     }
 
     let mut current_mapping = InterfaceMapping::default();
+    let mut reexports = Vec::new();
 
     for (src_addr, dst_addr) in &graph_def.links {
+        // Links reading one of this graph's own outputs are resolved once all outputs are known
+        if src_addr.starts_with("outputs.") {
+            reexports.push((src_addr, dst_addr));
+            continue;
+        }
         let sources = resolve_source(src_addr, &primitive_nodes, &sub_mappings)?;
-        let destinations = resolve_destination(dst_addr, &primitive_nodes, &sub_mappings)?;
-
-        for (src_node, src_port) in &sources {
-            for (dst_node, dst_port) in &destinations {
-                raw_ir.graph.add_edge(*src_node, *dst_node, RawEdge {
-                    src_port: src_port.clone(),
-                    dst_port: dst_port.clone(),
-                });
+        add_link(src_addr, dst_addr, &sources, &primitive_nodes, &sub_mappings, raw_ir, &mut current_mapping)?;
+    }
+
+    // Re-exports may chain (`outputs.a -> outputs.b -> outputs.c`), so resolve until no progress is made
+    while !reexports.is_empty() {
+        let pending = reexports.len();
+        let mut unresolved = Vec::new();
+        for (src_addr, dst_addr) in reexports {
+            let out_name = &src_addr["outputs.".len()..];
+            match current_mapping.outputs.get(out_name).cloned() {
+                Some(src) => add_link(src_addr, dst_addr, &[src], &primitive_nodes, &sub_mappings, raw_ir, &mut current_mapping)?,
+                None => unresolved.push((src_addr, dst_addr)),
             }
         }
-
-        update_interface_mapping(src_addr, dst_addr, &sources, &destinations, &mut current_mapping);
+        if unresolved.len() == pending {
+            let names: Vec<_> = unresolved.iter().map(|(s, d)| format!("{} -> {}", s, d)).collect();
+            return Err(anyhow::anyhow!("Unresolved output re-exports in {}: {}", path.display(), names.join(", ")));
+        }
+        reexports = unresolved;
     }
 
     Ok(current_mapping)
 }
 
+fn add_link(
+    src_addr: &str,
+    dst_addr: &str,
+    sources: &[(NodeIndex, String)],
+    nodes: &HashMap<String, NodeIndex>,
+    subgraphs: &HashMap<String, InterfaceMapping>,
+    raw_ir: &mut RawIR,
+    mapping: &mut InterfaceMapping,
+) -> anyhow::Result<()> {
+    let destinations = resolve_destination(dst_addr, nodes, subgraphs)?;
+
+    for (src_node, src_port) in sources {
+        for (dst_node, dst_port) in &destinations {
+            raw_ir.graph.add_edge(*src_node, *dst_node, RawEdge {
+                src_port: src_port.clone(),
+                dst_port: dst_port.clone(),
+            });
+        }
+    }
+
+    update_interface_mapping(src_addr, dst_addr, sources, &destinations, mapping)
+}
+
+/// 1-based line of each `"id": "..."` in a graph file. serde_json keeps no positions, so
+/// the text is scanned; the first definition of an id wins.
+fn node_id_lines(source: &str) -> HashMap<&str, usize> {
+    let mut lines = HashMap::new();
+    let mut rest = source;
+    while let Some(pos) = rest.find("\"id\"") {
+        let line = source.len() - rest.len() + pos;
+        rest = &rest[pos + 4..];
+        let Some(value) = rest.trim_start().strip_prefix(':').map(str::trim_start) else {
+            continue;
+        };
+        if let Some(value) = value.strip_prefix('"')
+            && let Some(end) = value.find('"') {
+            let line_no = source[..line].matches('\n').count() + 1;
+            lines.entry(&value[..end]).or_insert(line_no);
+        }
+    }
+    lines
+}
+
 fn normalize_op_json(
     value: &mut serde_json::Value, 
     manifest: &Manifest,
     synthetic_vars: &mut HashMap<String, String>
 ) {
-    if value.is_object() {
-        if let Ok(op) = serde_json::from_value::<crate::inliner::json::JsonDimOp>(value.clone()) {
-            let resolved_dim = crate::analyzer::process_json_dim(
-                &crate::inliner::json::JsonDim::Op(op), 
-                synthetic_vars, 
-                manifest
-            );
-            *value = match resolved_dim {
-                crate::core::types::Dim::Variable(var_name) => serde_json::Value::String(var_name),
-                crate::core::types::Dim::Static(val) => serde_json::Value::Number(val.into()),
-            };
-            return;
+    if value.is_object()
+        && let Ok(op) = serde_json::from_value::<crate::inliner::json::JsonDimOp>(value.clone()) {
+        let resolved_dim = crate::analyzer::process_json_dim(
+            &crate::inliner::json::JsonDim::Op(op), 
+            synthetic_vars, 
+            manifest
+        );
+        *value = match resolved_dim {
+            crate::core::types::Dim::Variable(var_name) => serde_json::Value::String(var_name),
+            crate::core::types::Dim::Static(val) => serde_json::Value::Number(val.into()),
+        };
+        return;
+    }
+    // A bare parameter name folds like a dim expression, so op dims agree with port shapes
+    if let Some(name) = value.as_str()
+        && manifest.parameters.as_ref().is_some_and(|params| params.contains_key(name)) {
+        match crate::analyzer::process_json_dim(
+            &crate::inliner::json::JsonDim::Symbol(name.to_string()),
+            synthetic_vars,
+            manifest
+        ) {
+            crate::core::types::Dim::Static(val) => *value = serde_json::Value::Number(val.into()),
+            crate::core::types::Dim::Variable(alias) => *value = serde_json::Value::String(alias),
         }
+        return;
     }
 
     if let Some(obj) = value.as_object_mut() {
@@ -166,6 +321,55 @@ fn normalize_op_json(
     }
 }
 
+/// Replaces a `{"Constant": {"path": ..., "dtype": ..., "count": ...}}` reference with the
+/// little-endian values read from disk. `file` is the older spelling of `path`; `dtype`
+/// defaults to f32 and `count`, when given, must match the file length.
+fn load_constant_file(value: &mut serde_json::Value, graph_path: &Path, node_id: &str) -> anyhow::Result<()> {
+    let Some(params) = value.get_mut("Constant").and_then(|p| p.as_object_mut()) else {
+        return Ok(());
+    };
+    let Some(file) = params.remove("path").or_else(|| params.remove("file")) else {
+        return Ok(());
+    };
+    let file = file.as_str()
+        .ok_or_else(|| anyhow::anyhow!("Constant 'path' for node '{}' must be a string", node_id))?;
+    let dtype = match params.remove("dtype") {
+        Some(name) => DataType::from_name(name.as_str().unwrap_or_default())
+            .with_context(|| format!("Invalid Constant dtype for node '{}'", node_id))?,
+        None => DataType::F32,
+    };
+    let count = match params.remove("count") {
+        Some(count) => Some(count.as_u64()
+            .ok_or_else(|| anyhow::anyhow!("Constant 'count' for node '{}' must be a non-negative integer", node_id))? as usize),
+        None => None,
+    };
+
+    let data_path = resolve_data_path(graph_path, file);
+    let bytes = std::fs::read(&data_path)
+        .map_err(|e| anyhow::anyhow!("Failed to read constant data for node '{}' from {}: {}", node_id, data_path.display(), e))?;
+    let elem_size = dtype.size_bytes();
+    if bytes.len() % elem_size != 0 {
+        return Err(anyhow::anyhow!("Constant data file {} for node '{}' is not a whole number of {:?} values ({} bytes)", data_path.display(), node_id, dtype, bytes.len()));
+    }
+    if let Some(count) = count
+        && count * elem_size != bytes.len() {
+        return Err(anyhow::anyhow!("Constant data file {} for node '{}' holds {} {:?} values but count is {}", data_path.display(), node_id, bytes.len() / elem_size, dtype, count));
+    }
+
+    let values: Vec<f32> = bytes.chunks_exact(elem_size)
+        .map(|b| match dtype {
+            DataType::F16 => crate::core::utils::half_to_f32(u16::from_le_bytes([b[0], b[1]])),
+            DataType::F32 => f32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+            DataType::F64 => f64::from_le_bytes(b.try_into().unwrap()) as f32,
+            DataType::I32 => i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f32,
+            DataType::I64 => i64::from_le_bytes(b.try_into().unwrap()) as f32,
+            DataType::U32 => u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f32,
+        })
+        .collect();
+    params.insert("values".to_string(), serde_json::json!(values));
+    Ok(())
+}
+
 fn resolve_source(
     addr: &str,
     nodes: &HashMap<String, NodeIndex>,
@@ -179,10 +383,9 @@ fn resolve_source(
     if let Some(&idx) = nodes.get(node_id) {
         return Ok(vec![(idx, port.to_string())]);
     }
-    if let Some(mapping) = subgraphs.get(node_id) {
-        if let Some(src) = mapping.outputs.get(port) {
-            return Ok(vec![src.clone()]);
-        }
+    if let Some(mapping) = subgraphs.get(node_id)
+        && let Some(src) = mapping.outputs.get(port) {
+        return Ok(vec![src.clone()]);
     }
     Err(anyhow::anyhow!("Source not found: {}", addr))
 }
@@ -200,10 +403,9 @@ fn resolve_destination(
     if let Some(&idx) = nodes.get(node_id) {
         return Ok(vec![(idx, port.to_string())]);
     }
-    if let Some(mapping) = subgraphs.get(node_id) {
-        if let Some(consumers) = mapping.inputs.get(port) {
-            return Ok(consumers.clone());
-        }
+    if let Some(mapping) = subgraphs.get(node_id)
+        && let Some(consumers) = mapping.inputs.get(port) {
+        return Ok(consumers.clone());
     }
     Err(anyhow::anyhow!("Destination not found: {}", addr))
 }
@@ -214,13 +416,17 @@ fn update_interface_mapping(
     sources: &[(NodeIndex, String)],
     destinations: &[(NodeIndex, String)],
     mapping: &mut InterfaceMapping,
-) {
+) -> anyhow::Result<()> {
     if let Some(in_name) = src_addr.strip_prefix("inputs.") {
         mapping.inputs.entry(in_name.to_string()).or_default().extend(destinations.iter().cloned());
     }
-    if let Some(out_name) = dst_addr.strip_prefix("outputs.") {
-        if let Some(src) = sources.first() {
-            mapping.outputs.insert(out_name.to_string(), src.clone());
+    if let Some(out_name) = dst_addr.strip_prefix("outputs.")
+        && let Some(src) = sources.first() {
+        if let Some(existing) = mapping.outputs.get(out_name)
+            && existing != src {
+            return Err(anyhow::anyhow!("Output '{}' is driven by more than one source (latest: {})", out_name, src_addr));
         }
+        mapping.outputs.insert(out_name.to_string(), src.clone());
     }
+    Ok(())
 }
@@ -1,6 +1,9 @@
 use std::path::{Path, PathBuf};
 
-pub fn resolve_subgraph_path(current_file: &Path, target: &str) -> PathBuf {
+/// Library root used when no other search roots are configured.
+pub const DEFAULT_LIB_ROOT: &str = "assets/lib";
+
+pub fn resolve_subgraph_path(current_file: &Path, target: &str, search_roots: &[PathBuf]) -> PathBuf {
     // 1. Если путь начинается с assets/, он абсолютный от корня проекта
     if target.starts_with("assets/") {
         let mut p = PathBuf::from(target);
@@ -16,16 +19,36 @@ pub fn resolve_subgraph_path(current_file: &Path, target: &str) -> PathBuf {
         p.set_extension("json");
     }
 
-    // 3. Если относительно файла не нашли, пробуем в библиотеке (assets/lib)
+    // 3. Если относительно файла не нашли, пробуем корни библиотек по порядку
     if !p.exists() {
-        let mut lib_p = PathBuf::from("assets/lib").join(target);
-        if !lib_p.to_string_lossy().ends_with(".json") {
-            lib_p.set_extension("json");
+        for root in search_roots {
+            let mut lib_p = root.join(target);
+            if !lib_p.to_string_lossy().ends_with(".json") {
+                lib_p.set_extension("json");
+            }
+            if lib_p.exists() {
+                return lib_p;
+            }
+        }
+        // 4. Пути, записанные от рабочего каталога, продолжают работать
+        let mut cwd_p = PathBuf::from(target);
+        if !cwd_p.to_string_lossy().ends_with(".json") {
+            cwd_p.set_extension("json");
         }
-        if lib_p.exists() {
-            return lib_p;
+        if cwd_p.exists() {
+            return cwd_p;
         }
     }
 
+    p
+}
+
+/// Data files referenced by a graph (constant weights) are resolved next to the graph,
+/// falling back to the working directory for paths written from the project root.
+pub fn resolve_data_path(current_file: &Path, target: &str) -> PathBuf {
+    let p = current_file.parent().unwrap_or_else(|| Path::new(".")).join(target);
+    if !p.exists() && Path::new(target).exists() {
+        return PathBuf::from(target);
+    }
     p
 }
\ No newline at end of file
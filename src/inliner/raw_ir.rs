@@ -1,10 +1,15 @@
 use crate::core::op::Op;
+use crate::core::types::SourceLocation;
 use petgraph::graph::DiGraph;
 
 #[derive(Debug, Clone)]
 pub struct RawNode {
     pub id: String,
     pub op: Op,
+    /// Defining graph file and line; None for the bridged graph inputs and outputs.
+    pub origin: Option<SourceLocation>,
+    /// The node's `metadata` annotations from the graph file.
+    pub metadata: Option<serde_json::Value>,
 }
 
 #[derive(Debug, Clone)]
@@ -28,4 +33,9 @@ impl RawIR {
             outputs: Vec::new(),
         }
     }
-}
\ No newline at end of file
+}
+impl Default for RawIR {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,686 @@
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+pub mod manifest;
+pub mod analyzer;
+pub mod inliner;
+pub mod resolver;
+pub mod linearizer;
+pub mod codegen;
+pub mod linker;
+pub mod report;
+pub mod core;
+
+#[derive(Debug, Clone)]
+pub struct CompileOptions {
+    pub test: bool,
+    pub run: bool,
+    /// Run each test this many times and time every program call in the runtime (0 disables).
+    pub bench_iterations: usize,
+    pub report: bool,
+    /// Emit runtime assertions for shape constraints on symbolic dims.
+    pub debug_runtime: bool,
+    /// Emit bounds-checked buffer accesses and NaN/Inf scans after every node (C backend only).
+    pub debug_codegen: bool,
+    /// Subgraph search roots tried before the manifest's `lib_paths` and the default `assets/lib`.
+    pub lib_paths: Vec<PathBuf>,
+    pub max_inline_depth: usize,
+    /// Lower static MatMuls to `cblas_sgemm` (also enabled by the manifest's `blas` field).
+    pub blas: bool,
+    /// Library linked when BLAS is used, passed to gcc as `-l<name>`.
+    pub blas_lib: String,
+    /// Static element count below which generated loops are not parallelized.
+    pub parallel_threshold: usize,
+    /// Static element count up to which elementwise loops are fully unrolled (0 disables).
+    pub unroll_threshold: usize,
+    /// Nodes per generated sub-function; overrides the manifest's `split_functions` when non-zero.
+    pub split_functions: usize,
+    /// Emit `#line` directives mapping generated C back to the graph files (C backend only).
+    pub line_directives: bool,
+    /// Code generation target; overrides the manifest's `backend` field when set.
+    pub backend: Option<codegen::Backend>,
+    /// Generate `sion_api.h`/`sion_api.c` so the runtime can be built as a static library.
+    pub emit_lib: bool,
+    /// Programs compiled concurrently within one dependency level.
+    pub jobs: usize,
+    /// Keep compiling the remaining programs after one fails; failures are collected in
+    /// `CompileArtifacts::failures` and no runtime is linked.
+    pub keep_going: bool,
+    /// Runtime flavour; `Sdl2` shows the manifest's display source in a window.
+    pub runtime: linker::RuntimeTarget,
+    /// Directory for the generated sources and the binaries built from them.
+    pub out_dir: PathBuf,
+    /// Directory for the generated C headers; they sit next to the sources in `out_dir` when unset.
+    /// Every gcc/nvcc invocation gets it as an extra `-I`, so `#include "MOD_ID.h"` still resolves.
+    pub include_dir: Option<PathBuf>,
+    /// When shape inference fails, write the table of already resolved shapes here instead of stderr.
+    pub dump_shapes: Option<PathBuf>,
+}
+
+impl Default for CompileOptions {
+    fn default() -> Self {
+        Self {
+            test: false,
+            run: false,
+            bench_iterations: 0,
+            report: false,
+            debug_runtime: false,
+            debug_codegen: false,
+            lib_paths: Vec::new(),
+            max_inline_depth: inliner::InlineOptions::default().max_depth,
+            blas: false,
+            blas_lib: "openblas".to_string(),
+            parallel_threshold: codegen::DEFAULT_PARALLEL_THRESHOLD,
+            unroll_threshold: codegen::DEFAULT_UNROLL_THRESHOLD,
+            split_functions: 0,
+            line_directives: false,
+            backend: None,
+            emit_lib: false,
+            jobs: 1,
+            keep_going: false,
+            runtime: linker::RuntimeTarget::Headless,
+            out_dir: PathBuf::from("generated"),
+            include_dir: None,
+            dump_shapes: None,
+        }
+    }
+}
+
+impl CompileOptions {
+    /// Where the generated headers are written.
+    pub fn header_dir(&self) -> &Path {
+        self.include_dir.as_deref().unwrap_or(&self.out_dir)
+    }
+
+    /// `-I` flags that make both the sources and the headers visible to the C compiler.
+    fn include_flags(&self) -> Vec<String> {
+        let mut flags = vec![format!("-I{}", self.out_dir.display())];
+        if let Some(dir) = &self.include_dir {
+            flags.push(format!("-I{}", dir.display()));
+        }
+        flags
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GeneratedFile {
+    pub path: PathBuf,
+    pub contents: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct CompileArtifacts {
+    pub files: Vec<GeneratedFile>,
+    pub test_runner: Option<PathBuf>,
+    /// Extra libraries the generated code must be linked against.
+    pub link_libs: Vec<String>,
+    /// Module sources compiled separately by nvcc (CUDA backend only).
+    pub device_sources: Vec<PathBuf>,
+    pub backend: codegen::Backend,
+    /// Per-program statistics and stage timings, written by `--report-json`.
+    pub report: report::json::CompileReport,
+    /// Programs that failed under `keep_going`, in execution order.
+    pub failures: Vec<ProgramFailure>,
+    /// Resolved ports of every compiled program, in execution order.
+    pub interfaces: Vec<ProgramPorts>,
+}
+
+/// Inputs and outputs of one program with the shapes and dtypes resolution settled on.
+#[derive(Debug, Clone)]
+pub struct ProgramPorts {
+    pub program: String,
+    pub inputs: Vec<core::types::Port>,
+    pub outputs: Vec<core::types::Port>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ProgramFailure {
+    pub program: String,
+    /// The error with its full context chain.
+    pub message: String,
+}
+
+impl CompileArtifacts {
+    pub fn file(&self, name: &str) -> Option<&GeneratedFile> {
+        self.files.iter().find(|f| f.path.file_name().is_some_and(|n| n == name))
+    }
+
+    pub fn write(&self) -> anyhow::Result<()> {
+        for file in &self.files {
+            if let Some(dir) = file.path.parent() {
+                std::fs::create_dir_all(dir)?;
+            }
+            std::fs::write(&file.path, &file.contents)
+                .with_context(|| format!("Failed to write {}", file.path.display()))?;
+        }
+        Ok(())
+    }
+}
+
+/// Runs the whole pipeline for a manifest and returns the generated C sources without touching the disk.
+///
+/// ```
+/// let opts = sion_flow_rt::CompileOptions {
+///     out_dir: std::path::PathBuf::from("never_written"),
+///     ..Default::default()
+/// };
+/// let manifest = std::path::Path::new("tests/programs/window/manifest.json");
+/// let artifacts = sion_flow_rt::compile_manifest(manifest, &opts).unwrap();
+/// let module = artifacts.file("window.c").unwrap();
+/// assert_eq!(module.path, std::path::Path::new("never_written/window.c"));
+/// assert!(module.contents.contains("void window_func("));
+/// assert!(artifacts.file("runtime.c").is_some());
+/// assert!(artifacts.test_runner.is_none());
+/// assert!(!std::path::Path::new("never_written").exists());
+/// ```
+pub fn compile_manifest(manifest_path: &Path, opts: &CompileOptions) -> anyhow::Result<CompileArtifacts> {
+    let analysis_start = Instant::now();
+    // 1. Load Manifest
+    let manifest_content = std::fs::read_to_string(manifest_path)
+        .with_context(|| format!("Failed to read manifest at {}", manifest_path.display()))?;
+    let manifest = manifest::Manifest::from_json(&manifest_content)
+        .map_err(|e| core::utils::json_parse_context(e, manifest_path, &manifest_content))?;
+    println!("  [1/6] Manifest loaded: {}", manifest_path.display());
+
+    // 2. Project Analysis
+    let manifest_dir = manifest_path.parent().unwrap_or(Path::new("."));
+    let mut plan = analyzer::analyze_project(&manifest, manifest_dir)?;
+    println!("  [2/6] Project analysis complete. {} programs found.", plan.programs.len());
+    let analysis_ms = report::json::millis(analysis_start.elapsed());
+
+    let mut search_roots = opts.lib_paths.clone();
+    search_roots.extend(manifest.lib_paths.iter().map(|p| manifest_dir.join(p)));
+    search_roots.push(PathBuf::from(inliner::paths::DEFAULT_LIB_ROOT));
+    let inline_opts = inliner::InlineOptions {
+        search_roots,
+        max_depth: opts.max_inline_depth,
+    };
+
+    let backend = match opts.backend {
+        Some(backend) => backend,
+        None => codegen::Backend::from_name(manifest.backend.as_deref().unwrap_or("c"))?,
+    };
+    if opts.emit_lib && backend == codegen::Backend::Rust {
+        anyhow::bail!("--emit-lib generates a C API and is not supported by the rust backend");
+    }
+    if opts.debug_codegen && backend != codegen::Backend::C {
+        anyhow::bail!("--debug-codegen instruments the C emitter and is not supported by the {} backend", backend.name());
+    }
+    if opts.line_directives && backend != codegen::Backend::C {
+        anyhow::bail!("--line-directives maps the C emitter's output and is not supported by the {} backend", backend.name());
+    }
+    if opts.runtime == linker::RuntimeTarget::Sdl2 {
+        if backend == codegen::Backend::Rust {
+            anyhow::bail!("--runtime sdl2 generates a C runtime and is not supported by the rust backend");
+        }
+        if plan.display_source.is_none() {
+            anyhow::bail!("--runtime sdl2 needs a source with \"type\": \"Display\" to show");
+        }
+    }
+    let reduce_mode = core::types::ReduceMode::from_name(manifest.reduce_mode.as_deref().unwrap_or("default"))?;
+    let codegen_opts = codegen::CodegenOptions {
+        blas: opts.blas || manifest.blas,
+        parallel_threshold: opts.parallel_threshold,
+        unroll_threshold: opts.unroll_threshold,
+        debug_checks: opts.debug_codegen,
+        split_functions: if opts.split_functions > 0 { opts.split_functions } else { manifest.split_functions },
+        line_directives: opts.line_directives,
+    };
+
+    let mut files = Vec::new();
+    let mut device_sources = Vec::new();
+
+    // 3. Module Compilation (Per Program)
+    // Programs on the same dependency level are independent and compile concurrently,
+    // up to `jobs` at a time. Logs and results are applied in execution order afterwards.
+    let mut compiled = HashMap::new();
+    let mut program_reports = Vec::new();
+    for level in plan.execution_levels.clone() {
+        let ctx = ModuleContext {
+            plan: &plan,
+            manifest: &manifest,
+            manifest_dir,
+            inline_opts: &inline_opts,
+            codegen_opts: &codegen_opts,
+            backend,
+            reduce_mode,
+            report: opts.report,
+            dump_shapes: opts.dump_shapes.as_deref(),
+        };
+        for batch in level.chunks(opts.jobs.max(1)) {
+            let results: Vec<_> = if batch.len() == 1 {
+                vec![compile_program(&batch[0], &ctx)]
+            } else {
+                std::thread::scope(|scope| {
+                    let handles: Vec<_> = batch.iter()
+                        .map(|prog_id| scope.spawn(|| compile_program(prog_id, &ctx)))
+                        .collect();
+                    handles.into_iter().map(|h| h.join().expect("module compilation thread panicked")).collect()
+                })
+            };
+            for (prog_id, (log, result)) in batch.iter().zip(results) {
+                compiled.insert(prog_id.clone(), (log, result));
+            }
+        }
+        // Later levels read these outputs, so they see the dtypes the programs actually compute
+        for prog_id in &level {
+            if let Some((_, Ok(program))) = compiled.get(prog_id) {
+                for port in &program.outputs {
+                    analyzer::propagate_output_dtype(&mut plan, prog_id, &port.name, port.dtype)?;
+                }
+            }
+        }
+    }
+
+    let mut failures = Vec::new();
+    let mut interfaces = Vec::new();
+    for prog_id in &plan.execution_order {
+        let (log, result) = compiled.remove(prog_id).expect("every program belongs to a level");
+        print!("{}", log);
+        let program = match result {
+            Ok(program) => program,
+            Err(err) if opts.keep_going => {
+                failures.push(ProgramFailure { program: prog_id.clone(), message: format!("{:#}", err) });
+                continue;
+            }
+            Err(err) => return Err(err),
+        };
+        plan.synthetic_vars.extend(program.synthetic_vars);
+        plan.runtime_assertions.insert(prog_id.clone(), program.assertions);
+        plan.workspace_info.insert(prog_id.clone(), program.module.workspace_slots);
+        interfaces.push(ProgramPorts { program: prog_id.clone(), inputs: program.inputs, outputs: program.outputs });
+
+        // Files are named after the module id so the runtime's includes and `mod`s find them
+        let source_path = opts.out_dir.join(format!("{}.{}", program.module.id, program.extension));
+        let mut program_files = vec![source_path.display().to_string()];
+        if backend == codegen::Backend::Cuda {
+            device_sources.push(source_path.clone());
+        }
+        files.push(GeneratedFile {
+            path: source_path,
+            contents: program.module.c_source,
+        });
+        if backend != codegen::Backend::Rust {
+            let header_path = opts.header_dir().join(format!("{}.h", program.module.id));
+            program_files.push(header_path.display().to_string());
+            files.push(GeneratedFile {
+                path: header_path,
+                contents: program.module.h_source,
+            });
+        }
+
+        let slots = &plan.workspace_info[prog_id];
+        program_reports.push(report::json::ProgramReport {
+            id: prog_id.clone(),
+            node_count: program.node_count,
+            workspace_slots: slots.len(),
+            workspace_bytes: report::json::workspace_bytes(slots),
+            files: program_files,
+            timings: program.timings,
+        });
+    }
+
+    // The runtime would call the missing modules, so only the successful ones are emitted
+    if !failures.is_empty() {
+        let report = report::json::CompileReport {
+            manifest: manifest_path.display().to_string(),
+            backend: backend.name().to_string(),
+            analysis_ms,
+            link_ms: 0.0,
+            programs: program_reports,
+            failures: failures.clone(),
+        };
+        return Ok(CompileArtifacts { files, test_runner: None, link_libs: Vec::new(), device_sources, backend, report, failures, interfaces });
+    }
+
+    // 4. Linker (Generate top-level runtime)
+    let link_start = Instant::now();
+    let runtime_opts = linker::RuntimeOptions {
+        bench_iterations: opts.bench_iterations,
+        debug_runtime: opts.debug_runtime,
+        backend,
+        target: opts.runtime,
+    };
+    let (runtime_name, runtime_source) = match backend {
+        codegen::Backend::Rust => ("runtime.rs", linker::rust::generate_runtime_rs(&plan, &runtime_opts)),
+        _ => ("runtime.c", linker::generate_runtime_c(&plan, &runtime_opts)),
+    };
+    files.push(GeneratedFile {
+        path: opts.out_dir.join(runtime_name),
+        contents: runtime_source,
+    });
+    println!("  [4/6] Linker generated {}", runtime_name);
+
+    files.push(GeneratedFile {
+        path: opts.out_dir.join("interface.json"),
+        contents: linker::interface::generate_interface_json(&plan),
+    });
+
+    if opts.emit_lib {
+        files.push(GeneratedFile {
+            path: opts.header_dir().join("sion_api.h"),
+            contents: include_str!("../templates/sion_api.h").to_string(),
+        });
+        files.push(GeneratedFile {
+            path: opts.out_dir.join("sion_api.c"),
+            contents: linker::generate_api_c(&plan),
+        });
+        println!("  [4/6] Linker generated sion_api.h and sion_api.c");
+    }
+
+    // 5. Test Runner Generation
+    let mut test_runner = None;
+    if opts.test || opts.run || opts.bench_iterations > 0 {
+        let (runner_name, runner_source) = match backend {
+            codegen::Backend::Rust => ("main.rs", linker::rust::generate_test_runner_rs(&plan, &manifest.tests, &runtime_opts)),
+            _ => ("test_runner.c", linker::generate_test_runner(&plan, &manifest.tests, &runtime_opts)),
+        };
+        let runner_path = opts.out_dir.join(runner_name);
+        files.push(GeneratedFile {
+            path: runner_path.clone(),
+            contents: runner_source,
+        });
+        test_runner = Some(runner_path);
+        println!("  [5/6] Generated {}", runner_name);
+    }
+
+    let mut link_libs = if codegen_opts.blas { vec![opts.blas_lib.clone()] } else { Vec::new() };
+    if backend == codegen::Backend::OpenCl {
+        link_libs.push("OpenCL".to_string());
+    }
+    if opts.runtime == linker::RuntimeTarget::Sdl2 {
+        link_libs.push("SDL2".to_string());
+    }
+    let report = report::json::CompileReport {
+        manifest: manifest_path.display().to_string(),
+        backend: backend.name().to_string(),
+        analysis_ms,
+        link_ms: report::json::millis(link_start.elapsed()),
+        programs: program_reports,
+        failures: Vec::new(),
+    };
+    Ok(CompileArtifacts { files, test_runner, link_libs, device_sources, backend, report, failures, interfaces })
+}
+
+/// Read-only state shared by the per-program compilation threads.
+struct ModuleContext<'a> {
+    plan: &'a analyzer::ProjectPlan,
+    manifest: &'a manifest::Manifest,
+    /// Program paths in the manifest are relative to this directory.
+    manifest_dir: &'a Path,
+    inline_opts: &'a inliner::InlineOptions,
+    codegen_opts: &'a codegen::CodegenOptions,
+    backend: codegen::Backend,
+    reduce_mode: core::types::ReduceMode,
+    report: bool,
+    dump_shapes: Option<&'a Path>,
+}
+
+struct CompiledProgram {
+    module: codegen::CompiledModule,
+    extension: &'static str,
+    assertions: Vec<resolver::ir::RuntimeAssertion>,
+    /// Synthetic vars known after inlining, merged back into the plan.
+    synthetic_vars: HashMap<String, String>,
+    node_count: usize,
+    /// Ports as resolved, in the order `ResolvedIR` lists them.
+    inputs: Vec<core::types::Port>,
+    outputs: Vec<core::types::Port>,
+    timings: report::json::StageTimings,
+}
+
+/// Inlines, resolves, linearizes and generates code for one program. Progress lines go to
+/// the returned log instead of stdout so concurrent compilations do not interleave.
+fn compile_program(prog_id: &str, ctx: &ModuleContext) -> (String, anyhow::Result<CompiledProgram>) {
+    let mut log = format!("  [3/6] Compiling module: {}\n", prog_id);
+    let result = compile_program_logged(prog_id, ctx, &mut log);
+    (log, result)
+}
+
+/// Prints the shapes resolved before a failed inference, or writes them to `path`, and
+/// hands the error back unchanged.
+fn dump_shapes(err: anyhow::Error, path: Option<&Path>) -> anyhow::Error {
+    let Some(dump) = err.downcast_ref::<resolver::ShapeDump>() else {
+        return err;
+    };
+    match path {
+        Some(path) => match std::fs::write(path, dump.table()) {
+            Ok(()) => eprintln!("Shape dump written to {}", path.display()),
+            Err(write_err) => eprintln!("Failed to write shape dump to {}: {}", path.display(), write_err),
+        },
+        None => eprint!("{}", dump.table()),
+    }
+    err
+}
+
+fn compile_program_logged(prog_id: &str, ctx: &ModuleContext, log: &mut String) -> anyhow::Result<CompiledProgram> {
+    let plan = ctx.plan;
+    let prog_def = ctx.manifest.programs.iter().find(|p| p.id == prog_id).unwrap();
+    let prog_interface = plan.programs.get(prog_id).ok_or_else(|| anyhow::anyhow!("Interface for {} not found", prog_id))?;
+    let prog_graph = plan.program_graphs.get(prog_id).cloned().ok_or_else(|| anyhow::anyhow!("Graph for {} not found", prog_id))?;
+    let prog_path = if prog_def.path.ends_with(".json") {
+        prog_def.path.clone()
+    } else {
+        format!("{}.json", prog_def.path)
+    };
+
+    let mut timings = report::json::StageTimings::default();
+    let output_layouts = plan.output_layouts.get(prog_id).cloned().unwrap_or_default();
+    let mut synthetic_vars = plan.synthetic_vars.clone();
+    let stage_start = Instant::now();
+    let raw_ir = inliner::load_and_inline(prog_graph, &ctx.manifest_dir.join(&prog_path), ctx.manifest, ctx.inline_opts, &output_layouts, &mut synthetic_vars)?;
+    timings.inline_ms = report::json::millis(stage_start.elapsed());
+    log.push_str(&format!("    - Inlining complete (nodes: {})\n", raw_ir.graph.node_count()));
+
+    let stage_start = Instant::now();
+    // Only linked or defaulted inputs carry a spec; the runtime passes NULL for the rest
+    let bound_inputs = prog_interface.inputs.iter()
+        .filter(|(name, _)| {
+            let addr = format!("{}.{}", prog_id, name);
+            plan.links.iter().any(|(_, dst)| *dst == addr) || plan.input_defaults.contains_key(&addr)
+        })
+        .map(|(name, port)| (name.clone(), port.clone()))
+        .collect();
+    let mut resolved_ir = resolver::resolve_module(raw_ir, bound_inputs)
+        .map_err(|e| dump_shapes(e, ctx.dump_shapes))
+        .with_context(|| format!("Program '{}' cannot be compiled", prog_id))?;
+    resolver::check_output_shapes(&resolved_ir.outputs, &prog_interface.outputs)
+        .with_context(|| format!("Program '{}' cannot be compiled", prog_id))?;
+    if ctx.reduce_mode == core::types::ReduceMode::Kahan {
+        for node in resolved_ir.graph.node_weights_mut() {
+            if let core::op::Op::ReduceSum { kahan, .. } = &mut node.op {
+                *kahan = true;
+            }
+        }
+    }
+    let folded = resolver::fold::fold_constants(&mut resolved_ir);
+    timings.resolve_ms = report::json::millis(stage_start.elapsed());
+    log.push_str("    - Type & Shape resolution complete\n");
+    for warning in &resolved_ir.warnings {
+        log.push_str(&format!("    - Warning: {}\n", warning));
+    }
+    if folded > 0 {
+        log.push_str(&format!("    - Constant folding complete (folded: {})\n", folded));
+    }
+    let assertions = resolved_ir.assertions.clone();
+    let (inputs, outputs) = (resolved_ir.inputs.clone(), resolved_ir.outputs.clone());
+
+    let stage_start = Instant::now();
+    let linear_ir = linearizer::linearize(resolved_ir)?;
+    timings.linearize_ms = report::json::millis(stage_start.elapsed());
+    log.push_str("    - Linearization complete\n");
+
+    if ctx.report {
+        log.push_str(&report::format_report(prog_id, &report::estimate_costs(&linear_ir)));
+    }
+
+    let stage_start = Instant::now();
+    let module_id = core::utils::sanitize_id(prog_id);
+    codegen::check_c_names(&linear_ir)?;
+    let (module, extension) = match ctx.backend {
+        codegen::Backend::C => (codegen::compile_module(&module_id, &linear_ir, ctx.codegen_opts), "c"),
+        codegen::Backend::Cuda => (codegen::cuda::compile_module(&module_id, &linear_ir)?, "cu"),
+        codegen::Backend::OpenCl => (codegen::opencl::compile_module(&module_id, &linear_ir)?, "c"),
+        codegen::Backend::Rust => (codegen::rust::compile_module(&module_id, &linear_ir)?, "rs"),
+    };
+    timings.codegen_ms = report::json::millis(stage_start.elapsed());
+    log.push_str("    - C code generated\n");
+
+    Ok(CompiledProgram { module, extension, assertions, synthetic_vars, node_count: linear_ir.nodes.len(), inputs, outputs, timings })
+}
+
+/// Compiles `sion_api.c` (which includes the runtime) and any CUDA modules into
+/// `libsionflow.a` in `out_dir`. Libraries in `link_libs` are not bundled and must be linked by the user.
+pub fn build_library(artifacts: &CompileArtifacts, opts: &CompileOptions) -> anyhow::Result<PathBuf> {
+    let api_source = artifacts.file("sion_api.c")
+        .ok_or_else(|| anyhow::anyhow!("No C API was generated (use --emit-lib)"))?;
+
+    std::fs::create_dir_all(&opts.out_dir)?;
+    let api_object = opts.out_dir.join("sion_api.o");
+    let status = std::process::Command::new("gcc")
+        .arg("-c")
+        .arg(&api_source.path)
+        .args(opts.include_flags())
+        .arg("-O2")
+        .arg("-o")
+        .arg(&api_object)
+        .status()
+        .context("Failed to execute gcc. Is it installed?")?;
+    if !status.success() {
+        anyhow::bail!("C compilation of {} failed", api_source.path.display());
+    }
+
+    let mut objects = vec![api_object];
+    for source in &artifacts.device_sources {
+        let object = opts.out_dir.join(source.with_extension("o").file_name().unwrap_or_default());
+        let status = std::process::Command::new("nvcc")
+            .arg("-c")
+            .arg(source)
+            .args(opts.include_flags())
+            .arg("-o")
+            .arg(&object)
+            .status()
+            .context("Failed to execute nvcc. Is the CUDA toolkit installed?")?;
+        if !status.success() {
+            anyhow::bail!("CUDA compilation of {} failed", source.display());
+        }
+        objects.push(object);
+    }
+
+    let library = opts.out_dir.join("libsionflow.a");
+    let _ = std::fs::remove_file(&library);
+    let status = std::process::Command::new("ar")
+        .arg("rcs")
+        .arg(&library)
+        .args(&objects)
+        .status()
+        .context("Failed to execute ar. Is it installed?")?;
+    if !status.success() {
+        anyhow::bail!("Archiving {} failed", library.display());
+    }
+    Ok(library)
+}
+
+/// Compiles the generated test runner with gcc and executes it. CUDA modules are compiled
+/// with nvcc, which then links them with the gcc-built runner; the Rust backend's runner
+/// is a single crate built with rustc.
+pub fn build_and_run(artifacts: &CompileArtifacts, opts: &CompileOptions) -> anyhow::Result<()> {
+    let runner_path = artifacts.test_runner.as_ref()
+        .ok_or_else(|| anyhow::anyhow!("No test runner was generated"))?;
+
+    println!("  [6/6] Compiling and running...");
+    std::fs::create_dir_all(&opts.out_dir)?;
+    
+    // Binaries sit next to the generated sources, so nothing is written outside `out_dir`
+    let output_name = opts.out_dir.join(if cfg!(windows) { "test_runner.exe" } else { "test_runner" });
+    let libs: Vec<String> = artifacts.link_libs.iter().map(|lib| format!("-l{}", lib)).collect();
+    
+    if artifacts.backend == codegen::Backend::Rust {
+        let status = std::process::Command::new("rustc")
+            .arg(runner_path)
+            .arg("--edition=2021")
+            .arg("-O")
+            .arg("-o")
+            .arg(&output_name)
+            .status()
+            .context("Failed to execute rustc. Is it installed?")?;
+
+        if !status.success() {
+            anyhow::bail!("Rust compilation failed");
+        }
+    } else if artifacts.device_sources.is_empty() {
+        let status = std::process::Command::new("gcc")
+            .arg(runner_path)
+            .args(opts.include_flags())
+            .arg("-o")
+            .arg(&output_name)
+            .args(&libs)
+            .arg("-lm")
+            .status()
+            .context("Failed to execute gcc. Is it installed?")?;
+
+        if !status.success() {
+            anyhow::bail!("C compilation failed");
+        }
+    } else {
+        let mut objects = Vec::new();
+        for source in &artifacts.device_sources {
+            let object = opts.out_dir.join(source.with_extension("o").file_name().unwrap_or_default());
+            let status = std::process::Command::new("nvcc")
+                .arg("-c")
+                .arg(source)
+                .args(opts.include_flags())
+                .arg("-o")
+                .arg(&object)
+                .status()
+                .context("Failed to execute nvcc. Is the CUDA toolkit installed?")?;
+            if !status.success() {
+                anyhow::bail!("CUDA compilation of {} failed", source.display());
+            }
+            objects.push(object);
+        }
+
+        let runner_object = opts.out_dir.join("test_runner.o");
+        let status = std::process::Command::new("gcc")
+            .arg("-c")
+            .arg(runner_path)
+            .args(opts.include_flags())
+            .arg("-o")
+            .arg(&runner_object)
+            .status()
+            .context("Failed to execute gcc. Is it installed?")?;
+        if !status.success() {
+            anyhow::bail!("C compilation failed");
+        }
+
+        let status = std::process::Command::new("nvcc")
+            .arg(&runner_object)
+            .args(&objects)
+            .arg("-o")
+            .arg(&output_name)
+            .args(&libs)
+            .arg("-lm")
+            .status()
+            .context("Failed to execute nvcc. Is the CUDA toolkit installed?")?;
+        if !status.success() {
+            anyhow::bail!("Linking with nvcc failed");
+        }
+    }
+
+    // A bare relative name would be looked up on PATH instead
+    let mut run_cmd = std::process::Command::new(Path::new(".").join(&output_name));
+
+    let run_status = run_cmd
+        .stdout(std::process::Stdio::inherit())
+        .stderr(std::process::Stdio::inherit())
+        .status()
+        .context("Failed to run the compiled test runner")?;
+    
+    if opts.test && !run_status.success() {
+        anyhow::bail!("Tests failed");
+    }
+    Ok(())
+}
@@ -1,5 +1,11 @@
-use crate::core::types::{Shape, DataType, Port, WorkspaceSlot};
+use crate::core::types::{Shape, DataType, Port, SourceLocation, WorkspaceSlot};
 use crate::core::op::Op;
+use crate::resolver::ir::OutputPort;
+
+/// Byte alignment of every workspace slot allocation.
+pub const WORKSPACE_ALIGNMENT: usize = 64;
+/// Slot sizes are rounded up to a multiple of this many elements.
+pub const WORKSPACE_PAD_ELEMS: usize = 16;
 
 // ... (InputConnection and LinearNode structs)
 
@@ -7,7 +13,29 @@ use crate::core::op::Op;
 pub struct InputConnection {
     pub node_id: String,
     pub src_port: String,
+    /// Index of the source's output port this input reads; `shape` is that port's shape.
+    pub port: usize,
+    /// Element offset of the port within the source's slot, for every port after the first.
+    pub port_offset: Option<String>,
     pub shape: Shape,
+    pub dtype: DataType,
+    /// Set when this input reads an eliminated Transpose: `node_id` is the Transpose's
+    /// input and `shape` the transposed shape, indexed through permuted strides.
+    pub view: Option<TransposeView>,
+}
+
+impl InputConnection {
+    /// Dtype of the buffer this input points into, matching its source's `LinearNode::compute_dtype`.
+    pub fn compute_dtype(&self) -> DataType {
+        if self.dtype.is_emulated() { DataType::F32 } else { self.dtype }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TransposeView {
+    /// Axis `k` of the view is axis `permutation[k]` of the source.
+    pub permutation: Vec<usize>,
+    pub source_shape: Shape,
 }
 
 #[derive(Debug, Clone)]
@@ -15,9 +43,55 @@ pub struct LinearNode {
     pub id: String,
     pub op: Op,
     pub inputs: Vec<InputConnection>,
+    /// Shape of the first output port.
     pub shape: Shape,
+    /// Output ports, stored back to back in the node's slot.
+    pub outputs: Vec<OutputPort>,
     pub dtype: DataType,
     pub offset: usize, // Offset in elements within the workspace buffer
+    pub output_alias: Option<String>, // Output port written directly instead of a workspace slot
+    pub origin: Option<SourceLocation>,
+    pub metadata: Option<serde_json::Value>,
+}
+
+impl LinearNode {
+    /// Inputs of emulated dtypes are staged into a float buffer before compute.
+    /// Constants live in static arrays emitted at file scope, and Flatten is a view of its input.
+    pub fn has_workspace_slot(&self) -> bool {
+        match self.op {
+            Op::Input { .. } => self.dtype.is_emulated(),
+            Op::Output { .. } | Op::Constant { .. } | Op::Flatten { .. } => false,
+            _ => self.output_alias.is_none(),
+        }
+    }
+
+    pub fn compute_dtype(&self) -> DataType {
+        if self.dtype.is_emulated() { DataType::F32 } else { self.dtype }
+    }
+
+    /// Elements of all output ports together.
+    pub fn slot_size_expr(&self) -> String {
+        ports_size_expr(&self.outputs)
+    }
+
+    /// Element count of the node's slot, padded to `WORKSPACE_PAD_ELEMS`.
+    pub fn padded_slot_size_expr(&self) -> String {
+        let size = self.slot_size_expr();
+        match size.parse::<usize>() {
+            Ok(size) => (size.div_ceil(WORKSPACE_PAD_ELEMS) * WORKSPACE_PAD_ELEMS).to_string(),
+            Err(_) => "(((SIZE) + PAD - 1) / PAD * PAD)"
+                .replace("SIZE", &size)
+                .replace("PAD", &WORKSPACE_PAD_ELEMS.to_string()),
+        }
+    }
+}
+
+/// Summed element count of `ports`, folded to a number when every shape is static.
+pub(crate) fn ports_size_expr(ports: &[OutputPort]) -> String {
+    if let Some(total) = ports.iter().map(|p| p.shape.static_size()).sum::<Option<usize>>() {
+        return total.to_string();
+    }
+    ports.iter().map(|p| format!("({})", p.shape.to_c_size_expr())).collect::<Vec<_>>().join(" + ")
 }
 
 #[derive(Debug, Clone)]
@@ -30,8 +104,8 @@ pub struct LinearIR {
 impl LinearIR {
     pub fn get_workspace_slots(&self) -> Vec<WorkspaceSlot> {
         self.nodes.iter()
-            .filter(|n| !matches!(n.op, Op::Input { .. } | Op::Output { .. }))
-            .map(|n| WorkspaceSlot { shape: n.shape.clone(), dtype: n.dtype })
+            .filter(|n| n.has_workspace_slot())
+            .map(|n| WorkspaceSlot { shape: n.shape.clone(), dtype: n.compute_dtype(), size_expr: n.padded_slot_size_expr() })
             .collect()
     }
 }
\ No newline at end of file
@@ -1,7 +1,11 @@
 pub mod ir;
 
-use crate::resolver::ir::ResolvedIR;
-use crate::linearizer::ir::{LinearIR, LinearNode, InputConnection};
+use crate::resolver::ir::{ResolvedEdge, ResolvedIR, ResolvedNode};
+use crate::core::op::Op;
+use crate::core::types::Layout;
+use petgraph::graph::{EdgeReference, NodeIndex};
+use crate::linearizer::ir::{ports_size_expr, LinearIR, LinearNode, InputConnection, TransposeView};
+use crate::core::utils::find_cycle;
 use petgraph::algo::toposort;
 use petgraph::visit::EdgeRef;
 
@@ -10,10 +14,13 @@ pub fn linearize(resolved: ResolvedIR) -> anyhow::Result<LinearIR> {
     let mut current_offset = 0;
     
     let order = toposort(&resolved.graph, None)
-        .map_err(|_| anyhow::anyhow!("Cycle detected during linearization"))?;
+        .map_err(|_| anyhow::anyhow!("Cycle detected during linearization between nodes: {}", find_cycle(&resolved.graph, |n| n.id.clone()).join(", ")))?;
 
     for idx in order {
         let node = &resolved.graph[idx];
+        if transpose_view_source(&resolved, idx).is_some() {
+            continue;
+        }
         
         let mut inputs = Vec::new();
         let mut incoming: Vec<_> = resolved.graph.edges_directed(idx, petgraph::Direction::Incoming).collect();
@@ -21,44 +28,174 @@ pub fn linearize(resolved: ResolvedIR) -> anyhow::Result<LinearIR> {
         
         for edge in incoming {
             let src_node = &resolved.graph[edge.source()];
+            // Consumers of an eliminated Transpose read its input through the permutation
+            if let Some(view_edge) = transpose_view_source(&resolved, edge.source())
+                && let Op::Transpose { permutation } = &src_node.op {
+                let view_src = &resolved.graph[view_edge.source()];
+                let port = view_src.output_index(&view_edge.weight().src_port).unwrap_or(0);
+                inputs.push(InputConnection {
+                    node_id: view_src.id.clone(),
+                    src_port: view_edge.weight().src_port.clone(),
+                    port,
+                    port_offset: port_offset(view_src, port),
+                    shape: src_node.shape.clone(),
+                    dtype: view_src.dtype,
+                    view: Some(TransposeView {
+                        permutation: permutation.clone(),
+                        source_shape: view_src.outputs[port].shape.clone(),
+                    }),
+                });
+                continue;
+            }
+            let port = src_node.output_index(&edge.weight().src_port).unwrap_or(0);
             inputs.push(InputConnection {
                 node_id: src_node.id.clone(),
                 src_port: edge.weight().src_port.clone(),
-                shape: src_node.shape.clone(),
+                port,
+                port_offset: port_offset(src_node, port),
+                shape: src_node.outputs[port].shape.clone(),
+                dtype: src_node.dtype,
+                view: None,
             });
         }
 
-        // Calculate offset for intermediate nodes (those that aren't pure inputs)
-        let offset = if matches!(node.op, crate::core::op::Op::Input { .. }) {
-            0
-        } else {
-            let start = current_offset;
-            if !matches!(node.op, crate::core::op::Op::Output { .. }) {
-                match &node.op {
-                    crate::core::op::Op::Split { parts, .. } => {
-                        current_offset += parts;
-                    }
-                    _ => {
-                        current_offset += 1;
-                    }
-                }
-            }
-            start
-        };
-
-        nodes.push(LinearNode {
+        let mut linear_node = LinearNode {
             id: node.id.clone(),
             op: node.op.clone(),
             inputs,
             shape: node.shape.clone(),
+            outputs: node.outputs.clone(),
             dtype: node.dtype,
-            offset,
-        });
+            offset: 0,
+            output_alias: direct_output(&resolved, idx),
+            origin: node.origin.clone(),
+            metadata: node.metadata.clone(),
+        };
+
+        // Calculate offset for nodes that own a workspace buffer
+        if linear_node.has_workspace_slot() {
+            linear_node.offset = current_offset;
+            current_offset += 1;
+        }
+
+        nodes.push(linear_node);
     }
 
-    Ok(LinearIR {
+    let ir = LinearIR {
         nodes,
         inputs: resolved.inputs,
         outputs: resolved.outputs,
-    })
+    };
+    check_workspace_offsets(&ir)?;
+    Ok(ir)
+}
+
+/// Fails when two nodes holding the same workspace slot are live at the same time. A slot
+/// is live from the node that writes it through its last reader in execution order.
+///
+/// ```
+/// use sion_flow_rt::core::op::Op;
+/// use sion_flow_rt::core::types::{Dim, Shape};
+/// use sion_flow_rt::inliner::builder::GraphBuilder;
+///
+/// let mut builder = GraphBuilder::new();
+/// let x = builder.input("x", Shape { dims: vec![Dim::Static(4)] });
+/// let a = builder.named_op("a", Op::Sin, &[x.clone()]);
+/// let b = builder.named_op("b", Op::Abs, &[x]);
+/// let sum = builder.op(Op::Add, &[a, b]);
+/// builder.output("out", &sum);
+///
+/// let (raw, input_specs) = builder.build();
+/// let resolved = sion_flow_rt::resolver::resolve_module(raw, input_specs).unwrap();
+/// let mut linear = sion_flow_rt::linearizer::linearize(resolved).unwrap();
+/// sion_flow_rt::linearizer::check_workspace_offsets(&linear).unwrap();
+///
+/// // `a` is still needed by the Add when `b` is written, so they cannot share a slot
+/// let a_offset = linear.nodes.iter().find(|n| n.id == "a").unwrap().offset;
+/// linear.nodes.iter_mut().find(|n| n.id == "b").unwrap().offset = a_offset;
+/// let err = sion_flow_rt::linearizer::check_workspace_offsets(&linear).unwrap_err().to_string();
+/// assert!(err.contains("'a'") && err.contains("'b'"), "{}", err);
+/// ```
+pub fn check_workspace_offsets(ir: &LinearIR) -> anyhow::Result<()> {
+    let live_ranges: Vec<(&LinearNode, usize, usize)> = ir.nodes.iter().enumerate()
+        .filter(|(_, node)| node.has_workspace_slot())
+        .map(|(pos, node)| {
+            let last_read = ir.nodes.iter().enumerate()
+                .filter(|(_, reader)| reader.inputs.iter().any(|input| input.node_id == node.id))
+                .map(|(reader_pos, _)| reader_pos)
+                .max()
+                .unwrap_or(pos);
+            (node, pos, last_read)
+        })
+        .collect();
+
+    for (i, (first, first_start, first_end)) in live_ranges.iter().enumerate() {
+        for (second, second_start, second_end) in &live_ranges[i + 1..] {
+            if first.offset == second.offset && first_start <= second_end && second_start <= first_end {
+                return Err(anyhow::anyhow!(
+                    "Nodes '{}' and '{}' are live at the same time but share workspace slot {}",
+                    first.id, second.id, first.offset));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// A Transpose is left out of the IR when every consumer can index through permuted strides:
+/// elementwise ops, and MatMul operands whose last two axes are swapped. Transposes feeding
+/// anything else (another Transpose, Split, reductions) are materialized. Returns the
+/// Transpose's input edge when it is eliminated.
+fn transpose_view_source(resolved: &ResolvedIR, idx: NodeIndex) -> Option<EdgeReference<'_, ResolvedEdge>> {
+    let Op::Transpose { permutation } = &resolved.graph[idx].op else {
+        return None;
+    };
+    let mut incoming = resolved.graph.edges_directed(idx, petgraph::Direction::Incoming);
+    let input = incoming.next()?;
+    // Later output ports are offsets into their source's slot, which views do not track
+    let source = &resolved.graph[input.source()];
+    if incoming.next().is_some() || source.output_index(&input.weight().src_port).is_some_and(|port| port > 0) {
+        return None;
+    }
+
+    let rank = permutation.len();
+    let swaps_last_two = rank >= 2
+        && permutation[..rank - 2].iter().enumerate().all(|(k, &axis)| k == axis)
+        && permutation[rank - 2..] == [rank - 1, rank - 2];
+    let mut consumers = resolved.graph.edges_directed(idx, petgraph::Direction::Outgoing).peekable();
+    consumers.peek()?;
+    let all_indexable = consumers.all(|edge| {
+        let consumer = &resolved.graph[edge.target()];
+        match consumer.op {
+            Op::Add | Op::Sub | Op::Mul | Op::Div | Op::Min | Op::Max | Op::Pow | Op::Lerp
+            | Op::Mod | Op::And | Op::Or | Op::Xor | Op::Shl | Op::Shr
+            | Op::Sin | Op::Abs | Op::Sqrt | Op::Square | Op::Exp | Op::Log => true,
+            // Matrix-vector products keep their row pointer walk
+            Op::MatMul => swaps_last_two && consumer.shape.dims.len() >= 2,
+            _ => false,
+        }
+    });
+    all_indexable.then_some(input)
+}
+
+/// Where output `port` of `node` starts in its slot; None for the first port.
+fn port_offset(node: &ResolvedNode, port: usize) -> Option<String> {
+    (port > 0).then(|| ports_size_expr(&node.outputs[..port]))
+}
+
+/// Returns the output port a node can write straight into: its only consumer is an
+/// Output of the same dtype, so the copy loop and the workspace slot are unnecessary.
+fn direct_output(resolved: &ResolvedIR, idx: NodeIndex) -> Option<String> {
+    let node = &resolved.graph[idx];
+    if matches!(node.op, Op::Input { .. } | Op::Output { .. } | Op::Constant { .. } | Op::Split { .. }) {
+        return None;
+    }
+    let mut consumers = resolved.graph.edges_directed(idx, petgraph::Direction::Outgoing);
+    let edge = consumers.next()?;
+    if consumers.next().is_some() {
+        return None;
+    }
+    match &resolved.graph[edge.target()] {
+        out @ ResolvedNode { op: Op::Output { name, layout: Layout::RowMajor }, .. } if out.dtype == node.dtype && out.shape == node.shape => Some(name.clone()),
+        _ => None,
+    }
 }
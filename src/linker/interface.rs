@@ -0,0 +1,116 @@
+//! `interface.json`: a machine-readable description of the generated code, so tools can
+//! drive the runtime without parsing headers. Bump `INTERFACE_VERSION` on breaking changes.
+
+use super::{program_inputs, runtime_vars};
+use crate::analyzer::ProjectPlan;
+use crate::core::types::{Dim, Layout, Port};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+pub const INTERFACE_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct InterfaceFile {
+    pub version: u32,
+    pub execution_order: Vec<String>,
+    /// Runtime dim variables (globals in runtime.c), sorted.
+    pub dim_vars: Vec<String>,
+    /// Variables derived from other dims, as C expressions.
+    pub synthetic_vars: BTreeMap<String, String>,
+    pub resources: BTreeMap<String, ResourceInfo>,
+    pub programs: BTreeMap<String, ProgramInfo>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ResourceInfo {
+    pub dtype: String,
+    /// Static dims are numbers, symbolic dims are variable names or C expressions.
+    pub shape: Vec<Dim>,
+    pub size_expr: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ProgramInfo {
+    /// In the order of the module function's parameters.
+    pub inputs: Vec<PortInfo>,
+    pub outputs: Vec<PortInfo>,
+    pub workspace_slots: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PortInfo {
+    pub name: String,
+    pub dtype: String,
+    pub c_type: String,
+    pub shape: Vec<Dim>,
+    pub size_expr: String,
+    pub layout: Layout,
+}
+
+impl PortInfo {
+    fn new(port: &Port, layout: Layout) -> Self {
+        Self {
+            name: port.name.clone(),
+            dtype: port.dtype.name().to_string(),
+            c_type: port.dtype.to_c_type().to_string(),
+            shape: port.shape.dims.clone(),
+            size_expr: port.shape.to_c_size_expr(),
+            layout,
+        }
+    }
+}
+
+/// Describes the plan after module compilation, once workspace slots are known.
+pub fn build_interface(plan: &ProjectPlan) -> InterfaceFile {
+    let resources = plan.resources.iter().map(|(id, res)| (id.clone(), ResourceInfo {
+        dtype: res.dtype.name().to_string(),
+        shape: res.shape.dims.clone(),
+        size_expr: res.shape.to_c_size_expr(),
+    })).collect();
+
+    let programs = plan.execution_order.iter().map(|prog_id| {
+        let interface = &plan.programs[prog_id];
+        let layouts = plan.output_layouts.get(prog_id);
+        let inputs = program_inputs(plan, prog_id).into_iter()
+            .map(|(name, _)| PortInfo::new(&interface.inputs[&name], Layout::RowMajor))
+            .collect();
+        let mut outputs: Vec<_> = interface.outputs.iter()
+            .map(|(name, port)| PortInfo::new(port, layouts.and_then(|l| l.get(name)).copied().unwrap_or_default()))
+            .collect();
+        outputs.sort_by(|a, b| a.name.cmp(&b.name));
+        (prog_id.clone(), ProgramInfo {
+            inputs,
+            outputs,
+            workspace_slots: plan.workspace_info.get(prog_id).map_or(0, |slots| slots.len()),
+        })
+    }).collect();
+
+    InterfaceFile {
+        version: INTERFACE_VERSION,
+        execution_order: plan.execution_order.clone(),
+        dim_vars: runtime_vars(plan),
+        synthetic_vars: plan.synthetic_vars.iter().map(|(k, v)| (k.clone(), v.clone())).collect(),
+        resources,
+        programs,
+    }
+}
+
+/// Pretty-printed `interface.json`, which parses back into the same `InterfaceFile`.
+///
+/// ```
+/// use sion_flow_rt::linker::interface::InterfaceFile;
+///
+/// for fixture in ["synthetic", "column_major_output", "argmax_i32"] {
+///     let manifest = format!("tests/programs/{}/manifest.json", fixture);
+///     let opts = sion_flow_rt::CompileOptions::default();
+///     let artifacts = sion_flow_rt::compile_manifest(std::path::Path::new(&manifest), &opts).unwrap();
+///     let json = &artifacts.file("interface.json").unwrap().contents;
+///     let parsed: InterfaceFile = serde_json::from_str(json).unwrap();
+///     assert_eq!(parsed.version, sion_flow_rt::linker::interface::INTERFACE_VERSION);
+///     assert!(!parsed.programs.is_empty());
+///     assert_eq!(&serde_json::to_string_pretty(&parsed).unwrap(), json);
+/// }
+/// ```
+pub fn generate_interface_json(plan: &ProjectPlan) -> String {
+    serde_json::to_string_pretty(&build_interface(plan)).expect("Failed to serialize interface.json")
+}
@@ -1,11 +1,47 @@
 use crate::analyzer::ProjectPlan;
 use crate::manifest::Test;
-use crate::core::types::Dim;
+use crate::core::types::{Dim, DataType};
+use crate::codegen::{Backend, HALF_SUPPORT};
+use crate::codegen::opencl::OPENCL_SUPPORT;
+use crate::linearizer::ir::WORKSPACE_ALIGNMENT;
 use crate::core::utils::sanitize_id;
-use std::collections::{HashSet};
+use std::collections::{HashMap, HashSet};
 use tera::{Tera, Context};
 
-pub fn generate_test_runner(plan: &ProjectPlan, tests: &[Test]) -> String {
+pub mod interface;
+pub mod rust;
+
+#[derive(Debug, Clone, Default)]
+pub struct RuntimeOptions {
+    /// Number of timed iterations the test runner performs after the tests (0 disables benchmarking).
+    pub bench_iterations: usize,
+    /// Check runtime shape assertions after dims are (re)computed.
+    pub debug_runtime: bool,
+    /// Target of the module code; decides how modules are included and buffers allocated.
+    pub backend: Backend,
+    pub target: RuntimeTarget,
+}
+
+/// What the generated runtime drives: nothing beyond the programs, or an SDL2 window that
+/// shows the display framebuffer after every step.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RuntimeTarget {
+    #[default]
+    Headless,
+    Sdl2,
+}
+
+impl RuntimeTarget {
+    pub fn from_name(name: &str) -> anyhow::Result<RuntimeTarget> {
+        match name {
+            "headless" => Ok(RuntimeTarget::Headless),
+            "sdl2" => Ok(RuntimeTarget::Sdl2),
+            other => Err(anyhow::anyhow!("Unknown runtime '{}' (expected \"headless\" or \"sdl2\")", other)),
+        }
+    }
+}
+
+pub fn generate_test_runner(plan: &ProjectPlan, tests: &[Test], opts: &RuntimeOptions) -> String {
     let mut tera = Tera::default();
     tera.add_raw_template("test_runner", include_str!("../../templates/test_runner.c.tera")).unwrap();
 
@@ -15,24 +51,15 @@ pub fn generate_test_runner(plan: &ProjectPlan, tests: &[Test]) -> String {
     for test in tests {
         let mut inputs = Vec::new();
         for (name, data) in &test.inputs {
-            // Find which resource is linked to this program input
-            let target_addr = "PROG.PORT".replace("PROG", &test.program).replace("PORT", name);
-            let mut resource_id = name.clone(); // Fallback
-            for (src_addr, dst_addr) in &plan.links {
-                if dst_addr == &target_addr {
-                    if let Some(res_id) = src_addr.strip_prefix("sources.") {
-                        resource_id = res_id.to_string();
-                    }
-                    break;
-                }
-            }
-
+            let resource_id = test_input_resource(plan, &test.program, name);
+            let dtype = plan.resources.get(&resource_id).map(|r| r.dtype).unwrap_or(DataType::F32);
             let mut formatted_data = Vec::new();
             for val in data {
-                formatted_data.push(if val.fract() == 0.0 { format!("{}.0f", val) } else { format!("{}f", val) });
+                formatted_data.push(c_literal(*val, dtype));
             }
             inputs.push(serde_json::json!({
                 "id": sanitize_id(&resource_id),
+                "store_fn": if dtype == DataType::F16 { "sion_float_to_half" } else { "" },
                 "data": formatted_data
             }));
         }
@@ -41,22 +68,28 @@ pub fn generate_test_runner(plan: &ProjectPlan, tests: &[Test]) -> String {
 
         let mut outputs = Vec::new();
         for (name, expected) in &test.expected {
-            let sanitized = sanitize_id(name);
-            let buf_name = "buf_PROG_PORT"
-                .replace("PROG", &sanitize_id(&test.program))
-                .replace("PORT", &sanitized);
+            let (buf_name, dtype) = expected_buffer(plan, &test.program, name);
             
             let mut expected_items = Vec::new();
             for (idx, val) in expected.iter().enumerate() {
                 expected_items.push(serde_json::json!({
                     "idx": idx,
-                    "val": if val.fract() == 0.0 { format!("{}.0f", val) } else { format!("{}f", val) }
+                    "val": c_literal(*val, dtype)
                 }));
             }
 
             outputs.push(serde_json::json!({
                 "full_name": name,
                 "buf_name": buf_name,
+                "load_fn": if dtype == DataType::F16 { "sion_half_to_float" } else { "" },
+                "print_fmt": if dtype.is_integer() { "%lld" } else { "%f" },
+                "print_cast": if dtype.is_integer() { "(long long)" } else { "(double)" },
+                // Half precision keeps ~3 significant decimal digits
+                "tolerance": match dtype {
+                    DataType::F16 => "1e-3 * fmax(1.0, fabs(EXPECTED))",
+                    DataType::F64 => "1e-12 * fmax(1.0, fabs(EXPECTED))",
+                    _ => "1e-5",
+                },
                 "expected_items": expected_items
             }));
         }
@@ -69,16 +102,73 @@ pub fn generate_test_runner(plan: &ProjectPlan, tests: &[Test]) -> String {
     }
 
     context.insert("tests", &rendered_tests);
+    context.insert("bench_iterations", &opts.bench_iterations);
+    context.insert("display_loop", &(opts.target == RuntimeTarget::Sdl2));
     tera.render("test_runner", &context).expect("Failed to render test_runner template")
 }
 
-pub fn generate_runtime_c(plan: &ProjectPlan) -> String {
-    let mut tera = Tera::default();
-    tera.add_raw_template("runtime", include_str!("../../templates/runtime.c.tera")).unwrap();
+/// Test value as a C literal; only F64 buffers get a double literal.
+fn c_literal(val: f64, dtype: DataType) -> String {
+    // Integers past 2^24 would round if written as float literals
+    if dtype.is_integer() {
+        return format!("{}", val as i64);
+    }
+    let digits = if val.fract() == 0.0 { format!("{}.0", val) } else { format!("{}", val) };
+    if dtype == DataType::F64 { digits } else { digits + "f" }
+}
 
-    let mut context = Context::new();
+/// Finds the resource linked to a program input a test fills, falling back to the input name.
+fn test_input_resource(plan: &ProjectPlan, program: &str, name: &str) -> String {
+    let target_addr = "PROG.PORT".replace("PROG", program).replace("PORT", name);
+    for (src_addr, dst_addr) in &plan.links {
+        if dst_addr == &target_addr {
+            if let Some(res_id) = src_addr.strip_prefix("sources.") {
+                return res_id.to_string();
+            }
+            break;
+        }
+    }
+    name.to_string()
+}
 
-    // 1. All variables
+/// Maps an expected key onto the runtime buffer holding it, following `generate_runtime_c`'s
+/// naming: `sources.X` is `resource_X`, `prog.port` is `buf_prog_port`, and a bare port
+/// name refers to an output of the test's own program.
+fn expected_buffer(plan: &ProjectPlan, test_program: &str, key: &str) -> (String, DataType) {
+    if let Some(res_id) = key.strip_prefix("sources.") {
+        let dtype = plan.resources.get(res_id).map(|r| r.dtype).unwrap_or(DataType::F32);
+        return (format!("resource_{}", sanitize_id(res_id)), dtype);
+    }
+    let (prog_id, port) = key.split_once('.').unwrap_or((test_program, key));
+    let dtype = plan.programs.get(prog_id)
+        .and_then(|p| p.outputs.get(port))
+        .map(|p| p.dtype)
+        .unwrap_or(DataType::F32);
+    (format!("buf_{}_{}", sanitize_id(prog_id), sanitize_id(port)), dtype)
+}
+
+/// Finds program outputs written back into a source that programs also read from.
+/// Such feedback resources are double-buffered: programs read the front buffer, the writer
+/// fills the back buffer, and both are swapped at the end of the step.
+fn find_state_writers(plan: &ProjectPlan) -> HashMap<(String, String), String> {
+    let read_sources: HashSet<&str> = plan.links.iter()
+        .filter_map(|(src, _)| src.strip_prefix("sources."))
+        .collect();
+
+    let mut writers = HashMap::new();
+    for (src_addr, dst_addr) in &plan.links {
+        if let Some(res_id) = dst_addr.strip_prefix("sources.")
+            && read_sources.contains(res_id)
+            && let Some((src_p, src_port)) = src_addr.split_once('.')
+            && src_p != "sources" {
+            writers.insert((src_p.to_string(), src_port.to_string()), res_id.to_string());
+        }
+    }
+    writers
+}
+
+/// Symbolic dims of every program port plus the synthetic vars, sorted.
+fn runtime_vars(plan: &ProjectPlan) -> Vec<String> {
     let mut all_vars = HashSet::new();
     for interface in plan.programs.values() {
         for port in interface.inputs.values().chain(interface.outputs.values()) {
@@ -94,15 +184,78 @@ pub fn generate_runtime_c(plan: &ProjectPlan) -> String {
     }
     let mut sorted_vars: Vec<_> = all_vars.into_iter().collect();
     sorted_vars.sort();
-    context.insert("vars", &sorted_vars);
+    sorted_vars
+}
+
+/// Where a program input reads from; ids are sanitized runtime name suffixes.
+enum InputSource {
+    Resource(String),
+    /// Output buffer of another program, as `prog_port`.
+    Buffer(String),
+    Default(String),
+    Unconnected,
+}
+
+/// Resolves a program's inputs in the order its module function takes them, which is the
+/// order the program graph declares them in.
+fn program_inputs(plan: &ProjectPlan, prog_id: &str) -> Vec<(String, InputSource)> {
+    let in_names: Vec<String> = match plan.program_graphs.get(prog_id) {
+        Some(graph) => graph.inputs.iter().map(|p| p.name.clone()).collect(),
+        None => {
+            let mut names: Vec<_> = plan.programs[prog_id].inputs.keys().cloned().collect();
+            names.sort();
+            names
+        }
+    };
+    in_names.into_iter().map(|name| {
+        let target_addr = format!("{}.{}", prog_id, name);
+        let source = match plan.links.iter().find(|(_, dst_addr)| dst_addr == &target_addr) {
+            Some((src_addr, _)) => match src_addr.strip_prefix("sources.") {
+                Some(res_id) => InputSource::Resource(sanitize_id(res_id)),
+                None => {
+                    let (src_p, src_port) = src_addr.split_once('.').unwrap_or((src_addr, ""));
+                    InputSource::Buffer(format!("{}_{}", sanitize_id(src_p), sanitize_id(src_port)))
+                }
+            },
+            None if plan.input_defaults.contains_key(&target_addr) => {
+                InputSource::Default(format!("{}_{}", sanitize_id(prog_id), sanitize_id(&name)))
+            }
+            None => InputSource::Unconnected,
+        };
+        (name, source)
+    }).collect()
+}
+
+pub fn generate_runtime_c(plan: &ProjectPlan, opts: &RuntimeOptions) -> String {
+    let mut tera = Tera::default();
+    tera.add_raw_template("runtime", include_str!("../../templates/runtime.c.tera")).unwrap();
+    tera.add_raw_template("runtime_sdl2", include_str!("../../templates/runtime_sdl2.c.tera")).unwrap();
+
+    let mut context = Context::new();
+    context.insert("bench", &(opts.bench_iterations > 0));
+    // CUDA modules are separate translation units compiled by nvcc
+    context.insert("external_modules", &(opts.backend == Backend::Cuda));
+    context.insert("opencl_support", if opts.backend == Backend::OpenCl { OPENCL_SUPPORT } else { "" });
+    context.insert("workspace_alignment", &WORKSPACE_ALIGNMENT);
+
+    let uses_half = plan.resources.values().any(|r| r.dtype == DataType::F16)
+        || plan.programs.values().any(|p| p.inputs.values().chain(p.outputs.values()).any(|port| port.dtype == DataType::F16));
+    context.insert("half_support", if uses_half { HALF_SUPPORT } else { "" });
+
+    // 1. All variables
+    context.insert("vars", &runtime_vars(plan));
 
     // 2. Resources
+    let state_writers = find_state_writers(plan);
     let mut resources = Vec::new();
-    for (id, res) in &plan.resources {
+    let mut sorted_resources: Vec<_> = plan.resources.iter().collect();
+    sorted_resources.sort_by_key(|(id, _)| id.as_str());
+    for (id, res) in sorted_resources {
         resources.push(serde_json::json!({
             "id": sanitize_id(id),
             "dtype": res.dtype.to_c_type(),
-            "size_expr": res.shape.to_c_size_expr()
+            "size_expr": res.shape.to_c_size_expr(),
+            "is_state": state_writers.values().any(|r| r == id)
         }));
     }
     context.insert("resources", &resources);
@@ -113,11 +266,15 @@ pub fn generate_runtime_c(plan: &ProjectPlan) -> String {
         let interface = &plan.programs[prog_id];
         
         let mut out_ports = Vec::new();
-        for (name, port) in &interface.outputs {
+        let mut sorted_outputs: Vec<_> = interface.outputs.iter().collect();
+        sorted_outputs.sort_by_key(|(name, _)| name.as_str());
+        for (name, port) in sorted_outputs {
+            let state_res = state_writers.get(&(prog_id.clone(), name.clone()));
             out_ports.push(serde_json::json!({
                 "id": sanitize_id(name),
                 "dtype": port.dtype.to_c_type(),
-                "size_expr": port.shape.to_c_size_expr()
+                "size_expr": port.shape.to_c_size_expr(),
+                "state_res": state_res.map(|r| sanitize_id(r))
             }));
         }
 
@@ -126,40 +283,32 @@ pub fn generate_runtime_c(plan: &ProjectPlan) -> String {
             for slot in slots {
                 workspace_slots.push(serde_json::json!({
                     "dtype": slot.dtype.to_c_type(),
-                    "size_expr": slot.shape.to_c_size_expr()
+                    "size_expr": slot.size_expr
                 }));
             }
         }
 
-        let mut call_args = Vec::new();
-        let mut in_names: Vec<_> = interface.inputs.keys().collect();
-        in_names.sort();
-        for name in &in_names {
-            let target_addr = format!("{}.{}", prog_id, name);
-            let mut found = false;
-            for (src_addr, dst_addr) in &plan.links {
-                if dst_addr == &target_addr {
-                    if let Some(res_id) = src_addr.strip_prefix("sources.") {
-                        call_args.push(format!("resource_{}", sanitize_id(res_id)));
-                    } else if let Some((src_p, src_port)) = src_addr.split_once('.') {
-                        call_args.push(format!("buf_{}_{}", sanitize_id(src_p), sanitize_id(src_port)));
-                    }
-                    found = true;
-                    break;
-                }
-            }
-            if !found { call_args.push("NULL".to_string()); }
-        }
+        let inputs = program_inputs(plan, prog_id);
+        let mut call_args: Vec<String> = inputs.iter().map(|(_, source)| match source {
+            InputSource::Resource(res_id) => format!("resource_{}", res_id),
+            InputSource::Buffer(buf) => format!("buf_{}", buf),
+            InputSource::Default(def_id) => format!("default_{}", def_id),
+            InputSource::Unconnected => "NULL".to_string(),
+        }).collect();
         let mut out_names: Vec<_> = interface.outputs.keys().collect();
         out_names.sort();
         for name in &out_names {
             call_args.push(format!("buf_{}_{}", sanitize_id(prog_id), sanitize_id(name)));
         }
 
+        let params: Vec<String> = inputs.iter()
+            .map(|(name, _)| format!("const {}* restrict in_{}", interface.inputs[name].dtype.to_c_type(), sanitize_id(name)))
+            .chain(out_names.iter().map(|name| format!("{}* restrict out_{}", interface.outputs[*name].dtype.to_c_type(), sanitize_id(name))))
+            .collect();
+
         programs.push(serde_json::json!({
             "id": sanitize_id(prog_id),
-            "inputs": in_names,
-            "outputs": out_names,
+            "params": params,
             "outputs_ports": out_ports,
             "workspace_size": workspace_slots.len(),
             "workspace_slots": workspace_slots,
@@ -177,25 +326,134 @@ pub fn generate_runtime_c(plan: &ProjectPlan) -> String {
     }
     context.insert("synthetic_vars", &syn_vars);
 
+    // Runtime shape assertions (debug runtime only)
+    let mut assertions = Vec::new();
+    if opts.debug_runtime {
+        for prog_id in &plan.execution_order {
+            for assertion in plan.runtime_assertions.get(prog_id).into_iter().flatten() {
+                assertions.push(serde_json::json!({
+                    "condition": assertion.condition,
+                    "message": assertion.message.replace('"', "'")
+                }));
+            }
+        }
+    }
+    context.insert("assertions", &assertions);
+
     // 5. Sync Back
     let mut sync_back = Vec::new();
     for (src_addr, dst_addr) in &plan.links {
-        if let Some(res_id) = dst_addr.strip_prefix("sources.") {
-            if let Some((src_p, src_port)) = src_addr.split_once('.') {
-                if src_p != "sources" {
-                    let res = &plan.resources[res_id];
-                    sync_back.push(serde_json::json!({
-                        "res_id": sanitize_id(res_id),
-                        "src_prog": sanitize_id(src_p),
-                        "src_port": sanitize_id(src_port),
-                        "dtype": res.dtype.to_c_type(),
-                        "size_expr": res.shape.to_c_size_expr()
-                    }));
-                }
-            }
+        if let Some(res_id) = dst_addr.strip_prefix("sources.")
+            && let Some((src_p, src_port)) = src_addr.split_once('.')
+            && src_p != "sources"
+            && !state_writers.contains_key(&(src_p.to_string(), src_port.to_string())) {
+            let res = &plan.resources[res_id];
+            sync_back.push(serde_json::json!({
+                "res_id": sanitize_id(res_id),
+                "src_prog": sanitize_id(src_p),
+                "src_port": sanitize_id(src_port),
+                "dtype": res.dtype.to_c_type(),
+                "size_expr": res.shape.to_c_size_expr()
+            }));
         }
     }
     context.insert("sync_back", &sync_back);
 
-    tera.render("runtime", &context).expect("Failed to render runtime template")
-}
\ No newline at end of file
+    // 6. Default-filled buffers for unconnected inputs
+    let mut defaults = Vec::new();
+    let mut sorted_defaults: Vec<_> = plan.input_defaults.iter().collect();
+    sorted_defaults.sort_by_key(|(addr, _)| addr.as_str());
+    for (addr, value) in sorted_defaults {
+        let (prog_id, port_name) = addr.split_once('.').unwrap_or_default();
+        let port = &plan.programs[prog_id].inputs[port_name];
+        defaults.push(serde_json::json!({
+            "id": format!("{}_{}", sanitize_id(prog_id), sanitize_id(port_name)),
+            "dtype": port.dtype.to_c_type(),
+            "size_expr": port.shape.to_c_size_expr(),
+            "value": format!("{:?}f", value)
+        }));
+    }
+    context.insert("defaults", &defaults);
+
+    // 7. State swaps (double-buffered feedback resources)
+    let mut state_swaps: Vec<_> = state_writers.values().map(|r| serde_json::json!({
+        "res_id": sanitize_id(r),
+        "dtype": plan.resources[r].dtype.to_c_type()
+    })).collect();
+    state_swaps.sort_by_key(|s| s["res_id"].as_str().unwrap_or_default().to_string());
+    context.insert("state_swaps", &state_swaps);
+
+    // 8. Display framebuffer, blitted from the display source after every step
+    let display = plan.display_source.as_ref().map(|res_id| {
+        let res = &plan.resources[res_id];
+        let dims = &res.shape.dims;
+        serde_json::json!({
+            "id": sanitize_id(res_id),
+            "height": dims[0].to_c_expr(),
+            "width": dims[1].to_c_expr(),
+            "channels": match dims.get(2) { Some(Dim::Static(c)) => *c, _ => 1 },
+            "load_fn": if res.dtype == DataType::F16 { "sion_half_to_float" } else { "" }
+        })
+    });
+    context.insert("display", &display);
+
+    let template = match opts.target {
+        RuntimeTarget::Headless => "runtime",
+        RuntimeTarget::Sdl2 => {
+            assert!(display.is_some(), "sdl2 runtime requires a display source");
+            "runtime_sdl2"
+        }
+    };
+    tera.render(template, &context).expect("Failed to render runtime template")
+}
+/// C API over the runtime, addressed by manifest names. Settable names are the sources and
+/// every program input linked to one; readable names are the sources and all program outputs.
+pub fn generate_api_c(plan: &ProjectPlan) -> String {
+    let mut tera = Tera::default();
+    tera.add_raw_template("sion_api", include_str!("../../templates/sion_api.c.tera")).unwrap();
+
+    let port_entry = |addr: String, buf: String, dtype: DataType, size_expr: String| serde_json::json!({
+        "addr": addr,
+        "buf": buf,
+        "size_expr": size_expr,
+        "store_fn": if dtype == DataType::F16 { "sion_float_to_half".to_string() } else { format!("({})", dtype.to_c_type()) },
+        "load_fn": if dtype == DataType::F16 { "sion_half_to_float" } else { "(float)" }
+    });
+
+    let mut inputs = Vec::new();
+    let mut outputs = Vec::new();
+    let mut sorted_resources: Vec<_> = plan.resources.iter().collect();
+    sorted_resources.sort_by_key(|(id, _)| id.as_str());
+    for (id, res) in sorted_resources {
+        let addr = format!("sources.{}", id);
+        let buf = format!("resource_{}", sanitize_id(id));
+        inputs.push(port_entry(addr.clone(), buf.clone(), res.dtype, res.shape.to_c_size_expr()));
+        outputs.push(port_entry(addr, buf, res.dtype, res.shape.to_c_size_expr()));
+    }
+
+    let state_writers = find_state_writers(plan);
+    for prog_id in &plan.execution_order {
+        let interface = &plan.programs[prog_id];
+        for (name, source) in program_inputs(plan, prog_id) {
+            if let InputSource::Resource(res_id) = source {
+                let port = &interface.inputs[&name];
+                inputs.push(port_entry(format!("{}.{}", prog_id, name), format!("resource_{}", res_id), port.dtype, port.shape.to_c_size_expr()));
+            }
+        }
+        let mut sorted_outputs: Vec<_> = interface.outputs.iter().collect();
+        sorted_outputs.sort_by_key(|(name, _)| name.as_str());
+        for (name, port) in sorted_outputs {
+            // State outputs are swapped into the front buffer of their resource after each run
+            let buf = match state_writers.get(&(prog_id.clone(), name.clone())) {
+                Some(res_id) => format!("resource_{}", sanitize_id(res_id)),
+                None => format!("buf_{}_{}", sanitize_id(prog_id), sanitize_id(name)),
+            };
+            outputs.push(port_entry(format!("{}.{}", prog_id, name), buf, port.dtype, port.shape.to_c_size_expr()));
+        }
+    }
+
+    let mut context = Context::new();
+    context.insert("inputs", &inputs);
+    context.insert("outputs", &outputs);
+    tera.render("sion_api", &context).expect("Failed to render sion_api template")
+}
@@ -0,0 +1,194 @@
+//! Runtime and test runner for the Rust backend. They mirror runtime.c and test_runner.c:
+//! the runtime owns every resource, buffer and workspace as a field of `Runtime`, and
+//! state outputs write straight into the back buffer of their resource.
+
+use super::{expected_buffer, find_state_writers, program_inputs, runtime_vars, test_input_resource, InputSource, RuntimeOptions};
+use crate::analyzer::ProjectPlan;
+use crate::codegen::rust::{float_literal, rust_type, HALF_SUPPORT};
+use crate::core::types::DataType;
+use crate::core::utils::sanitize_id;
+use crate::manifest::Test;
+use tera::{Context, Tera};
+
+/// Converts an f32 literal into a value of the given storage type.
+fn store_expr(dtype: DataType, value: f32) -> String {
+    match dtype {
+        DataType::F32 => float_literal(value),
+        DataType::F16 => format!("sion_float_to_half({})", float_literal(value)),
+        other => format!("{} as {}", float_literal(value), rust_type(other)),
+    }
+}
+
+pub fn generate_test_runner_rs(plan: &ProjectPlan, tests: &[Test], opts: &RuntimeOptions) -> String {
+    let mut tera = Tera::default();
+    tera.add_raw_template("test_runner", include_str!("../../templates/main.rs.tera")).unwrap();
+
+    let state_writers = find_state_writers(plan);
+    let mut rendered_tests = Vec::new();
+    for test in tests {
+        let mut inputs = Vec::new();
+        for (name, data) in &test.inputs {
+            let resource_id = test_input_resource(plan, &test.program, name);
+            let dtype = plan.resources.get(&resource_id).map(|r| r.dtype).unwrap_or(DataType::F32);
+            inputs.push(serde_json::json!({
+                "id": sanitize_id(&resource_id),
+                "data": data.iter().map(|v| store_expr(dtype, *v as f32)).collect::<Vec<_>>()
+            }));
+        }
+
+        let mut outputs = Vec::new();
+        for (name, expected) in &test.expected {
+            let (mut buf_name, dtype) = expected_buffer(plan, &test.program, name);
+            // State outputs have no buffer of their own; after the swap they are the front buffer
+            let (prog_id, port) = name.split_once('.').unwrap_or((&test.program, name));
+            if let Some(res_id) = state_writers.get(&(prog_id.to_string(), port.to_string())) {
+                buf_name = format!("resource_{}", sanitize_id(res_id));
+            }
+
+            let expected_items: Vec<_> = expected.iter().enumerate().map(|(idx, val)| serde_json::json!({
+                "idx": idx,
+                "val": float_literal(*val as f32)
+            })).collect();
+            outputs.push(serde_json::json!({
+                "full_name": name,
+                "buf_name": buf_name,
+                "load_fn": if dtype == DataType::F16 { "sion_half_to_float" } else { "" },
+                // Half precision keeps ~3 significant decimal digits
+                "tolerance": if dtype == DataType::F16 { "1e-3 * f64::max(1.0, (EXPECTED as f64).abs())" } else { "1e-5" },
+                "expected_items": expected_items
+            }));
+        }
+
+        rendered_tests.push(serde_json::json!({
+            "name": test.name,
+            "inputs": inputs,
+            "outputs": outputs
+        }));
+    }
+
+    let modules: Vec<String> = plan.execution_order.iter().map(|id| sanitize_id(id)).collect();
+    let mut context = Context::new();
+    context.insert("modules", &modules);
+    context.insert("tests", &rendered_tests);
+    context.insert("bench_iterations", &opts.bench_iterations);
+    tera.render("test_runner", &context).expect("Failed to render main.rs template")
+}
+
+pub fn generate_runtime_rs(plan: &ProjectPlan, opts: &RuntimeOptions) -> String {
+    let mut tera = Tera::default();
+    tera.add_raw_template("runtime", include_str!("../../templates/runtime.rs.tera")).unwrap();
+
+    let mut context = Context::new();
+    context.insert("bench", &(opts.bench_iterations > 0));
+    let uses_half = plan.resources.values().any(|r| r.dtype == DataType::F16)
+        || plan.programs.values().any(|p| p.inputs.values().chain(p.outputs.values()).any(|port| port.dtype == DataType::F16));
+    context.insert("half_support", if uses_half { HALF_SUPPORT } else { "" });
+    context.insert("vars", &runtime_vars(plan));
+
+    let state_writers = find_state_writers(plan);
+    let mut resources = Vec::new();
+    let mut sorted_resources: Vec<_> = plan.resources.iter().collect();
+    sorted_resources.sort_by_key(|(id, _)| id.as_str());
+    for (id, res) in sorted_resources {
+        resources.push(serde_json::json!({
+            "id": sanitize_id(id),
+            "dtype": rust_type(res.dtype),
+            "size_expr": res.shape.to_c_size_expr(),
+            "is_state": state_writers.values().any(|r| r == id)
+        }));
+    }
+    context.insert("resources", &resources);
+
+    let mut programs = Vec::new();
+    for prog_id in &plan.execution_order {
+        let interface = &plan.programs[prog_id];
+
+        let mut out_ports = Vec::new();
+        let mut call_args: Vec<String> = program_inputs(plan, prog_id).into_iter().map(|(_, source)| match source {
+            InputSource::Resource(res_id) => format!("&self.resource_{}", res_id),
+            InputSource::Buffer(buf) => format!("&self.buf_{}", buf),
+            InputSource::Default(def_id) => format!("&self.default_{}", def_id),
+            InputSource::Unconnected => "&[]".to_string(),
+        }).collect();
+        let mut sorted_outputs: Vec<_> = interface.outputs.iter().collect();
+        sorted_outputs.sort_by_key(|(name, _)| name.as_str());
+        for (name, port) in sorted_outputs {
+            match state_writers.get(&(prog_id.clone(), name.clone())) {
+                Some(res_id) => call_args.push(format!("&mut self.resource_{}_back", sanitize_id(res_id))),
+                None => {
+                    call_args.push(format!("&mut self.buf_{}_{}", sanitize_id(prog_id), sanitize_id(name)));
+                    out_ports.push(serde_json::json!({
+                        "id": sanitize_id(name),
+                        "dtype": rust_type(port.dtype),
+                        "size_expr": port.shape.to_c_size_expr()
+                    }));
+                }
+            }
+        }
+
+        let slot_sizes: Vec<&str> = plan.workspace_info.get(prog_id).into_iter().flatten()
+            .map(|slot| slot.size_expr.as_str())
+            .collect();
+
+        programs.push(serde_json::json!({
+            "id": sanitize_id(prog_id),
+            "outputs_ports": out_ports,
+            "workspace_slots": slot_sizes,
+            "call_args": call_args
+        }));
+    }
+    context.insert("programs", &programs);
+
+    let mut sorted_syn: Vec<_> = plan.synthetic_vars.iter().collect();
+    sorted_syn.sort();
+    context.insert("synthetic_vars", &sorted_syn);
+
+    let mut assertions = Vec::new();
+    if opts.debug_runtime {
+        for prog_id in &plan.execution_order {
+            for assertion in plan.runtime_assertions.get(prog_id).into_iter().flatten() {
+                assertions.push(serde_json::json!({
+                    "condition": assertion.condition,
+                    "message": assertion.message.replace('"', "'")
+                }));
+            }
+        }
+    }
+    context.insert("assertions", &assertions);
+
+    let mut sync_back = Vec::new();
+    for (src_addr, dst_addr) in &plan.links {
+        if let Some(res_id) = dst_addr.strip_prefix("sources.")
+            && let Some((src_p, src_port)) = src_addr.split_once('.')
+            && src_p != "sources"
+            && !state_writers.contains_key(&(src_p.to_string(), src_port.to_string())) {
+            sync_back.push(serde_json::json!({
+                "res_id": sanitize_id(res_id),
+                "src_prog": sanitize_id(src_p),
+                "src_port": sanitize_id(src_port)
+            }));
+        }
+    }
+    context.insert("sync_back", &sync_back);
+
+    let mut defaults = Vec::new();
+    let mut sorted_defaults: Vec<_> = plan.input_defaults.iter().collect();
+    sorted_defaults.sort_by_key(|(addr, _)| addr.as_str());
+    for (addr, value) in sorted_defaults {
+        let (prog_id, port_name) = addr.split_once('.').unwrap_or_default();
+        let port = &plan.programs[prog_id].inputs[port_name];
+        defaults.push(serde_json::json!({
+            "id": format!("{}_{}", sanitize_id(prog_id), sanitize_id(port_name)),
+            "dtype": rust_type(port.dtype),
+            "size_expr": port.shape.to_c_size_expr(),
+            "value": store_expr(port.dtype, *value)
+        }));
+    }
+    context.insert("defaults", &defaults);
+
+    let mut state_swaps: Vec<String> = state_writers.values().map(|r| sanitize_id(r)).collect();
+    state_swaps.sort();
+    context.insert("state_swaps", &state_swaps);
+
+    tera.render("runtime", &context).expect("Failed to render runtime.rs template")
+}
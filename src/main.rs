@@ -1,118 +1,142 @@
-use anyhow::{Context};
-use std::path::Path;
-
-mod manifest;
-mod analyzer;
-mod inliner;
-mod resolver;
-mod linearizer;
-mod codegen;
-mod linker;
-mod core;
+use std::path::{Path, PathBuf};
+use sion_flow_rt::{CompileOptions, compile_manifest, build_and_run, build_library};
+use sion_flow_rt::codegen::Backend;
+use sion_flow_rt::linker::RuntimeTarget;
+use sion_flow_rt::report;
+use anyhow::Context;
 
 fn main() -> anyhow::Result<()> {
     let args: Vec<String> = std::env::args().collect();
     if args.len() < 2 {
-        println!("Usage: SionFlowRT <manifest.json> [--test] [--run]");
+        println!("Usage: SionFlowRT <manifest.json> [--test] [--run] [--bench [N]] [--report] [--debug-runtime] [--debug-codegen] [--line-directives] [--lib-path DIR]... [--max-inline-depth N] [--blas] [--blas-lib NAME] [--parallel-threshold N] [--unroll-threshold N] [--split-functions N] [--backend c|cuda|opencl|rust] [--emit-lib] [--out-dir DIR] [--include-dir DIR] [--dump-shapes PATH] [--jobs N] [--keep-going] [--report-json PATH] [--print-interfaces] [--runtime headless|sdl2]");
         return Ok(());
     }
 
     let manifest_path = &args[1];
-    let is_test = args.contains(&"--test".to_string());
-    let is_run = args.contains(&"--run".to_string());
-
-    println!("SionFlowRT 2.0 - Starting Compilation...");
-
-    // 1. Load Manifest
-    let manifest_content = std::fs::read_to_string(manifest_path)
-        .with_context(|| format!("Failed to read manifest at {}", manifest_path))?;
-    let manifest = manifest::Manifest::from_json(&manifest_content)?;
-    println!("  [1/6] Manifest loaded: {}", manifest_path);
-
-    // 2. Project Analysis
-    let manifest_dir = Path::new(manifest_path).parent().unwrap_or(Path::new("."));
-    let mut plan = analyzer::analyze_project(&manifest, manifest_dir)?;
-    println!("  [2/6] Project analysis complete. {} programs found.", plan.programs.len());
+    let bench_iterations = match args.iter().position(|a| a == "--bench") {
+        Some(pos) => args.get(pos + 1).and_then(|n| n.parse::<usize>().ok()).unwrap_or(1),
+        None => 0,
+    };
+    let lib_paths = args.iter()
+        .zip(args.iter().skip(1))
+        .filter(|(flag, _)| *flag == "--lib-path")
+        .map(|(_, dir)| PathBuf::from(dir))
+        .collect();
+    let mut opts = CompileOptions {
+        test: args.contains(&"--test".to_string()),
+        run: args.contains(&"--run".to_string()),
+        bench_iterations,
+        report: args.contains(&"--report".to_string()),
+        debug_runtime: args.contains(&"--debug-runtime".to_string()),
+        debug_codegen: args.contains(&"--debug-codegen".to_string()),
+        line_directives: args.contains(&"--line-directives".to_string()),
+        lib_paths,
+        blas: args.contains(&"--blas".to_string()),
+        emit_lib: args.contains(&"--emit-lib".to_string()),
+        keep_going: args.contains(&"--keep-going".to_string()),
+        ..CompileOptions::default()
+    };
+    if let Some(pos) = args.iter().position(|a| a == "--blas-lib") {
+        opts.blas_lib = args.get(pos + 1)
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("--blas-lib expects a library name"))?;
+    }
+    if let Some(pos) = args.iter().position(|a| a == "--out-dir") {
+        opts.out_dir = args.get(pos + 1)
+            .map(PathBuf::from)
+            .ok_or_else(|| anyhow::anyhow!("--out-dir expects a directory"))?;
+    }
+    if let Some(pos) = args.iter().position(|a| a == "--include-dir") {
+        opts.include_dir = Some(args.get(pos + 1)
+            .map(PathBuf::from)
+            .ok_or_else(|| anyhow::anyhow!("--include-dir expects a directory"))?);
+    }
+    if let Some(pos) = args.iter().position(|a| a == "--dump-shapes") {
+        opts.dump_shapes = Some(args.get(pos + 1)
+            .map(PathBuf::from)
+            .ok_or_else(|| anyhow::anyhow!("--dump-shapes expects a file path"))?);
+    }
+    if let Some(pos) = args.iter().position(|a| a == "--max-inline-depth") {
+        opts.max_inline_depth = args.get(pos + 1)
+            .and_then(|n| n.parse::<usize>().ok())
+            .ok_or_else(|| anyhow::anyhow!("--max-inline-depth expects a number"))?;
+    }
+    if let Some(pos) = args.iter().position(|a| a == "--parallel-threshold") {
+        opts.parallel_threshold = args.get(pos + 1)
+            .and_then(|n| n.parse::<usize>().ok())
+            .ok_or_else(|| anyhow::anyhow!("--parallel-threshold expects a number"))?;
+    }
+    if let Some(pos) = args.iter().position(|a| a == "--unroll-threshold") {
+        opts.unroll_threshold = args.get(pos + 1)
+            .and_then(|n| n.parse::<usize>().ok())
+            .ok_or_else(|| anyhow::anyhow!("--unroll-threshold expects a number"))?;
+    }
+    if let Some(pos) = args.iter().position(|a| a == "--split-functions") {
+        opts.split_functions = args.get(pos + 1)
+            .and_then(|n| n.parse::<usize>().ok())
+            .ok_or_else(|| anyhow::anyhow!("--split-functions expects a number"))?;
+    }
+    if let Some(pos) = args.iter().position(|a| a == "--jobs") {
+        opts.jobs = args.get(pos + 1)
+            .and_then(|n| n.parse::<usize>().ok())
+            .filter(|&n| n > 0)
+            .ok_or_else(|| anyhow::anyhow!("--jobs expects a positive number"))?;
+    }
 
-    // 3. Module Compilation (Per Program)
-    for prog_id in &plan.execution_order {
-        println!("  [3/6] Compiling module: {}", prog_id);
-        
-        let prog_def = manifest.programs.iter().find(|p| &p.id == prog_id).unwrap();
-        let prog_interface = plan.programs.get(prog_id).ok_or_else(|| anyhow::anyhow!("Interface for {} not found", prog_id))?;
-        let prog_graph = plan.program_graphs.get(prog_id).cloned().ok_or_else(|| anyhow::anyhow!("Graph for {} not found", prog_id))?;
-        let prog_path = if prog_def.path.ends_with(".json") { 
-            prog_def.path.clone() 
-        } else { 
-            format!("{}.json", prog_def.path) 
-        };
-        
-        let raw_ir = inliner::load_and_inline(prog_graph, Path::new(&prog_path), &manifest, &mut plan.synthetic_vars)?;
-        println!("    - Inlining complete (nodes: {})", raw_ir.graph.node_count());
+    if let Some(pos) = args.iter().position(|a| a == "--runtime") {
+        let name = args.get(pos + 1)
+            .ok_or_else(|| anyhow::anyhow!("--runtime expects \"headless\" or \"sdl2\""))?;
+        opts.runtime = RuntimeTarget::from_name(name)?;
+    }
+    if let Some(pos) = args.iter().position(|a| a == "--backend") {
+        let name = args.get(pos + 1)
+            .ok_or_else(|| anyhow::anyhow!("--backend expects a backend name"))?;
+        opts.backend = Some(Backend::from_name(name)?);
+    }
+    let report_json = match args.iter().position(|a| a == "--report-json") {
+        Some(pos) => Some(args.get(pos + 1)
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("--report-json expects a file path"))?),
+        None => None,
+    };
 
-        let resolved_ir = resolver::resolve_module(raw_ir, prog_interface.inputs.clone())?;
-        println!("    - Type & Shape resolution complete");
+    let print_interfaces = args.contains(&"--print-interfaces".to_string());
 
-        let linear_ir = linearizer::linearize(resolved_ir)?;
-        println!("    - Linearization complete");
+    println!("SionFlowRT 2.0 - Starting Compilation...");
 
-        plan.workspace_info.insert(prog_id.clone(), linear_ir.get_workspace_slots());
+    let artifacts = compile_manifest(Path::new(manifest_path), &opts)?;
+    artifacts.write()?;
 
-        let c_code = codegen::generate_module_source(prog_id, &linear_ir);
-        let h_code = codegen::generate_module_header(prog_id, &linear_ir);
-        
-        std::fs::create_dir_all("generated")?;
-        std::fs::write(format!("generated/{}.c", prog_id), c_code)?;
-        std::fs::write(format!("generated/{}.h", prog_id), h_code)?;
-        println!("    - C code generated");
+    if print_interfaces {
+        print!("{}", report::format_interfaces(&artifacts.interfaces));
     }
 
-    // 4. Linker (Generate top-level runtime)
-    let runtime_c = linker::generate_runtime_c(&plan);
-    std::fs::write("generated/runtime.c", runtime_c)?;
-    println!("  [4/6] Linker generated runtime.c");
-
-    // 5. Test Runner Generation
-    if is_test || is_run {
-        let runner_c = linker::generate_test_runner(&plan, &manifest.tests);
-        std::fs::write("generated/test_runner.c", runner_c)?;
-        println!("  [5/6] Generated test_runner.c");
-
-        println!("  [6/6] Compiling and running...");
-        std::fs::create_dir_all("out")?;
-        
-        let output_name = if cfg!(windows) { "out/test_runner.exe" } else { "out/test_runner" };
-        
-        let status = std::process::Command::new("gcc")
-            .arg("generated/test_runner.c")
-            .arg("-Igenerated")
-            .arg("-o")
-            .arg(output_name)
-            .arg("-lm")
-            .status()
-            .context("Failed to execute gcc. Is it installed?")?;
+    // Written before any keep-going failure is reported, so CI still gets the report
+    if let Some(path) = &report_json {
+        std::fs::write(path, report::json::generate_report_json(&artifacts.report))
+            .with_context(|| format!("Failed to write compile report to {}", path))?;
+        println!("  Compile report written to {}", path);
+    }
 
-        if !status.success() {
-            anyhow::bail!("C compilation failed");
+    if !artifacts.failures.is_empty() {
+        for failure in &artifacts.failures {
+            println!("  Program '{}' failed: {}", failure.program, failure.message);
         }
+        let failed: Vec<&str> = artifacts.failures.iter().map(|f| f.program.as_str()).collect();
+        let generated: Vec<&str> = artifacts.report.programs.iter().map(|p| p.id.as_str()).collect();
+        anyhow::bail!(
+            "Compilation failed for {} of {} programs ({}); modules were generated for: {}",
+            failed.len(), failed.len() + generated.len(), failed.join(", "),
+            if generated.is_empty() { "none".to_string() } else { generated.join(", ") });
+    }
 
-        if is_test || is_run {
-            let mut run_cmd = if cfg!(windows) {
-                 std::process::Command::new(format!("{}.exe", output_name.strip_suffix(".exe").unwrap_or(output_name)))
-            } else {
-                 std::process::Command::new(format!("./{}", output_name))
-            };
+    if opts.emit_lib {
+        let library = build_library(&artifacts, &opts)?;
+        println!("  Static library written to {}", library.display());
+    }
 
-            let run_status = run_cmd
-                .stdout(std::process::Stdio::inherit())
-                .stderr(std::process::Stdio::inherit())
-                .status()
-                .context("Failed to run the compiled test runner")?;
-            
-            if is_test && !run_status.success() {
-                anyhow::bail!("Tests failed");
-            }
-        }
+    if opts.test || opts.run || opts.bench_iterations > 0 {
+        build_and_run(&artifacts, &opts)?;
     } else {
         println!("  [5/6] Skipping test generation (use --test to enable)");
         println!("  [6/6] Done.");
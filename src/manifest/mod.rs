@@ -6,6 +6,8 @@ pub struct SourceDef {
     #[serde(rename = "type")]
     pub kind: Option<String>,
     pub shape: Vec<serde_json::Value>,
+    #[serde(default)]
+    pub dtype: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -18,8 +20,8 @@ pub struct ProgramDef {
 pub struct Test {
     pub name: String,
     pub program: String,
-    pub inputs: BTreeMap<String, Vec<f32>>,
-    pub expected: BTreeMap<String, Vec<f32>>,
+    pub inputs: BTreeMap<String, Vec<f64>>,
+    pub expected: BTreeMap<String, Vec<f64>>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -31,6 +33,27 @@ pub struct Manifest {
     pub tests: Vec<Test>,
     #[serde(default)]
     pub parameters: Option<BTreeMap<String, serde_json::Value>>,
+    /// Extra subgraph search roots, relative to the manifest directory.
+    #[serde(default)]
+    pub lib_paths: Vec<String>,
+    /// Constant fill for program inputs left unconnected, keyed by "prog.port".
+    #[serde(default)]
+    pub defaults: BTreeMap<String, f32>,
+    /// Element order of program outputs keyed by "prog.port": "row_major" (default) or "column_major".
+    #[serde(default)]
+    pub layouts: BTreeMap<String, String>,
+    /// ReduceSum accumulation for every program: "default" or "kahan" (compensated summation).
+    #[serde(default)]
+    pub reduce_mode: Option<String>,
+    /// Lower static MatMuls to BLAS calls.
+    #[serde(default)]
+    pub blas: bool,
+    /// Split each generated C module into sub-functions of this many nodes (0 keeps one function).
+    #[serde(default)]
+    pub split_functions: usize,
+    /// Code generation target: "c" (default), "cuda", "opencl" or "rust".
+    #[serde(default)]
+    pub backend: Option<String>,
 }
 
 impl Manifest {
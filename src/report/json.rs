@@ -0,0 +1,72 @@
+//! `--report-json`: a machine-readable summary of one compilation for CI, listing every
+//! program with its size, generated files and how long each pipeline stage took.
+
+use crate::core::types::WorkspaceSlot;
+use crate::ProgramFailure;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CompileReport {
+    pub manifest: String,
+    pub backend: String,
+    /// Manifest loading and project analysis.
+    pub analysis_ms: f64,
+    /// Runtime, interface and test runner generation.
+    pub link_ms: f64,
+    /// Programs in execution order.
+    pub programs: Vec<ProgramReport>,
+    /// Programs that failed under `--keep-going`, in execution order; they have no entry above.
+    #[serde(default)]
+    pub failures: Vec<ProgramFailure>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ProgramReport {
+    pub id: String,
+    /// Nodes left after inlining and linearization, graph inputs and outputs included.
+    pub node_count: usize,
+    pub workspace_slots: usize,
+    /// Total workspace size; `None` when a slot depends on a symbolic dim.
+    pub workspace_bytes: Option<usize>,
+    pub files: Vec<String>,
+    pub timings: StageTimings,
+}
+
+/// Wall-clock time of each per-program stage, in milliseconds.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct StageTimings {
+    pub inline_ms: f64,
+    pub resolve_ms: f64,
+    pub linearize_ms: f64,
+    pub codegen_ms: f64,
+}
+
+pub fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+pub fn workspace_bytes(slots: &[WorkspaceSlot]) -> Option<usize> {
+    slots.iter()
+        .map(|slot| slot.size_expr.parse::<usize>().ok().map(|elems| elems * slot.dtype.size_bytes()))
+        .sum()
+}
+
+/// Pretty-printed report, as written by `--report-json`.
+///
+/// ```
+/// use sion_flow_rt::report::json::{generate_report_json, CompileReport};
+///
+/// let manifest = std::path::Path::new("tests/programs/independent_programs/manifest.json");
+/// let opts = sion_flow_rt::CompileOptions::default();
+/// let artifacts = sion_flow_rt::compile_manifest(manifest, &opts).unwrap();
+/// let parsed: CompileReport = serde_json::from_str(&generate_report_json(&artifacts.report)).unwrap();
+///
+/// let mut ids: Vec<&str> = parsed.programs.iter().map(|p| p.id.as_str()).collect();
+/// ids.sort();
+/// assert_eq!(ids, ["combine", "offset", "scale", "square"]);
+/// assert!(parsed.programs.iter().all(|p| p.files.len() == 2 && p.node_count > 0));
+/// ```
+pub fn generate_report_json(report: &CompileReport) -> String {
+    serde_json::to_string_pretty(report).expect("Failed to serialize compile report")
+}
@@ -0,0 +1,160 @@
+pub mod json;
+
+use crate::core::op::Op;
+use crate::core::types::{Dim, Port};
+use crate::ProgramPorts;
+use crate::linearizer::ir::{LinearIR, LinearNode};
+
+/// Estimated work of a single node. `None` means a symbolic dim made the count unknown.
+#[derive(Debug, Clone)]
+pub struct NodeCost {
+    pub id: String,
+    pub op: &'static str,
+    pub flops: Option<usize>,
+    pub bytes: Option<usize>,
+}
+
+/// Per-node FLOP and byte estimates for `--report`, skipping the graph inputs.
+///
+/// ```
+/// use sion_flow_rt::core::op::Op;
+/// use sion_flow_rt::core::types::{Dim, Shape};
+/// use sion_flow_rt::inliner::builder::GraphBuilder;
+///
+/// let mut builder = GraphBuilder::new();
+/// let a = builder.input("a", Shape { dims: vec![Dim::Static(4), Dim::Static(4)] });
+/// let b = builder.input("b", Shape { dims: vec![Dim::Static(4), Dim::Static(4)] });
+/// let product = builder.named_op("product", Op::MatMul, &[a, b]);
+/// builder.output("out", &product);
+///
+/// let (raw, input_specs) = builder.build();
+/// let resolved = sion_flow_rt::resolver::resolve_module(raw, input_specs).unwrap();
+/// let linear = sion_flow_rt::linearizer::linearize(resolved).unwrap();
+/// let costs = sion_flow_rt::report::estimate_costs(&linear);
+/// let matmul = costs.iter().find(|c| c.id == "product").unwrap();
+/// // 2 * M * N * K
+/// assert_eq!(matmul.flops, Some(2 * 4 * 4 * 4));
+/// // two 4x4 inputs read, one written, 4 bytes each
+/// assert_eq!(matmul.bytes, Some(3 * 16 * 4));
+/// ```
+pub fn estimate_costs(ir: &LinearIR) -> Vec<NodeCost> {
+    ir.nodes.iter()
+        .filter(|n| !matches!(n.op, Op::Input { .. }))
+        .map(|n| NodeCost {
+            id: n.id.clone(),
+            op: n.op.name(),
+            flops: estimate_flops(n),
+            bytes: estimate_bytes(n),
+        })
+        .collect()
+}
+
+fn estimate_flops(node: &LinearNode) -> Option<usize> {
+    let out = node.shape.static_size();
+    let input_size = |i: usize| node.inputs.get(i).and_then(|inp| inp.shape.static_size());
+    let input_dim = |i: usize, axis_from_end: usize| {
+        let dims = &node.inputs.get(i)?.shape.dims;
+        match dims.get(dims.len().checked_sub(axis_from_end + 1)?)? {
+            Dim::Static(v) => Some(*v),
+            Dim::Variable(_) => None,
+        }
+    };
+
+    match &node.op {
+        Op::Input { .. } | Op::Output { .. } | Op::Constant { .. } | Op::Identity | Op::Dropout { .. }
+        | Op::Reshape { .. } | Op::Flatten { .. } | Op::Broadcast { .. } | Op::Transpose { .. } | Op::Split { .. }
+        | Op::Window { .. } | Op::GatherND | Op::Diag | Op::Iota { .. } => Some(0),
+        Op::Sin | Op::Abs | Op::Sqrt | Op::Square | Op::Exp | Op::Log
+        | Op::Add | Op::Sub | Op::Mul | Op::Div | Op::Min | Op::Max | Op::Pow
+        | Op::Mod | Op::And | Op::Or | Op::Xor | Op::Shl | Op::Shr => out,
+        Op::Lerp => out.map(|o| 3 * o),
+        // 4-tap weighted sum plus source coordinate math; nearest is a pure copy
+        Op::Resize { mode, .. } if mode == "bilinear" => out.map(|o| 11 * o),
+        Op::Resize { .. } => Some(0),
+        Op::RandomUniform { .. } => out.map(|o| 2 * o),
+        Op::RandomNormal { .. } => out.map(|o| 8 * o),
+        Op::ReduceSum { .. } => input_size(0),
+        // one comparison per element
+        Op::ArgMax { .. } | Op::ArgMin { .. } => input_size(0),
+        Op::Trace => input_dim(0, 0),
+        // mean pass, then subtract + square + accumulate
+        Op::ReduceVar { .. } | Op::ReduceStd { .. } => input_size(0).map(|s| 4 * s),
+        // square + accumulate, then scale
+        Op::Normalize { .. } => input_size(0).map(|s| 3 * s),
+        // mean and variance passes, then subtract + scale (+ affine multiply-add)
+        Op::LayerNorm { affine, .. } => out.map(|o| if *affine { 9 * o } else { 7 * o }),
+        // 2 * M * N * K across the batch
+        Op::MatMul => Some(2 * out? * input_dim(0, 0)?),
+        // 2 * out * C_in * K
+        Op::Conv1D { .. } => Some(2 * out? * input_dim(1, 1)? * input_dim(1, 0)?),
+        // 2 * out * KH * KW
+        Op::DepthwiseConv2D { .. } => Some(2 * out? * input_dim(1, 1)? * input_dim(1, 0)?),
+    }
+}
+
+fn estimate_bytes(node: &LinearNode) -> Option<usize> {
+    let written = node.shape.static_size()?;
+    let mut read = 0;
+    for input in &node.inputs {
+        read += input.shape.static_size()?;
+    }
+    Some((read + written) * node.dtype.size_bytes())
+}
+
+pub fn format_report(prog_id: &str, costs: &[NodeCost]) -> String {
+    let fmt = |v: Option<usize>| v.map(|v| v.to_string()).unwrap_or_else(|| "?".to_string());
+    let mut out = String::new();
+    out.push_str(&format!("    Cost report for '{}':\n", prog_id));
+    out.push_str(&format!("      {:<32} {:<16} {:>14} {:>14}\n", "node", "op", "FLOPs", "bytes"));
+
+    let mut total_flops = 0;
+    let mut total_bytes = 0;
+    let mut partial = false;
+    for cost in costs {
+        out.push_str(&format!("      {:<32} {:<16} {:>14} {:>14}\n", cost.id, cost.op, fmt(cost.flops), fmt(cost.bytes)));
+        match (cost.flops, cost.bytes) {
+            (Some(f), Some(b)) => {
+                total_flops += f;
+                total_bytes += b;
+            }
+            _ => partial = true,
+        }
+    }
+
+    let label = if partial { "total (static nodes only)" } else { "total" };
+    out.push_str(&format!("      {:<49} {:>14} {:>14}\n", label, total_flops, total_bytes));
+    out
+}
+
+/// One block per program listing its resolved ports, as printed by `--print-interfaces`.
+///
+/// ```
+/// let opts = sion_flow_rt::CompileOptions::default();
+/// let manifest = std::path::Path::new("tests/programs/print_interfaces/manifest.json");
+/// let artifacts = sion_flow_rt::compile_manifest(manifest, &opts).unwrap();
+/// assert_eq!(sion_flow_rt::report::format_interfaces(&artifacts.interfaces), concat!(
+///     "  Interface of 'summarize':\n",
+///     "    input  grid: f32[2, 3]\n",
+///     "    input  bias: f32[3]\n",
+///     "    output col_sums: f32[3]\n",
+///     "    output flipped: f32[3, 2]\n",
+///     "    output shifted: f32[3]\n",
+/// ));
+/// ```
+pub fn format_interfaces(interfaces: &[ProgramPorts]) -> String {
+    let fmt = |port: &Port| {
+        let dims: Vec<String> = port.shape.dims.iter().map(|d| d.to_c_expr()).collect();
+        format!("{}: {}[{}]", port.name, port.dtype.name(), dims.join(", "))
+    };
+    let mut out = String::new();
+    for interface in interfaces {
+        out.push_str(&format!("  Interface of '{}':\n", interface.program));
+        for port in &interface.inputs {
+            out.push_str(&format!("    input  {}\n", fmt(port)));
+        }
+        for port in &interface.outputs {
+            out.push_str(&format!("    output {}\n", fmt(port)));
+        }
+    }
+    out
+}
@@ -0,0 +1,227 @@
+//! Compile-time evaluation of constant-only subgraphs. A node whose inputs are all F32
+//! Constants is computed here and becomes a Constant itself; constants left without
+//! consumers are dropped. The arithmetic mirrors the generated C: f32 throughout, operands
+//! broadcast against the output shape, reductions accumulate in axis order.
+
+use crate::core::op::Op;
+use crate::core::types::{DataType, Dim, Shape};
+use crate::resolver::ir::ResolvedIR;
+use petgraph::algo::toposort;
+use petgraph::graph::NodeIndex;
+use petgraph::visit::EdgeRef;
+use std::collections::{HashMap, HashSet};
+
+/// Folds every constant-only node in place and returns how many were folded.
+pub fn fold_constants(ir: &mut ResolvedIR) -> usize {
+    let Ok(order) = toposort(&ir.graph, None) else {
+        return 0;
+    };
+
+    let mut values: HashMap<NodeIndex, Vec<f32>> = HashMap::new();
+    let mut folded = HashSet::new();
+    for idx in order {
+        let node = &ir.graph[idx];
+        if let Op::Constant { values: data, .. } = &node.op {
+            if node.dtype == DataType::F32 {
+                values.insert(idx, data.clone());
+            }
+            continue;
+        }
+        if node.dtype != DataType::F32 {
+            continue;
+        }
+
+        let mut incoming: Vec<_> = ir.graph.edges_directed(idx, petgraph::Direction::Incoming).collect();
+        if incoming.is_empty() {
+            continue;
+        }
+        incoming.sort_by(|a, b| a.weight().dst_port.cmp(&b.weight().dst_port));
+        let Some(inputs) = incoming.iter()
+            .map(|edge| Some((values.get(&edge.source())?.as_slice(), &ir.graph[edge.source()].shape)))
+            .collect::<Option<Vec<_>>>() else {
+            continue;
+        };
+
+        if let Some(result) = evaluate(&node.op, &inputs, &node.shape) {
+            values.insert(idx, result);
+            folded.insert(idx);
+        }
+    }
+    if folded.is_empty() {
+        return 0;
+    }
+
+    // Constants (folded or not) still read by a node that was not folded survive, the rest are dead
+    let live: HashSet<NodeIndex> = ir.graph.edge_references()
+        .filter(|edge| !folded.contains(&edge.target()))
+        .map(|edge| edge.source())
+        .collect();
+    ir.graph = ir.graph.filter_map(
+        |idx, node| {
+            let is_folded = folded.contains(&idx);
+            if (is_folded || matches!(node.op, Op::Constant { .. })) && !live.contains(&idx) {
+                return None;
+            }
+            let mut node = node.clone();
+            if is_folded {
+                node.op = Op::Constant { values: values[&idx].clone(), shape: Some(node.shape.dims.clone()) };
+            }
+            Some(node)
+        },
+        |edge_idx, edge| {
+            let (_, target) = ir.graph.edge_endpoints(edge_idx)?;
+            (!folded.contains(&target)).then(|| edge.clone())
+        },
+    );
+    folded.len()
+}
+
+/// Values of `op` over constant inputs (in port order), or None when the op is not folded.
+fn evaluate(op: &Op, inputs: &[(&[f32], &Shape)], out_shape: &Shape) -> Option<Vec<f32>> {
+    let out_dims = static_dims(out_shape)?;
+    let size: usize = out_dims.iter().product();
+    let operand = |i: usize| -> Option<Vec<f32>> {
+        let (data, shape) = inputs.get(i)?;
+        let in_dims = static_dims(shape)?;
+        Some((0..size).map(|flat| data[broadcast_index(&in_dims, &out_dims, flat)]).collect())
+    };
+
+    let result = match op {
+        Op::Add | Op::Sub | Op::Mul | Op::Div | Op::Min | Op::Max | Op::Pow | Op::Mod if inputs.len() == 2 => {
+            let (left, right) = (operand(0)?, operand(1)?);
+            let apply: fn(f32, f32) -> f32 = match op {
+                Op::Add => |a, b| a + b,
+                Op::Sub => |a, b| a - b,
+                Op::Mul => |a, b| a * b,
+                Op::Div => |a, b| a / b,
+                Op::Min => f32::min,
+                Op::Max => f32::max,
+                Op::Mod => |a, b| a % b,
+                _ => f32::powf,
+            };
+            left.iter().zip(&right).map(|(a, b)| apply(*a, *b)).collect()
+        }
+        Op::Lerp if inputs.len() == 3 => {
+            let (a, b, t) = (operand(0)?, operand(1)?, operand(2)?);
+            (0..size).map(|i| a[i] + t[i] * (b[i] - a[i])).collect()
+        }
+        Op::Sin | Op::Abs | Op::Sqrt | Op::Square | Op::Exp | Op::Log | Op::Broadcast { .. } => {
+            let src = operand(0)?;
+            let apply: fn(f32) -> f32 = match op {
+                Op::Sin => f32::sin,
+                Op::Abs => f32::abs,
+                Op::Sqrt => f32::sqrt,
+                Op::Square => |v| v * v,
+                Op::Exp => f32::exp,
+                Op::Log => f32::ln,
+                _ => |v| v,
+            };
+            src.into_iter().map(apply).collect()
+        }
+        // Element order is unchanged, only the shape is reinterpreted
+        Op::Identity | Op::Dropout { .. } | Op::Reshape { .. } | Op::Flatten { .. } => {
+            let (data, _) = inputs.first()?;
+            (data.len() == size).then(|| data.to_vec())?
+        }
+        Op::ReduceSum { axis, init, kahan } => {
+            reduce(inputs.first()?, *axis, |lane| {
+                if *kahan {
+                    let (mut acc, mut comp) = (*init, 0.0f32);
+                    for v in lane {
+                        let y = v - comp;
+                        let t = acc + y;
+                        comp = (t - acc) - y;
+                        acc = t;
+                    }
+                    acc
+                } else {
+                    lane.iter().fold(*init, |acc, v| acc + v)
+                }
+            })?
+        }
+        Op::ReduceVar { axis } | Op::ReduceStd { axis } => {
+            let is_std = matches!(op, Op::ReduceStd { .. });
+            reduce(inputs.first()?, *axis, |lane| {
+                let mean = lane.iter().fold(0.0f32, |acc, v| acc + v) / lane.len() as f32;
+                let var = lane.iter().fold(0.0f32, |acc, v| acc + (v - mean) * (v - mean)) / lane.len() as f32;
+                if is_std { var.sqrt() } else { var }
+            })?
+        }
+        Op::MatMul if inputs.len() == 2 => matmul(inputs[0], inputs[1], size)?,
+        _ => return None,
+    };
+    Some(result)
+}
+
+fn static_dims(shape: &Shape) -> Option<Vec<usize>> {
+    shape.dims.iter().map(|d| match d {
+        Dim::Static(v) => Some(*v),
+        Dim::Variable(_) => None,
+    }).collect()
+}
+
+/// Same mapping as the generated broadcast index: dims align from the right and size-1
+/// input dims have stride 0.
+fn broadcast_index(in_dims: &[usize], out_dims: &[usize], flat: usize) -> usize {
+    let offset = out_dims.len() - in_dims.len().min(out_dims.len());
+    let mut index = 0;
+    let mut in_stride = 1;
+    let mut out_stride = 1;
+    for k in (0..out_dims.len()).rev() {
+        let coord = (flat / out_stride) % out_dims[k];
+        if k >= offset {
+            let dim = in_dims[k - offset];
+            if dim != 1 {
+                index += coord * in_stride;
+            }
+            in_stride *= dim;
+        }
+        out_stride *= out_dims[k];
+    }
+    index
+}
+
+/// Applies `lane_fn` to every lane along `axis`, producing the outputs in row-major order.
+fn reduce(input: &(&[f32], &Shape), axis: usize, lane_fn: impl Fn(&[f32]) -> f32) -> Option<Vec<f32>> {
+    let (data, shape) = input;
+    let dims = static_dims(shape)?;
+    let reduce_dim = *dims.get(axis)?;
+    let outer: usize = dims[..axis].iter().product();
+    let inner: usize = dims[axis + 1..].iter().product();
+    let mut out = Vec::with_capacity(outer * inner);
+    for o in 0..outer {
+        for i in 0..inner {
+            let lane: Vec<f32> = (0..reduce_dim).map(|r| data[o * reduce_dim * inner + r * inner + i]).collect();
+            out.push(lane_fn(&lane));
+        }
+    }
+    Some(out)
+}
+
+/// Batched [.., M, K] x [.., K, N] with equal batch counts, accumulating each dot product
+/// in order like the generated loops.
+fn matmul(left: (&[f32], &Shape), right: (&[f32], &Shape), size: usize) -> Option<Vec<f32>> {
+    let a = static_dims(left.1)?;
+    let b = static_dims(right.1)?;
+    if a.len() < 2 || b.len() < 2 {
+        return None;
+    }
+    let (m, k, n) = (a[a.len() - 2], a[a.len() - 1], b[b.len() - 1]);
+    let batch = size / (m * n).max(1);
+    if left.0.len() != batch * m * k || right.0.len() != batch * k * n {
+        return None;
+    }
+    let mut out = vec![0.0f32; size];
+    for bi in 0..batch {
+        for i in 0..m {
+            for j in 0..n {
+                let mut acc = 0.0f32;
+                for l in 0..k {
+                    acc += left.0[bi * m * k + i * k + l] * right.0[bi * k * n + l * n + j];
+                }
+                out[bi * m * n + i * n + j] = acc;
+            }
+        }
+    }
+    Some(out)
+}
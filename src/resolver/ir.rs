@@ -1,4 +1,4 @@
-use crate::core::types::{Shape, DataType, Port};
+use crate::core::types::{Shape, DataType, Port, SourceLocation};
 use crate::core::op::Op;
 use petgraph::graph::DiGraph;
 
@@ -6,8 +6,31 @@ use petgraph::graph::DiGraph;
 pub struct ResolvedNode {
     pub id: String,
     pub op: Op,
+    /// Shape of the first output port.
     pub shape: Shape,
+    pub outputs: Vec<OutputPort>,
     pub dtype: DataType,
+    pub origin: Option<SourceLocation>,
+    pub metadata: Option<serde_json::Value>,
+}
+
+impl ResolvedNode {
+    /// Index of the output a link reads through `port`. Single-output nodes answer to any
+    /// port name, since links have always named that port freely.
+    pub fn output_index(&self, port: &str) -> Option<usize> {
+        match self.outputs.as_slice() {
+            [_] => Some(0),
+            ports => ports.iter().position(|p| p.name == port),
+        }
+    }
+}
+
+/// One result of a node. Single-output nodes have one port, `output`; a Split has one per
+/// part, named by its index.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutputPort {
+    pub name: String,
+    pub shape: Shape,
 }
 
 #[derive(Debug, Clone)]
@@ -16,9 +39,19 @@ pub struct ResolvedEdge {
     pub dst_port: String,
 }
 
+/// A shape constraint that depends on runtime dims and can only be checked once they are known.
+#[derive(Debug, Clone)]
+pub struct RuntimeAssertion {
+    pub condition: String, // C expression
+    pub message: String,
+}
+
 #[derive(Debug, Clone)]
 pub struct ResolvedIR {
     pub graph: DiGraph<ResolvedNode, ResolvedEdge>,
     pub inputs: Vec<Port>,
     pub outputs: Vec<Port>, // Changed from HashMap for consistency
+    pub assertions: Vec<RuntimeAssertion>,
+    /// Suspicious but valid constructs, reported in the compile log.
+    pub warnings: Vec<String>,
 }
\ No newline at end of file
@@ -1,56 +1,252 @@
+pub mod fold;
 pub mod ir;
 
 use crate::core::types::{Shape, DataType, Dim, Port};
 use crate::inliner::raw_ir::{RawIR};
-use crate::resolver::ir::{ResolvedIR, ResolvedNode, ResolvedEdge};
+use crate::resolver::ir::{OutputPort, ResolvedIR, ResolvedNode, ResolvedEdge, RuntimeAssertion};
 use crate::core::op::Op;
+use crate::core::utils::find_cycle;
 use petgraph::algo::toposort;
 use petgraph::visit::EdgeRef;
 use petgraph::graph::NodeIndex;
 use std::collections::HashMap;
 use anyhow::{Context, anyhow};
 
+/// Shapes of every node resolved before shape inference failed, in topological order.
+/// It rides on the error as context so the caller can print it or write it to a file;
+/// the error message itself only points at it.
+#[derive(Debug, Clone)]
+pub struct ShapeDump {
+    pub failed_node: String,
+    pub failed_op: String,
+    /// Node id, op name, dtype and output shapes of each resolved node.
+    pub rows: Vec<(String, String, DataType, Vec<Shape>)>,
+}
+
+impl ShapeDump {
+    fn new(failed_node: &str, failed_op: &str, graph: &petgraph::graph::DiGraph<ResolvedNode, ResolvedEdge>) -> Self {
+        // Nodes are added in topological order, so the graph's own order is the one to print
+        let rows = graph.node_weights()
+            .map(|n| (n.id.clone(), n.op.name().to_string(), n.dtype, n.outputs.iter().map(|p| p.shape.clone()).collect()))
+            .collect();
+        Self { failed_node: failed_node.to_string(), failed_op: failed_op.to_string(), rows }
+    }
+
+    /// One aligned line per resolved node, ending with the node that failed.
+    pub fn table(&self) -> String {
+        let shape = |s: &Shape| format!("[{}]", s.dims.iter().map(|d| d.to_c_expr()).collect::<Vec<_>>().join(", "));
+        let id_width = self.rows.iter().map(|r| r.0.len()).chain([self.failed_node.len(), 4]).max().unwrap_or(4);
+        let op_width = self.rows.iter().map(|r| r.1.len()).chain([self.failed_op.len(), 2]).max().unwrap_or(2);
+        let mut out = format!("Shapes resolved before '{}' failed:\n", self.failed_node);
+        out.push_str(&format!("  {:id_width$}  {:op_width$}  shape\n", "node", "op"));
+        for (id, op, dtype, shapes) in &self.rows {
+            let shapes: Vec<String> = shapes.iter().map(|s| format!("{}{}", dtype.name(), shape(s))).collect();
+            out.push_str(&format!("  {:id_width$}  {:op_width$}  {}\n", id, op, shapes.join(", ")));
+        }
+        out.push_str(&format!("  {:id_width$}  {:op_width$}  <failed>\n", self.failed_node, self.failed_op));
+        out
+    }
+}
+
+impl std::fmt::Display for ShapeDump {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Shape inference failed for node '{}' ({}); see the shape dump for what was resolved before it",
+            self.failed_node, self.failed_op)
+    }
+}
+
+/// Resolves shapes and dtypes of every node. `input_specs` holds the bound inputs only: an
+/// Input node without a spec is an error, a spec no Input node reads is a warning.
+///
+/// ```
+/// use sion_flow_rt::core::op::Op;
+/// use sion_flow_rt::core::types::{DataType, Dim, Port, Shape};
+/// use sion_flow_rt::inliner::builder::GraphBuilder;
+///
+/// let build = || {
+///     let mut builder = GraphBuilder::new();
+///     let x = builder.input("x", Shape { dims: vec![Dim::Static(4)] });
+///     let y = builder.input("y", Shape { dims: vec![Dim::Static(4)] });
+///     let sum = builder.op(Op::Add, &[x, y]);
+///     builder.output("out", &sum);
+///     builder.build()
+/// };
+///
+/// let (raw, mut input_specs) = build();
+/// input_specs.remove("x");
+/// input_specs.remove("y");
+/// let err = sion_flow_rt::resolver::resolve_module(raw, input_specs).unwrap_err().to_string();
+/// assert!(err.contains("'x', 'y'"), "{}", err);
+///
+/// let (raw, mut input_specs) = build();
+/// let stale = Port { name: "z".to_string(), shape: Shape { dims: vec![Dim::Static(4)] }, dtype: DataType::F32 };
+/// input_specs.insert("z".to_string(), stale);
+/// let resolved = sion_flow_rt::resolver::resolve_module(raw, input_specs).unwrap();
+/// assert!(resolved.warnings.iter().any(|w| w.contains("'z'")), "{:?}", resolved.warnings);
+/// ```
 pub fn resolve_module(
     raw: RawIR,
     input_specs: HashMap<String, Port>,
 ) -> anyhow::Result<ResolvedIR> {
-    let mut resolved_graph = petgraph::graph::DiGraph::new();
+    let mut resolved_graph = petgraph::graph::DiGraph::<ResolvedNode, ResolvedEdge>::new();
     let mut node_map: HashMap<NodeIndex, NodeIndex> = HashMap::new(); 
-    let mut shapes: HashMap<NodeIndex, Shape> = HashMap::new();
+    let mut assertions = Vec::new();
+    let mut warnings = Vec::new();
+
+    // Without a spec an Input would resolve to a rank-0 shape and quietly shrink everything downstream
+    let mut read_inputs: Vec<&str> = raw.graph.node_weights()
+        .filter_map(|n| match &n.op { Op::Input { name } => Some(name.as_str()), _ => None })
+        .collect();
+    read_inputs.sort();
+    read_inputs.dedup();
+    let unbound: Vec<String> = read_inputs.iter()
+        .filter(|name| !input_specs.contains_key(**name))
+        .map(|name| format!("'{}'", name))
+        .collect();
+    if !unbound.is_empty() {
+        return Err(anyhow!("The graph reads unbound inputs {}: link them in the manifest or give them a default", unbound.join(", ")));
+    }
+    let mut unread: Vec<&String> = input_specs.keys().filter(|name| !read_inputs.contains(&name.as_str())).collect();
+    unread.sort();
+    for name in unread {
+        warnings.push(format!("Input spec '{}' is not read by any node, so it is ignored", name));
+    }
+
+    // An empty graph compiles to a function that writes nothing, so every declared output needs a producer
+    if raw.outputs.is_empty() {
+        return Err(anyhow!("Program declares no outputs, so there is nothing to compute"));
+    }
+    for port in &raw.outputs {
+        let produced = raw.graph.node_weights().any(|n| matches!(&n.op, Op::Output { name, .. } if *name == port.name));
+        if !produced {
+            return Err(anyhow!("Output '{}' has no producer: nothing links to 'outputs.{}'", port.name, port.name));
+        }
+    }
 
     let order = toposort(&raw.graph, None)
-        .map_err(|_| anyhow!("Cycle detected in module graph"))?;
+        .map_err(|_| anyhow!("Cycle detected in module graph between nodes: {}", find_cycle(&raw.graph, |n| n.id.clone()).join(", ")))?;
 
     for old_idx in order {
         let raw_node = &raw.graph[old_idx];
-        let op = raw_node.op.clone();
+        let mut op = raw_node.op.clone();
 
         let mut input_shapes = Vec::new();
+        let mut input_dtypes = Vec::new();
+        let mut input_ids = Vec::new();
         let mut incoming_edges: Vec<_> = raw.graph.edges_directed(old_idx, petgraph::Direction::Incoming).collect();
         incoming_edges.sort_by(|a, b| a.weight().dst_port.cmp(&b.weight().dst_port));
+        let ports: Vec<&str> = incoming_edges.iter().map(|e| e.weight().dst_port.as_str()).collect();
+        // Scale and bias are optional, but linking either one asks for both
+        if let Op::LayerNorm { affine, .. } = &mut op {
+            *affine = ports.iter().any(|p| *p == "scale" || *p == "bias");
+        }
+        check_input_ports(&raw_node.id, &op, &ports)?;
         
         for edge in incoming_edges {
             let src_old_idx = edge.source();
             let src_new_idx = node_map.get(&src_old_idx)
                 .ok_or_else(|| anyhow!("Source node not found in map for edge to '{}'", raw_node.id))?;
-            let shape = shapes.get(src_new_idx)
-                .ok_or_else(|| anyhow!("Shape not found for source node of '{}'", raw_node.id))?;
-            input_shapes.push(shape.clone());
+            // Each link reads one output port of its source, with that port's own shape
+            let src_node = &resolved_graph[*src_new_idx];
+            let port = src_node.output_index(&edge.weight().src_port)
+                .ok_or_else(|| anyhow!("Node '{}' ({}) has no output port '{}' for the link to '{}'; its ports are {}",
+                    src_node.id, src_node.op.name(), edge.weight().src_port, raw_node.id,
+                    src_node.outputs.iter().map(|p| format!("'{}'", p.name)).collect::<Vec<_>>().join(", ")))?;
+            input_shapes.push(src_node.outputs[port].shape.clone());
+            input_dtypes.push(resolved_graph[*src_new_idx].dtype);
+            input_ids.push(resolved_graph[*src_new_idx].id.as_str());
+        }
+
+        // A `_` in a Reshape target becomes the input's element count over the other dims
+        let mut reshape_inferred = false;
+        if let Op::Reshape { new_shape } = &mut op
+            && let Some(input) = input_shapes.first()
+            && new_shape.contains(&Dim::Variable(RESHAPE_WILDCARD.to_string())) {
+            let (dims, divisibility) = infer_reshape_wildcard(&raw_node.id, &input.dims, new_shape)
+                .map_err(|e| e.context(ShapeDump::new(&raw_node.id, "Reshape", &resolved_graph)))?;
+            assertions.extend(divisibility);
+            *new_shape = dims;
+            reshape_inferred = true;
         }
 
         let node_shape = infer_shape(&op, &input_shapes, &input_specs)
-            .with_context(|| format!("Shape inference failed for node '{}' ({:?})", raw_node.id, op))?;
-        let node_dtype = DataType::F32;
+            .map_err(|e| e.context(ShapeDump::new(&raw_node.id, op.name(), &resolved_graph)))?;
+
+        // A symbol broadcast against a static size must be that size at runtime
+        if matches!(op, Op::Add | Op::Sub | Op::Mul | Op::Div | Op::Min | Op::Max | Op::Pow
+            | Op::Mod | Op::And | Op::Or | Op::Xor | Op::Shl | Op::Shr | Op::Lerp) {
+            for (name, size) in symbolic_broadcast_assumptions(&input_shapes, &node_shape) {
+                warnings.push(format!("Node '{}' ({}) broadcasts symbolic dim '{}' against {}, so it assumes {} == {}", raw_node.id, op.name(), name, size, name, size));
+                assertions.push(RuntimeAssertion {
+                    condition: format!("({}) == {}", name, size),
+                    message: format!("{} '{}': {} must be {} to broadcast", op.name(), raw_node.id, name, size),
+                });
+            }
+        }
+        // Symbolic split dims are only known at runtime, so divisibility is asserted there
+        if let Op::Split { axis, parts } = &op
+            && let Some(Dim::Variable(name)) = input_shapes.first().and_then(|s| s.dims.get(*axis)) {
+            assertions.push(RuntimeAssertion {
+                condition: format!("({}) % {} == 0", name, parts),
+                message: format!("Split '{}': {} is not divisible by {} parts", raw_node.id, name, parts),
+            });
+        }
+        if let Op::Reshape { new_shape } = &op
+            && !reshape_inferred
+            && let Some(input) = input_shapes.first()
+            && (input.static_size().is_none() || node_shape.static_size().is_none())
+            && product_dim(&input.dims) != product_dim(new_shape) {
+            warnings.push(format!("Reshape '{}' from {:?} to {:?} has symbolic dims, so its element count is not checked", raw_node.id, input.dims, new_shape));
+        }
+        // Compute happens in float, or in double once an F64 value flows in; graph inputs, declared
+        // outputs and index-producing ops carry their own dtype, undeclared outputs keep their
+        // producer's. Binary ops stay in integer arithmetic when both operands share an integer dtype.
+        let node_dtype = match &op {
+            Op::Input { name } => input_specs.get(name).map(|p| p.dtype).unwrap_or(DataType::F32),
+            Op::Output { name, .. } => match raw.outputs.iter().find(|p| &p.name == name).and_then(|p| p.dtype.as_deref()) {
+                Some(dtype_name) => DataType::from_name(dtype_name)
+                    .with_context(|| format!("Invalid dtype for output '{}'", name))?,
+                None => input_dtypes.first().copied().unwrap_or(DataType::F32),
+            },
+            Op::ArgMax { .. } | Op::ArgMin { .. } => DataType::I32,
+            Op::Iota { dtype, .. } => *dtype,
+            Op::And | Op::Or | Op::Xor | Op::Shl | Op::Shr
+                if !input_dtypes.iter().all(|d| d.is_integer()) => {
+                    return Err(anyhow!("Node '{}' ({}) is a bitwise op and needs integer operands, found {:?}", raw_node.id, op.name(), input_dtypes));
+                }
+            Op::Add | Op::Sub | Op::Mul | Op::Div | Op::Min | Op::Max | Op::Pow
+            | Op::Mod | Op::And | Op::Or | Op::Xor | Op::Shl | Op::Shr
+                if input_dtypes.iter().any(|d| d.is_integer()) => match input_dtypes.as_slice() {
+                    [left, right] if left == right => *left,
+                    [left, right] => return Err(anyhow!(
+                        "Node '{}' ({}) mixes operand dtypes: '{}' is {} but '{}' is {}; integer arithmetic needs both operands of the same integer dtype",
+                        raw_node.id, op.name(), input_ids[0], left.name(), input_ids[1], right.name())),
+                    _ => return Err(anyhow!("Node '{}' ({}) mixes operand dtypes {:?}; integer arithmetic needs both operands of the same integer dtype", raw_node.id, op.name(), input_dtypes)),
+                },
+            // Views, copies and rearrangements only move values, so integers keep their dtype
+            // instead of passing through float
+            Op::Transpose { .. } | Op::Flatten { .. } | Op::Identity | Op::Reshape { .. } | Op::Split { .. }
+            | Op::Broadcast { .. } | Op::Window { .. } | Op::Dropout { .. }
+                if input_dtypes.first().is_some_and(|d| d.is_integer()) => input_dtypes[0],
+            // Gathered values keep the data's precision; the indices do not matter
+            Op::GatherND if input_dtypes.first() == Some(&DataType::F64) => DataType::F64,
+            Op::GatherND => DataType::F32,
+            _ if input_dtypes.contains(&DataType::F64) => DataType::F64,
+            _ => DataType::F32,
+        };
 
+        let outputs = output_ports(&op, &node_shape);
         let new_idx = resolved_graph.add_node(ResolvedNode {
             id: raw_node.id.clone(),
             op,
-            shape: node_shape.clone(),
+            shape: node_shape,
+            outputs,
             dtype: node_dtype,
+            origin: raw_node.origin.clone(),
+            metadata: raw_node.metadata.clone(),
         });
 
         node_map.insert(old_idx, new_idx);
-        shapes.insert(new_idx, node_shape);
     }
 
     for edge in raw.graph.edge_references() {
@@ -71,21 +267,22 @@ pub fn resolve_module(
     
     // Сортируем по имени выходного порта для детерминизма
     out_nodes.sort_by(|&a, &b| {
-        let name_a = if let Op::Output { name } = &resolved_graph[a].op { name } else { "" };
-        let name_b = if let Op::Output { name } = &resolved_graph[b].op { name } else { "" };
+        let name_a = if let Op::Output { name, .. } = &resolved_graph[a].op { name } else { "" };
+        let name_b = if let Op::Output { name, .. } = &resolved_graph[b].op { name } else { "" };
         name_a.cmp(name_b)
     });
 
     for idx in out_nodes {
         let node = &resolved_graph[idx];
-        if let Op::Output { name } = &node.op {
+        if let Op::Output { name, .. } = &node.op {
             let mut incoming = resolved_graph.edges_directed(idx, petgraph::Direction::Incoming);
             if let Some(edge) = incoming.next() {
                 let src_node = &resolved_graph[edge.source()];
+                let port = src_node.output_index(&edge.weight().src_port).unwrap_or(0);
                 outputs.push(Port {
                     name: name.clone(),
-                    shape: src_node.shape.clone(),
-                    dtype: src_node.dtype,
+                    shape: src_node.outputs[port].shape.clone(),
+                    dtype: node.dtype,
                 });
             }
         }
@@ -94,16 +291,29 @@ pub fn resolve_module(
     Ok(ResolvedIR {
         graph: resolved_graph,
         inputs: raw.inputs.iter().map(|i| {
+            // Unbound inputs nothing reads still take an argument, which the runtime passes as NULL
             input_specs.get(&i.name).cloned().unwrap_or(Port { 
                 name: i.name.clone(), 
                 shape: Shape { dims: vec![] }, 
-                dtype: DataType::F32 
+                dtype: i.dtype.as_deref().and_then(|d| DataType::from_name(d).ok()).unwrap_or(DataType::F32)
             })
         }).collect(),
         outputs,
+        assertions,
+        warnings,
     })
 }
 
+/// The output ports a node exposes: one per Split part, a single `output` otherwise.
+fn output_ports(op: &Op, shape: &Shape) -> Vec<OutputPort> {
+    match op {
+        Op::Split { parts, .. } => (0..*parts)
+            .map(|part| OutputPort { name: part.to_string(), shape: shape.clone() })
+            .collect(),
+        _ => vec![OutputPort { name: "output".to_string(), shape: shape.clone() }],
+    }
+}
+
 fn infer_shape(
     op: &Op,
     inputs: &[Shape],
@@ -115,10 +325,28 @@ fn infer_shape(
                 .ok_or_else(|| anyhow!("Missing input spec for '{}' in program interface", name))?;
             Ok(spec.shape.clone())
         }
-        Op::Constant { values } => {
-            Ok(Shape { dims: vec![Dim::Static(values.len())] })
+        Op::Constant { values, shape } => {
+            let Some(dims) = shape else {
+                return Ok(Shape { dims: vec![Dim::Static(values.len())] });
+            };
+            let mut count = 1;
+            for dim in dims {
+                match dim {
+                    Dim::Static(v) => count *= v,
+                    Dim::Variable(name) => return Err(anyhow!("Constant shape must be static, found symbolic dim '{}'", name)),
+                }
+            }
+            if count != values.len() {
+                return Err(anyhow!("Constant has {} values but shape {:?} requires {}", values.len(), dims, count));
+            }
+            Ok(Shape { dims: dims.clone() })
+        }
+        Op::RandomUniform { shape, .. } | Op::RandomNormal { shape, .. } => {
+            Ok(Shape { dims: shape.clone() })
         }
-        Op::Add | Op::Sub | Op::Mul | Op::Div | Op::Min | Op::Max | Op::Pow => {
+        Op::Iota { len, .. } => Ok(Shape { dims: vec![len.clone()] }),
+        Op::Add | Op::Sub | Op::Mul | Op::Div | Op::Min | Op::Max | Op::Pow
+        | Op::Mod | Op::And | Op::Or | Op::Xor | Op::Shl | Op::Shr => {
             if inputs.len() == 2 {
                 broadcast_shapes(&inputs[0], &inputs[1])
             } else if inputs.len() == 1 {
@@ -127,14 +355,57 @@ fn infer_shape(
                 Err(anyhow!("Binary op {:?} expects 1 or 2 inputs, found {}", op, inputs.len()))
             }
         }
-        Op::Sin | Op::Abs | Op::Sqrt | Op::Square | Op::Exp | Op::Log | Op::Output { .. } => {
+        Op::Lerp => {
+            if inputs.len() != 3 {
+                return Err(anyhow!("Lerp requires exactly 3 inputs (a, b, t), found {}", inputs.len()));
+            }
+            let ab = broadcast_shapes(&inputs[0], &inputs[1])?;
+            broadcast_shapes(&ab, &inputs[2])
+        }
+        Op::Sin | Op::Abs | Op::Sqrt | Op::Square | Op::Exp | Op::Log | Op::Identity | Op::Dropout { .. } | Op::Output { .. } => {
             if inputs.is_empty() {
                 return Err(anyhow!("Unary/Output op {:?} requires at least 1 input", op));
             }
             Ok(inputs[0].clone())
         }
         Op::Reshape { new_shape } => {
-            Ok(Shape { dims: new_shape.clone() })
+            let reshaped = Shape { dims: new_shape.clone() };
+            // Symbolic counts are only known at runtime; resolve_module warns about those
+            if let Some(input) = inputs.first()
+                && let (Some(from), Some(to)) = (input.static_size(), reshaped.static_size())
+                && from != to {
+                return Err(anyhow!("Reshape from {:?} ({} elements) to {:?} ({} elements) changes the element count", input.dims, from, new_shape, to));
+            }
+            Ok(reshaped)
+        }
+        Op::Broadcast { shape } => {
+            if inputs.is_empty() { return Err(anyhow!("Broadcast requires 1 input")); }
+            let input_dims = &inputs[0].dims;
+            if input_dims.len() > shape.len() {
+                return Err(anyhow!("Broadcast cannot expand rank {} input {:?} to lower rank target {:?}", input_dims.len(), input_dims, shape));
+            }
+            // Input dims align with the trailing target dims and must be 1 or equal to them
+            let lead = shape.len() - input_dims.len();
+            for (axis, dim) in input_dims.iter().enumerate() {
+                if *dim != Dim::Static(1) && *dim != shape[lead + axis] {
+                    return Err(anyhow!("Broadcast cannot expand input shape {:?} to {:?}: axis {} is {:?}, expected 1 or {:?}", input_dims, shape, axis, dim, shape[lead + axis]));
+                }
+            }
+            Ok(Shape { dims: shape.clone() })
+        }
+        Op::Flatten { start_axis, merge_leading } => {
+            if inputs.is_empty() { return Err(anyhow!("Flatten requires 1 input")); }
+            let dims = &inputs[0].dims;
+            if *start_axis > dims.len() {
+                return Err(anyhow!("Flatten start_axis {} out of bounds for rank {}", start_axis, dims.len()));
+            }
+            let mut new_dims = if *merge_leading {
+                vec![product_dim(&dims[..*start_axis])]
+            } else {
+                dims[..*start_axis].to_vec()
+            };
+            new_dims.push(product_dim(&dims[*start_axis..]));
+            Ok(Shape { dims: new_dims })
         }
         Op::Transpose { permutation } => {
             if inputs.is_empty() {
@@ -145,23 +416,49 @@ fn infer_shape(
                 return Err(anyhow!("Transpose permutation length {} doesn't match input rank {}", permutation.len(), input_dims.len()));
             }
             let mut new_dims = Vec::with_capacity(permutation.len());
+            let mut seen = vec![false; permutation.len()];
             for &axis in permutation {
                 if axis >= input_dims.len() {
                     return Err(anyhow!("Transpose axis {} out of bounds for rank {}", axis, input_dims.len()));
                 }
+                if std::mem::replace(&mut seen[axis], true) {
+                    return Err(anyhow!("Transpose permutation {:?} is not a permutation: axis {} appears more than once", permutation, axis));
+                }
                 new_dims.push(input_dims[axis].clone());
             }
             Ok(Shape { dims: new_dims })
         }
-        Op::ReduceSum { axis } => {
-            if inputs.is_empty() { return Err(anyhow!("ReduceSum requires 1 input")); }
+        Op::ReduceSum { axis, .. } | Op::ReduceVar { axis } | Op::ReduceStd { axis }
+        | Op::ArgMax { axis } | Op::ArgMin { axis } => {
+            if inputs.is_empty() { return Err(anyhow!("{} requires 1 input", op.name())); }
             let mut dims = inputs[0].dims.clone();
             if *axis >= dims.len() {
-                return Err(anyhow!("ReduceSum axis {} out of bounds for rank {}", axis, dims.len()));
+                return Err(anyhow!("{} axis {} out of bounds for rank {}", op.name(), axis, dims.len()));
             }
             dims.remove(*axis);
             Ok(Shape { dims })
         }
+        Op::Normalize { axis, .. } => {
+            if inputs.is_empty() { return Err(anyhow!("Normalize requires 1 input")); }
+            if *axis >= inputs[0].dims.len() {
+                return Err(anyhow!("Normalize axis {} out of bounds for rank {}", axis, inputs[0].dims.len()));
+            }
+            Ok(inputs[0].clone())
+        }
+        Op::LayerNorm { axis, .. } => {
+            let data = op.port_index("input").and_then(|i| inputs.get(i))
+                .ok_or_else(|| anyhow!("LayerNorm requires 1 input"))?;
+            if *axis >= data.dims.len() {
+                return Err(anyhow!("LayerNorm axis {} out of bounds for rank {}", axis, data.dims.len()));
+            }
+            for port in ["scale", "bias"] {
+                if let Some(param) = op.port_index(port).and_then(|i| inputs.get(i))
+                    && broadcast_shapes(data, param).ok().as_ref() != Some(data) {
+                    return Err(anyhow!("LayerNorm {} of shape {:?} does not broadcast to the input shape {:?}", port, param.dims, data.dims));
+                }
+            }
+            Ok(data.clone())
+        }
         Op::Split { axis, parts } => {
             if inputs.is_empty() { return Err(anyhow!("Split requires 1 input")); }
             let mut dims = inputs[0].dims.clone();
@@ -183,12 +480,116 @@ fn infer_shape(
             }
             Ok(Shape { dims })
         }
+        Op::Window { axis, size, stride } => {
+            if inputs.is_empty() { return Err(anyhow!("Window requires 1 input")); }
+            let mut dims = inputs[0].dims.clone();
+            if *axis >= dims.len() {
+                return Err(anyhow!("Window axis {} out of bounds for rank {}", axis, dims.len()));
+            }
+            let count = match &dims[*axis] {
+                Dim::Static(val) => {
+                    if val < size {
+                        return Err(anyhow!("Window of size {} does not fit dimension {} at axis {}", size, val, axis));
+                    }
+                    Dim::Static((val - size) / stride + 1)
+                }
+                Dim::Variable(name) => Dim::Variable(format!("(({} - {}) / {} + 1)", name, size, stride)),
+            };
+            dims[*axis] = Dim::Static(*size);
+            dims.insert(0, count);
+            Ok(Shape { dims })
+        }
+        Op::Conv1D { stride, padding } => {
+            if inputs.len() != 2 {
+                return Err(anyhow!("Conv1D requires exactly 2 inputs (input, kernel), found {}", inputs.len()));
+            }
+            let x = &inputs[0].dims;
+            let w = &inputs[1].dims;
+            if x.len() != 2 || w.len() != 3 {
+                return Err(anyhow!("Conv1D expects input [C_in, L] and kernel [C_out, C_in, K], found {:?} and {:?}", x, w));
+            }
+            if let (Dim::Static(a), Dim::Static(b)) = (&x[0], &w[1]) && a != b {
+                return Err(anyhow!("Conv1D input has {} channels but kernel expects {}", a, b));
+            }
+            let l_out = conv_out_dim(&x[1], &w[2], *stride, *padding)?;
+            Ok(Shape { dims: vec![w[0].clone(), l_out] })
+        }
+        Op::DepthwiseConv2D { stride, padding } => {
+            if inputs.len() != 2 {
+                return Err(anyhow!("DepthwiseConv2D requires exactly 2 inputs (input, kernel), found {}", inputs.len()));
+            }
+            let x = &inputs[0].dims;
+            let w = &inputs[1].dims;
+            if x.len() != 3 || w.len() != 3 {
+                return Err(anyhow!("DepthwiseConv2D expects input [C, H, W] and kernel [C, KH, KW], found {:?} and {:?}", x, w));
+            }
+            if let (Dim::Static(a), Dim::Static(b)) = (&x[0], &w[0]) && a != b {
+                return Err(anyhow!("DepthwiseConv2D input has {} channels but kernel has {}", a, b));
+            }
+            let h_out = conv_out_dim(&x[1], &w[1], *stride, *padding)?;
+            let w_out = conv_out_dim(&x[2], &w[2], *stride, *padding)?;
+            Ok(Shape { dims: vec![x[0].clone(), h_out, w_out] })
+        }
+        Op::Resize { scale, .. } => {
+            if inputs.is_empty() { return Err(anyhow!("Resize requires 1 input")); }
+            let dims = &inputs[0].dims;
+            if dims.len() != 3 {
+                return Err(anyhow!("Resize expects a [C, H, W] input, found {:?}", dims));
+            }
+            if scale.0 == 0 || scale.1 == 0 {
+                return Err(anyhow!("Resize scale factors must be positive, found {:?}", scale));
+            }
+            Ok(Shape { dims: vec![
+                dims[0].clone(),
+                product_dim(&[dims[1].clone(), Dim::Static(scale.0)]),
+                product_dim(&[dims[2].clone(), Dim::Static(scale.1)]),
+            ] })
+        }
+        Op::Diag | Op::Trace => {
+            if inputs.is_empty() { return Err(anyhow!("{} requires 1 input", op.name())); }
+            let dims = &inputs[0].dims;
+            if dims.len() != 2 {
+                return Err(anyhow!("{} expects a square [N, N] input, found {:?}", op.name(), dims));
+            }
+            if let (Dim::Static(rows), Dim::Static(cols)) = (&dims[0], &dims[1])
+                && rows != cols {
+                return Err(anyhow!("{} expects a square input, found {}x{}", op.name(), rows, cols));
+            }
+            if matches!(op, Op::Diag) {
+                Ok(Shape { dims: vec![dims[0].clone()] })
+            } else {
+                Ok(Shape { dims: vec![] })
+            }
+        }
+        Op::GatherND => {
+            if inputs.len() != 2 {
+                return Err(anyhow!("GatherND requires exactly 2 inputs (data, indices), found {}", inputs.len()));
+            }
+            let data_rank = inputs[0].dims.len();
+            let indices = &inputs[1].dims;
+            if indices.len() != 2 {
+                return Err(anyhow!("GatherND indices must have shape [N, R], found {:?}", indices));
+            }
+            if indices[1] != Dim::Static(data_rank) {
+                return Err(anyhow!("GatherND index depth {:?} must statically equal the data rank {}", indices[1], data_rank));
+            }
+            Ok(Shape { dims: vec![indices[0].clone()] })
+        }
         Op::MatMul => {
             if inputs.len() != 2 {
                 return Err(anyhow!("MatMul requires exactly 2 inputs, found {}", inputs.len()));
             }
             let a = &inputs[0].dims;
             let b = &inputs[1].dims;
+
+            // Matrix-vector product: [M, K] x [K] -> [M]
+            if a.len() == 2 && b.len() == 1 {
+                if let (Dim::Static(v1), Dim::Static(v2)) = (&a[1], &b[0])
+                    && v1 != v2 {
+                    return Err(anyhow!("Incompatible dimensions for MatMul: inner dims {} and {} do not match", v1, v2));
+                }
+                return Ok(Shape { dims: vec![a[0].clone()] });
+            }
             
             if a.len() < 2 || b.len() < 2 {
                 return Err(anyhow!("MatMul requires inputs with at least 2 dimensions, found shapes {:?} and {:?}", a, b));
@@ -218,7 +619,210 @@ fn infer_shape(
     }
 }
 
+/// Output length of a convolution window sweep: `(len + 2 * padding - kernel) / stride + 1`.
+fn conv_out_dim(len: &Dim, kernel: &Dim, stride: usize, padding: usize) -> anyhow::Result<Dim> {
+    if stride == 0 {
+        return Err(anyhow!("Convolution stride must be positive"));
+    }
+    match (len, kernel) {
+        (Dim::Static(l), Dim::Static(k)) => {
+            if l + 2 * padding < *k {
+                return Err(anyhow!("Kernel size {} exceeds padded input length {}", k, l + 2 * padding));
+            }
+            Ok(Dim::Static((l + 2 * padding - k) / stride + 1))
+        }
+        _ => Ok(Dim::Variable(format!("(({} + {} - {}) / {} + 1)", len.to_c_expr(), 2 * padding, kernel.to_c_expr(), stride))),
+    }
+}
+
+/// Incoming links must land on exactly the op's named ports, one link per port.
+fn check_input_ports(node_id: &str, op: &Op, ports: &[&str]) -> anyhow::Result<()> {
+    let expected = op.input_ports();
+    for port in ports {
+        if !expected.contains(port) {
+            let takes = match expected {
+                [] => "takes no inputs".to_string(),
+                _ => format!("takes {}", expected.iter().map(|p| format!("'{}'", p)).collect::<Vec<_>>().join(", ")),
+            };
+            return Err(anyhow!("Node '{}' ({}): unexpected input on port '{}'; {} {}", node_id, op.name(), port, op.name(), takes));
+        }
+        let links = ports.iter().filter(|p| *p == port).count();
+        if links > 1 {
+            return Err(anyhow!("Node '{}' ({}): port '{}' has {} incoming links, expected one", node_id, op.name(), port, links));
+        }
+    }
+    if let Some(missing) = expected.iter().find(|p| !ports.contains(p)) {
+        return Err(anyhow!("Node '{}' ({}): missing input on port '{}'", node_id, op.name(), missing));
+    }
+    Ok(())
+}
+
+/// Reshape target dim that is inferred from the input's element count.
+const RESHAPE_WILDCARD: &str = "_";
+
+/// Replaces the single `_` of a Reshape target with the input's element count divided by
+/// the other target dims. Symbols on both sides cancel; what remains must divide evenly when
+/// static, otherwise the quotient is returned with a runtime divisibility assertion.
+fn infer_reshape_wildcard(node_id: &str, input: &[Dim], new_shape: &[Dim]) -> anyhow::Result<(Vec<Dim>, Option<RuntimeAssertion>)> {
+    let wildcard = Dim::Variable(RESHAPE_WILDCARD.to_string());
+    let wildcards = new_shape.iter().filter(|d| **d == wildcard).count();
+    if wildcards > 1 {
+        return Err(anyhow!("Reshape '{}' has {} '_' dims in {:?}; only one can be inferred", node_id, wildcards, new_shape));
+    }
+
+    let mut numerator: Vec<Dim> = input.iter().flat_map(product_factors).collect();
+    let mut denominator = Vec::new();
+    for dim in new_shape.iter().filter(|d| **d != wildcard).flat_map(product_factors) {
+        match numerator.iter().position(|d| *d == dim && matches!(d, Dim::Variable(_))) {
+            Some(pos) => { numerator.remove(pos); }
+            None => denominator.push(dim),
+        }
+    }
+    let (num, den) = (product_dim(&numerator), product_dim(&denominator));
+    let num_static: usize = numerator.iter().filter_map(|d| match d {
+        Dim::Static(v) => Some(*v),
+        Dim::Variable(_) => None,
+    }).product();
+    let (inferred, divisibility) = match (&num, &den) {
+        (_, Dim::Static(0)) => {
+            return Err(anyhow!("Reshape '{}' cannot infer '_' in {:?} next to a zero-sized dim", node_id, new_shape));
+        }
+        (Dim::Static(n), Dim::Static(d)) if !n.is_multiple_of(*d) => {
+            return Err(anyhow!("Reshape '{}' cannot infer '_' in {:?}: {} elements of {:?} are not divisible by {}", node_id, new_shape, n, input, d));
+        }
+        (Dim::Static(n), Dim::Static(d)) => (Dim::Static(n / d), None),
+        // Only a static factor divides a symbolic count, which stays exact if it divides evenly
+        (Dim::Variable(_), Dim::Static(d)) if num_static.is_multiple_of(*d) => {
+            let mut dims: Vec<Dim> = numerator.into_iter().filter(|dim| matches!(dim, Dim::Variable(_))).collect();
+            if num_static / d != 1 {
+                dims.insert(0, Dim::Static(num_static / d));
+            }
+            (if dims.len() == 1 { dims.remove(0) } else { product_dim(&dims) }, None)
+        }
+        _ => {
+            let (n, d) = (num.to_c_expr(), den.to_c_expr());
+            (Dim::Variable(format!("({} / {})", n, d)), Some(RuntimeAssertion {
+                condition: format!("({}) % ({}) == 0", n, d),
+                message: format!("Reshape '{}': {} elements are not divisible by {}", node_id, n, d),
+            }))
+        }
+    };
+    let dims = new_shape.iter().map(|d| if *d == wildcard { inferred.clone() } else { d.clone() }).collect();
+    Ok((dims, divisibility))
+}
+
+/// The factors of a `product_dim` expression such as `(2 * N)`; other dims are one factor.
+fn product_factors(dim: &Dim) -> Vec<Dim> {
+    if let Dim::Variable(expr) = dim
+        && let Some(inner) = expr.strip_prefix('(').and_then(|e| e.strip_suffix(')'))
+        && inner.contains(" * ")
+        && !inner.contains(['(', ')', '/', '+', '-']) {
+        return inner.split(" * ")
+            .map(|factor| factor.parse().map(Dim::Static).unwrap_or_else(|_| Dim::Variable(factor.to_string())))
+            .collect();
+    }
+    vec![dim.clone()]
+}
+
+fn product_dim(dims: &[Dim]) -> Dim {
+    let mut static_product = 1;
+    let mut symbols = Vec::new();
+    for dim in dims {
+        match dim {
+            Dim::Static(v) => static_product *= v,
+            Dim::Variable(name) => symbols.push(name.clone()),
+        }
+    }
+    if symbols.is_empty() {
+        return Dim::Static(static_product);
+    }
+    if static_product != 1 {
+        symbols.insert(0, static_product.to_string());
+    }
+    Dim::Variable(format!("({})", symbols.join(" * ")))
+}
+
+/// `...` in a declared shape: any number of leading dims, taken from the shape it meets.
+pub const ELLIPSIS_DIM: &str = "...";
+
+/// Replaces the `...` in `declared` with the dims of `target` it lines up with, keeping the
+/// declared dims on either side, e.g. `[..., 3]` against `[2, 4, 3]` becomes `[2, 4, 3]`.
+/// Shapes without an ellipsis are returned unchanged.
+pub fn expand_ellipsis(declared: &Shape, target: &Shape) -> anyhow::Result<Shape> {
+    let ellipsis = Dim::Variable(ELLIPSIS_DIM.to_string());
+    let Some(pos) = declared.dims.iter().position(|d| *d == ellipsis) else {
+        return Ok(declared.clone());
+    };
+    if declared.dims[pos + 1..].contains(&ellipsis) {
+        return Err(anyhow!("Shape {:?} has more than one '...'", declared.dims));
+    }
+    let fixed = declared.dims.len() - 1;
+    if target.dims.len() < fixed {
+        return Err(anyhow!("Shape {:?} needs at least {} dims but {:?} has {}", declared.dims, fixed, target.dims, target.dims.len()));
+    }
+    let expanded = target.dims.len() - fixed;
+    let mut dims = declared.dims[..pos].to_vec();
+    dims.extend_from_slice(&target.dims[pos..pos + expanded]);
+    dims.extend_from_slice(&declared.dims[pos + 1..]);
+    Ok(Shape { dims })
+}
+
+/// Compares the shape inferred for each output with the one the program declares. Declared
+/// symbols bind to whatever dim they meet, consistently across all outputs; `_` matches any
+/// single dim, `...` any run of dims, size-1 dims are ignored, and an output declared
+/// without a shape is not checked.
+pub fn check_output_shapes(outputs: &[Port], declared: &HashMap<String, Port>) -> anyhow::Result<()> {
+    let fmt = |shape: &Shape| format!("[{}]", shape.dims.iter().map(|d| d.to_c_expr()).collect::<Vec<_>>().join(", "));
+    let mut bindings: HashMap<String, Dim> = HashMap::new();
+    for output in outputs {
+        let Some(expected) = declared.get(&output.name).filter(|p| !p.shape.dims.is_empty()) else {
+            continue;
+        };
+        let mismatch = || anyhow!("Output '{}' is declared as {} but the graph produces {}", output.name, fmt(&expected.shape), fmt(&output.shape));
+        // Size-1 dims change neither the element count nor the layout, so `[1]` is a scalar
+        let significant = |shape: &Shape| shape.dims.iter().filter(|d| **d != Dim::Static(1)).cloned().collect::<Vec<_>>();
+        let want_dims = significant(&expand_ellipsis(&expected.shape, &output.shape).map_err(|_| mismatch())?);
+        let got_dims = significant(&output.shape);
+        if want_dims.len() != got_dims.len() {
+            return Err(mismatch());
+        }
+        for (want, got) in want_dims.iter().zip(&got_dims) {
+            let matches = match want {
+                Dim::Variable(name) if name == "dynamic" => true,
+                Dim::Variable(name) => *bindings.entry(name.clone()).or_insert_with(|| got.clone()) == *got,
+                Dim::Static(_) => want == got,
+            };
+            if !matches {
+                return Err(mismatch());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Symbols in `inputs` that broadcast against a static dim other than 1 in `out`, paired
+/// with the size they are assumed to have.
+fn symbolic_broadcast_assumptions(inputs: &[Shape], out: &Shape) -> Vec<(String, usize)> {
+    let mut pinned = Vec::new();
+    for input in inputs {
+        let offset = out.dims.len().saturating_sub(input.dims.len());
+        for (dim, out_dim) in input.dims.iter().zip(&out.dims[offset..]) {
+            if let (Dim::Variable(name), Dim::Static(v)) = (dim, out_dim)
+                && *v != 1
+                && !pinned.contains(&(name.clone(), *v)) {
+                pinned.push((name.clone(), *v));
+            }
+        }
+    }
+    pinned
+}
+
 fn broadcast_shapes(a: &Shape, b: &Shape) -> anyhow::Result<Shape> {
+    // An operand with `...` takes its missing leading dims from the other one; when the
+    // other is too short, the dims it lacks count as 1 and the ellipsis expands to nothing
+    let expand = |shape: &Shape, other: &Shape| expand_ellipsis(shape, other)
+        .or_else(|_| expand_ellipsis(shape, &Shape { dims: vec![Dim::Static(1); shape.dims.len() - 1] }));
+    let (a, b) = (&expand(a, b)?, &expand(b, a)?);
     let mut out_dims = Vec::new();
     let len_a = a.dims.len();
     let len_b = b.dims.len();
@@ -236,7 +840,14 @@ fn broadcast_shapes(a: &Shape, b: &Shape) -> anyhow::Result<Shape> {
                 else { return Err(anyhow!("Shape mismatch for broadcast: {} and {}", va, vb)); }
             }
             (Dim::Variable(sa), Dim::Variable(sb)) if sa == sb => out_dims.push(Dim::Variable(sa.clone())),
-            (Dim::Variable(s), _) | (_, Dim::Variable(s)) => out_dims.push(Dim::Variable(s.clone())),
+            // Distinct symbols may still be equal at runtime, but nothing says so; parameters
+            // declared as aliases already share one name by now
+            (Dim::Variable(sa), Dim::Variable(sb)) => {
+                return Err(anyhow!("Shape mismatch for broadcast: symbolic dims '{}' and '{}' are not known to be equal", sa, sb));
+            }
+            (Dim::Variable(s), Dim::Static(1)) | (Dim::Static(1), Dim::Variable(s)) => out_dims.push(Dim::Variable(s.clone())),
+            // The static size wins; `symbolic_broadcast_assumptions` reports the symbol it pins
+            (Dim::Variable(_), Dim::Static(v)) | (Dim::Static(v), Dim::Variable(_)) => out_dims.push(Dim::Static(*v)),
         }
     }
     Ok(Shape { dims: out_dims })
@@ -0,0 +1,36 @@
+#ifndef SION_API_H
+#define SION_API_H
+
+#include <stddef.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Return codes of the sion_* functions */
+#define SION_OK 0
+#define SION_ERR_INVALID_ARGUMENT (-1)
+#define SION_ERR_UNKNOWN_PORT (-2)
+#define SION_ERR_SIZE_MISMATCH (-3)
+
+typedef struct sion_ctx sion_ctx;
+
+/* The runtime is a single global instance: returns NULL while another context is alive. */
+sion_ctx* sion_create(void);
+
+/* Copies `count` values into the buffer behind a manifest address ("prog.port" or "sources.name"). */
+int sion_set_input(sion_ctx* ctx, const char* name, const float* data, size_t count);
+
+/* Runs every program once, in execution order. */
+int sion_run(sion_ctx* ctx);
+
+/* Copies `count` values out of the buffer behind a manifest address ("prog.port" or "sources.name"). */
+int sion_get_output(sion_ctx* ctx, const char* name, float* data, size_t count);
+
+void sion_destroy(sion_ctx* ctx);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,213 @@
+//! Checks on the generated C sources of small graphs and fixtures, where the emitted text itself is
+//! the behaviour under test rather than the values a test runner reads back.
+
+use sion_flow_rt::codegen::CodegenOptions;
+use sion_flow_rt::core::op::Op;
+use sion_flow_rt::core::types::{Dim, Shape};
+use sion_flow_rt::inliner::builder::GraphBuilder;
+use sion_flow_rt::linearizer::ir::LinearIR;
+use std::path::Path;
+
+/// Static shape from its dims.
+fn shape(dims: &[usize]) -> Shape {
+    Shape { dims: dims.iter().map(|&d| Dim::Static(d)).collect() }
+}
+
+/// Resolves and linearizes the graph `build` adds to, then generates its module `graph`.
+fn graph_source(opts: &CodegenOptions, build: impl FnOnce(&mut GraphBuilder)) -> (LinearIR, String) {
+    let mut builder = GraphBuilder::new();
+    build(&mut builder);
+    let (raw, input_specs) = builder.build();
+    let resolved = sion_flow_rt::resolver::resolve_module(raw, input_specs).unwrap();
+    let linear = sion_flow_rt::linearizer::linearize(resolved).unwrap();
+    let source = sion_flow_rt::codegen::generate_module_source("graph", &linear, opts);
+    (linear, source)
+}
+
+/// Generated `<program>.c` of a fixture under `tests/programs`, compiled in memory.
+fn fixture_source(fixture: &str, program: &str) -> String {
+    fixture_file(fixture, &format!("{}.c", program), &sion_flow_rt::CompileOptions::default())
+}
+
+/// One generated file of a fixture under `tests/programs`, compiled in memory with `opts`.
+fn fixture_file(fixture: &str, name: &str, opts: &sion_flow_rt::CompileOptions) -> String {
+    let manifest = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/programs").join(fixture).join("manifest.json");
+    let artifacts = sion_flow_rt::compile_manifest(&manifest, opts).unwrap();
+    artifacts.file(name).unwrap().contents.clone()
+}
+
+/// `(id, op)` of every `// node:` comment, in emission order.
+fn node_comments(source: &str) -> Vec<(String, String)> {
+    source.lines()
+        .filter_map(|line| line.trim().strip_prefix("// node: "))
+        .map(|rest| {
+            let (id, rest) = rest.split_once(" (").unwrap();
+            (id.to_string(), rest.split_once(')').unwrap().0.to_string())
+        })
+        .collect()
+}
+
+#[test]
+fn constant_only_graph_keeps_final_constants_and_output_copies() {
+    let source = fixture_source("constant_folding", "folded");
+    let expected = [
+        ("row_sum", "Constant"),
+        ("outputs.sums", "Output"),
+        ("mixed", "Constant"),
+        ("outputs.product", "Output"),
+    ].map(|(id, op)| (id.to_string(), op.to_string()));
+    assert_eq!(node_comments(&source), expected, "{}", source);
+    assert_eq!(source.matches("static const float ").count(), 2, "{}", source);
+}
+
+#[test]
+fn folded_infinities_use_c_macros() {
+    let source = fixture_source("fold_non_finite", "non_finite");
+    assert!(source.contains("INFINITY, 1.0f"), "{}", source);
+    assert!(source.contains("-INFINITY, 0.0f"), "{}", source);
+    assert!(!source.contains("inff"), "{}", source);
+}
+
+#[test]
+fn flatten_view_takes_its_source_buffer_type() {
+    let source = fixture_source("integer_views", "integer_views");
+    assert!(source.contains("const int32_t* flat = sum;"), "{}", source);
+}
+
+#[test]
+fn opencl_kernels_qualify_every_local_pointer() {
+    let opts = sion_flow_rt::CompileOptions {
+        backend: Some(sion_flow_rt::codegen::Backend::OpenCl),
+        ..Default::default()
+    };
+    let source = fixture_file("argmax_i32", "argmax_i32.c", &opts);
+    assert!(source.contains("__global const int32_t* lane = in_scores"), "{}", source);
+    assert!(!source.contains("\"        const int32_t* lane"), "{}", source);
+}
+
+#[test]
+fn each_node_comment_heads_its_own_loop() {
+    let (linear, source) = graph_source(&CodegenOptions::default(), |builder| {
+        let x = builder.input("x", shape(&[4, 16]));
+        let wave = builder.named_op("wave", Op::Sin, std::slice::from_ref(&x));
+        let shifted = builder.named_op("shifted", Op::Add, &[wave, x]);
+        let total = builder.named_op("total", Op::Square, &[shifted]);
+        builder.output("out", &total);
+    });
+
+    for node in linear.nodes.iter().filter(|n| !matches!(n.op, Op::Input { .. })) {
+        let header = format!("    // node: {} ({})", node.id, node.op.name());
+        let start = source.find(&header).unwrap_or_else(|| panic!("no comment for {}:\n{}", node.id, source));
+        if matches!(node.op, Op::Output { .. }) {
+            continue;
+        }
+        // The loop writing the node sits between its comment and the next node's
+        let block = &source[start + header.len()..];
+        let block = &block[..block.find("// node: ").unwrap_or(block.len())];
+        assert!(block.contains("for (") && block.contains(&format!("{}[i] =", node.id)), "{}:{}", node.id, block);
+    }
+}
+
+#[test]
+fn small_integer_pow_expands_up_to_the_limit() {
+    let pow_source = |exponent: f32| graph_source(&CodegenOptions::default(), |builder| {
+        let x = builder.input("x", shape(&[16]));
+        let power = builder.op(Op::Constant { values: vec![exponent], shape: None }, &[]);
+        let raised = builder.named_op("raised", Op::Pow, &[x, power]);
+        builder.output("out", &raised);
+    }).1;
+
+    let limit = sion_flow_rt::codegen::POW_EXPANSION_MAX_EXPONENT;
+    let cubed = pow_source(3.0);
+    assert!(cubed.contains("raised[i] = (in_x[i] * in_x[i] * in_x[i]);"), "{}", cubed);
+    assert!(!cubed.contains("powf ("), "{}", cubed);
+
+    let at_limit = pow_source(limit as f32);
+    assert!(at_limit.contains(&format!("raised[i] = ({});", vec!["in_x[i]"; limit as usize].join(" * "))), "{}", at_limit);
+
+    // Above the limit the pow call stays
+    let above = pow_source((limit + 1) as f32);
+    assert!(above.contains("raised[i] = powf (in_x[i], "), "{}", above);
+    assert!(!above.contains("in_x[i] * in_x[i]"), "{}", above);
+}
+
+#[test]
+fn matmul_conv_and_transpose_nests_collapse_two_loops() {
+    let source = |op: Op, input_shapes: &[&[usize]]| graph_source(
+        // Keeps the pragmas on these small shapes
+        &CodegenOptions { parallel_threshold: 0, ..Default::default() },
+        |builder| {
+            let inputs: Vec<_> = input_shapes.iter().enumerate()
+                .map(|(i, dims)| builder.input(&format!("x{}", i), shape(dims)))
+                .collect();
+            let result = builder.op(op, &inputs);
+            builder.output("out", &result);
+        },
+    ).1;
+
+    let nests = [
+        source(Op::MatMul, &[&[4, 4], &[4, 4]]),
+        source(Op::Conv1D { stride: 1, padding: 0 }, &[&[2, 8], &[3, 2, 3]]),
+        source(Op::DepthwiseConv2D { stride: 1, padding: 0 }, &[&[2, 4, 4], &[2, 2, 2]]),
+        source(Op::Transpose { permutation: vec![2, 0, 1] }, &[&[2, 3, 4]]),
+    ];
+    for nest in &nests {
+        assert!(nest.contains("#pragma omp parallel for collapse(2)"), "{}", nest);
+    }
+}
+
+#[test]
+fn compiled_module_declares_and_defines_its_function() {
+    let (linear, _) = graph_source(&CodegenOptions::default(), |builder| {
+        let x = builder.input("x", shape(&[4]));
+        let y = builder.op(Op::Sin, &[x]);
+        builder.output("y", &y);
+    });
+    let module = sion_flow_rt::codegen::compile_module("wave", &linear, &CodegenOptions::default());
+
+    let signature = "void wave_func(void** workspace, const float* restrict in_x, float* restrict out_y)";
+    assert!(module.c_source.contains(&format!("{} {{", signature)), "{}", module.c_source);
+    assert!(module.h_source.contains(&format!("{};", signature)), "{}", module.h_source);
+}
+
+#[test]
+fn bench_mode_times_program_calls() {
+    let runtime = |bench_iterations| {
+        let opts = sion_flow_rt::CompileOptions { bench_iterations, ..Default::default() };
+        fixture_file("window", "runtime.c", &opts)
+    };
+    assert!(runtime(10).contains("clock_gettime(CLOCK_MONOTONIC"));
+    assert!(!runtime(0).contains("clock_gettime"));
+}
+
+#[test]
+fn debug_runtime_asserts_symbolic_split_divisibility() {
+    let runtime = |debug_runtime| {
+        let opts = sion_flow_rt::CompileOptions { debug_runtime, ..Default::default() };
+        fixture_file("split_symbolic", "runtime.c", &opts)
+    };
+    let check = r#"assert(((N) % 2 == 0) && "Split 'halves': N is not divisible by 2 parts");"#;
+    assert!(runtime(true).contains(check));
+    assert!(!runtime(false).contains("assert("));
+}
+
+#[test]
+fn constant_table_is_one_static_array() {
+    let source = fixture_source("constant_table", "ramp");
+    assert_eq!(source.matches("static const float ramp_table_data[100] = {").count(), 1, "{}", source);
+    assert!(!source.contains("table[0] ="), "{}", source);
+    assert!(!source.contains("table[i] ="), "{}", source);
+    assert!(source.contains("const float* restrict table = ramp_table_data;"), "{}", source);
+}
+
+#[test]
+fn sdl2_runtime_opens_the_display_window() {
+    let runtime = |runtime| {
+        let opts = sion_flow_rt::CompileOptions { runtime, ..Default::default() };
+        fixture_file("display_gradient", "runtime.c", &opts)
+    };
+    let sdl2 = runtime(sion_flow_rt::linker::RuntimeTarget::Sdl2);
+    assert!(sdl2.contains("SDL_Init(SDL_INIT_VIDEO)"), "{}", sdl2);
+    assert!(sdl2.contains("SDL_CreateWindow("), "{}", sdl2);
+    assert!(!runtime(sion_flow_rt::linker::RuntimeTarget::default()).contains("SDL_Init"));
+}
@@ -0,0 +1,191 @@
+//! Runs the JSON fixtures through the compiler binary. Every `tests/programs/<name>` manifest
+//! must compile and pass its own `tests`, and every `tests/errors/<name>` manifest must fail
+//! with output containing its `expected_error.txt` (after the flags in an optional `args.txt`).
+//! Each run writes into its own temporary `--out-dir`, so fixtures compile in parallel.
+//! Compiling a program fixture twice in-process must also give byte-identical sources, and the
+//! MatMul fixtures must still pass with `--blas` when an OpenBLAS install is found. A
+//! `--keep-going` run that fails must still write its `--report-json`, and headers moved to
+//! an `include_dir` must still be found when the test runner is built.
+
+use std::path::{Path, PathBuf};
+use std::process::Output;
+
+/// Fixture directories under `tests/<kind>` that hold a manifest, sorted by name.
+fn fixture_dirs(kind: &str) -> Vec<PathBuf> {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join(kind);
+    let mut dirs: Vec<PathBuf> = std::fs::read_dir(&root)
+        .unwrap_or_else(|e| panic!("Cannot list {}: {}", root.display(), e))
+        .map(|entry| entry.unwrap().path())
+        .filter(|dir| dir.join("manifest.json").exists())
+        .collect();
+    dirs.sort();
+    dirs
+}
+
+/// Compiles one fixture with `--test` into a scratch output directory.
+fn run_compiler(kind: &str, dir: &Path, extra_args: &[String]) -> Output {
+    let name = dir.file_name().unwrap().to_string_lossy();
+    let out_dir = std::env::temp_dir().join(format!("sionflow_{}_{}_{}", kind, name, std::process::id()));
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_SionFlowRT"))
+        .arg(dir.join("manifest.json"))
+        .arg("--test")
+        .arg("--out-dir")
+        .arg(&out_dir)
+        .args(extra_args)
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .output()
+        .expect("Failed to start the compiler binary");
+    let _ = std::fs::remove_dir_all(&out_dir);
+    output
+}
+
+/// Program fixtures whose MatMuls `--blas` lowers to `cblas_sgemm`.
+const MATMUL_FIXTURES: &[&str] = &["const_matmul", "matmul_accumulator", "matmul_batch_broadcast", "matvec"];
+
+/// Whether gcc can build a `cblas_sgemm` call against the default `openblas` library.
+fn blas_available() -> bool {
+    let dir = std::env::temp_dir().join(format!("sionflow_blas_probe_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let probe = dir.join("probe.c");
+    std::fs::write(&probe, concat!(
+        "#include <cblas.h>\n",
+        "int main(void) {\n",
+        "    float a = 2.0f, b = 3.0f, c = 0.0f;\n",
+        "    cblas_sgemm(CblasRowMajor, CblasNoTrans, CblasNoTrans, 1, 1, 1, 1.0f, &a, 1, &b, 1, 0.0f, &c, 1);\n",
+        "    return c == 6.0f ? 0 : 1;\n",
+        "}\n",
+    )).unwrap();
+    let built = std::process::Command::new("gcc")
+        .arg(&probe)
+        .arg("-o")
+        .arg(dir.join("probe"))
+        .arg("-lopenblas")
+        .output()
+        .is_ok_and(|output| output.status.success());
+    let _ = std::fs::remove_dir_all(&dir);
+    built
+}
+
+fn combined_output(output: &Output) -> String {
+    format!("{}{}", String::from_utf8_lossy(&output.stdout), String::from_utf8_lossy(&output.stderr))
+}
+
+/// Checks every fixture of `kind` on a few worker threads and panics with all failures at once.
+fn check_fixtures(kind: &str, check: impl Fn(&Path) -> Result<(), String> + Sync) {
+    let dirs = fixture_dirs(kind);
+    assert!(!dirs.is_empty(), "No fixtures found under tests/{}", kind);
+    let workers = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+    let chunk = dirs.len().div_ceil(workers);
+    let failures: Vec<String> = std::thread::scope(|scope| {
+        let handles: Vec<_> = dirs.chunks(chunk)
+            .map(|part| scope.spawn(|| part.iter().filter_map(|dir| check(dir).err()).collect::<Vec<_>>()))
+            .collect();
+        handles.into_iter().flat_map(|h| h.join().unwrap()).collect()
+    });
+    assert!(failures.is_empty(), "{} of {} fixtures under tests/{} failed:\n\n{}", failures.len(), dirs.len(), kind, failures.join("\n\n"));
+}
+
+#[test]
+fn programs_pass_their_tests() {
+    check_fixtures("programs", |dir| {
+        let output = run_compiler("programs", dir, &[]);
+        let text = combined_output(&output);
+        if output.status.success() && text.contains("All tests passed successfully!") {
+            Ok(())
+        } else {
+            Err(format!("{}:\n{}", dir.display(), text))
+        }
+    });
+}
+
+#[test]
+fn errors_report_expected_message() {
+    check_fixtures("errors", |dir| {
+        let expected = std::fs::read_to_string(dir.join("expected_error.txt"))
+            .map_err(|e| format!("{}: cannot read expected_error.txt: {}", dir.display(), e))?;
+        let args: Vec<String> = std::fs::read_to_string(dir.join("args.txt"))
+            .map(|text| text.split_whitespace().map(str::to_string).collect())
+            .unwrap_or_default();
+        let output = run_compiler("errors", dir, &args);
+        let text = combined_output(&output);
+        if !output.status.success() && text.contains(expected.trim_end()) {
+            Ok(())
+        } else {
+            Err(format!("{}: expected a failure containing {:?}, got:\n{}", dir.display(), expected.trim_end(), text))
+        }
+    });
+}
+
+#[test]
+fn programs_compile_deterministically() {
+    check_fixtures("programs", |dir| {
+        let opts = sion_flow_rt::CompileOptions::default();
+        let compile = || sion_flow_rt::compile_manifest(&dir.join("manifest.json"), &opts)
+            .map_err(|e| format!("{}: {:#}", dir.display(), e));
+        let (first, second) = (compile()?, compile()?);
+        if first.files.len() != second.files.len() {
+            return Err(format!("{}: the two compilations produced different file lists", dir.display()));
+        }
+        for (a, b) in first.files.iter().zip(&second.files) {
+            if a.path != b.path || a.contents != b.contents {
+                return Err(format!("{}: {} differs between two compilations", dir.display(), a.path.display()));
+            }
+        }
+        Ok(())
+    });
+}
+
+#[test]
+fn matmul_programs_pass_with_blas() {
+    if !blas_available() {
+        eprintln!("Skipping the --blas fixtures: no cblas.h and libopenblas found");
+        return;
+    }
+    check_fixtures("programs", |dir| {
+        if !MATMUL_FIXTURES.iter().any(|name| dir.ends_with(name)) {
+            return Ok(());
+        }
+        let output = run_compiler("blas", dir, &["--blas".to_string()]);
+        let text = combined_output(&output);
+        if output.status.success() && text.contains("All tests passed successfully!") {
+            Ok(())
+        } else {
+            Err(format!("{} with --blas:\n{}", dir.display(), text))
+        }
+    });
+}
+
+#[test]
+fn keep_going_still_writes_the_report() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/errors/keep_going");
+    let report_path = std::env::temp_dir().join(format!("sionflow_keep_going_report_{}.json", std::process::id()));
+    let output = run_compiler("report", &dir, &["--keep-going".to_string(), "--report-json".to_string(), report_path.display().to_string()]);
+    assert!(!output.status.success(), "{}", combined_output(&output));
+
+    let text = std::fs::read_to_string(&report_path).unwrap_or_else(|e| panic!("No report written: {}\n{}", e, combined_output(&output)));
+    let _ = std::fs::remove_file(&report_path);
+    let report: sion_flow_rt::report::json::CompileReport = serde_json::from_str(&text).unwrap();
+    let programs: Vec<&str> = report.programs.iter().map(|p| p.id.as_str()).collect();
+    let failed: Vec<&str> = report.failures.iter().map(|f| f.program.as_str()).collect();
+    assert_eq!(programs, ["good"]);
+    assert_eq!(failed, ["broken"]);
+}
+
+#[test]
+fn include_dir_holds_the_headers_and_still_builds() {
+    let root = std::env::temp_dir().join(format!("sionflow_include_dir_{}", std::process::id()));
+    let opts = sion_flow_rt::CompileOptions {
+        test: true,
+        out_dir: root.join("src"),
+        include_dir: Some(root.join("include")),
+        ..Default::default()
+    };
+    let manifest = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/programs/print_interfaces/manifest.json");
+    let artifacts = sion_flow_rt::compile_manifest(&manifest, &opts).unwrap();
+    artifacts.write().unwrap();
+    assert!(root.join("include/summarize.h").exists());
+    assert!(!root.join("src/summarize.h").exists());
+    let built = sion_flow_rt::build_and_run(&artifacts, &opts);
+    let _ = std::fs::remove_dir_all(&root);
+    built.unwrap();
+}